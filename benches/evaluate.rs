@@ -0,0 +1,131 @@
+//! Evaluation time and memory of the tree-based stepper versus the arena.
+//!
+//! ```sh
+//! # the programs of examples/ are generated with python3 and measured too
+//! cargo bench --bench evaluate
+//! # also measure other programs
+//! python examples/reverse.py > reverse.lam
+//! cargo bench --bench evaluate -- reverse.lam
+//! ```
+
+use std::{
+    env, fs::read_to_string, hint::black_box, mem::size_of, path::Path, process::Command,
+    time::Instant,
+};
+
+use interpreter::{
+    arena::{Arena, Node},
+    ast::Expr,
+    evaluate::{try_step, Outcome},
+    parser::parse,
+    typecheck::type_check,
+};
+
+/// `let x0 : num = 0 in let x1 : num = x0 + 1 in ... in x{n-1}`
+fn lets(n: usize) -> String {
+    let mut program = String::from("let x0 : num = 0 in ");
+    for i in 1..n {
+        program += &format!("let x{i} : num = x{} + {i} in ", i - 1);
+    }
+    program + &format!("x{}", n - 1)
+}
+
+/// Recursion that keeps a growing pending computation around
+fn sum(n: usize) -> String {
+    format!(
+        "letrec sum : num -> num = fun (n : num) -> (if n < 1 then 0 else n + (sum (n - 1))) in sum {n}"
+    )
+}
+
+/// The program `examples/{name}.py` prints, or `None` if the script fails to run
+fn example(name: &str) -> Option<String> {
+    let output = Command::new("python3")
+        .arg(format!("{name}.py"))
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples"))
+        .output()
+        .ok()?;
+    match output.status.success() {
+        true => String::from_utf8(output.stdout).ok(),
+        false => None,
+    }
+}
+
+/// Number of tree nodes, counted by copying into a scratch arena
+fn nodes(e: &Expr) -> usize {
+    let mut scratch = Arena::new();
+    scratch.from_expr(e);
+    scratch.len()
+}
+
+fn tree(ast: &Expr, measure: bool) -> (Expr, usize) {
+    let (mut e, mut peak) = (ast.clone(), 0);
+    loop {
+        if measure {
+            peak = peak.max(nodes(&e));
+        }
+        match try_step(&e) {
+            Outcome::Step(next) => e = next,
+            Outcome::Value => return (e, peak * size_of::<Expr>()),
+            Outcome::Blame(blame) => panic!("{blame}"),
+        }
+    }
+}
+
+fn arena(ast: &Expr) -> (Expr, usize) {
+    let mut arena = Arena::new();
+    let root = arena.from_expr(ast);
+    let value = arena.eval(root).unwrap();
+    (arena.to_expr(value), arena.peak_bytes())
+}
+
+fn time<T>(f: impl Fn() -> T) -> f64 {
+    let iterations = 3;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    (start.elapsed() / iterations).as_secs_f64() * 1000.0
+}
+
+fn bench(name: &str, program: &str) {
+    let ast = parse(program).unwrap();
+    if let Err(error) = type_check(&ast) {
+        return println!("{name:<20} skipped: {error}");
+    }
+    let (_, tree_bytes) = tree(&ast, true);
+    let (_, arena_bytes) = arena(&ast);
+    println!(
+        "{name:<20} {:>6} nodes | tree {:>10.3} ms {:>8} KiB peak | arena {:>10.3} ms {:>8} KiB peak",
+        nodes(&ast),
+        time(|| tree(&ast, false)),
+        tree_bytes / 1024,
+        time(|| arena(&ast)),
+        arena_bytes / 1024,
+    );
+}
+
+fn main() {
+    println!(
+        "node size: tree {} bytes, arena {} bytes",
+        size_of::<Expr>(),
+        size_of::<Node>()
+    );
+    for n in [100, 200, 400] {
+        bench(&format!("lets/{n}"), &lets(n));
+    }
+    for n in [25, 50, 100] {
+        bench(&format!("sum/{n}"), &sum(n));
+    }
+    for name in ["linkedlst", "queue", "reverse", "tuple"] {
+        match example(name) {
+            Some(program) => bench(&format!("examples/{name}"), &program),
+            None => println!(
+                "{:<20} skipped: python3 examples/{name}.py failed",
+                format!("examples/{name}")
+            ),
+        }
+    }
+    for path in env::args().skip(1).filter(|arg| !arg.starts_with("--")) {
+        bench(&path, &read_to_string(&path).unwrap());
+    }
+}
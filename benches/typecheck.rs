@@ -0,0 +1,76 @@
+//! Type checking throughput on programs with many variables in scope.
+//!
+//! ```sh
+//! # the programs of examples/ are generated with python3 and timed too
+//! cargo bench --bench typecheck
+//! # also time other programs
+//! python examples/linkedlst.py > linkedlst.lam
+//! cargo bench --bench typecheck -- linkedlst.lam
+//! ```
+
+use std::{env, fs::read_to_string, hint::black_box, path::Path, process::Command, time::Instant};
+
+use interpreter::{parser::parse, typecheck::type_check};
+
+/// `let x0 : num = 0 in let x1 : num = x0 + 1 in ... in x{n-1}`
+fn lets(n: usize) -> String {
+    let mut program = String::from("let x0 : num = 0 in ");
+    for i in 1..n {
+        program += &format!("let x{i} : num = x{} + {i} in ", i - 1);
+    }
+    program + &format!("x{}", n - 1)
+}
+
+/// `fun (x0 : num) -> ... -> fun (x{n-1} : num) -> (x0 + x1) * (x2 + x3) ...`
+fn lambdas(n: usize) -> String {
+    let binders: String = (0..n).map(|i| format!("fun (x{i} : num) -> ")).collect();
+    let body: Vec<String> = (0..n)
+        .step_by(2)
+        .map(|i| format!("(x{i} + x{})", (i + 1) % n))
+        .collect();
+    binders + &body.join(" * ")
+}
+
+/// The program `examples/{name}.py` prints, or `None` if the script fails to run
+fn example(name: &str) -> Option<String> {
+    let output = Command::new("python3")
+        .arg(format!("{name}.py"))
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples"))
+        .output()
+        .ok()?;
+    match output.status.success() {
+        true => String::from_utf8(output.stdout).ok(),
+        false => None,
+    }
+}
+
+fn bench(name: &str, program: &str) {
+    let ast = parse(program).unwrap();
+    type_check(&ast).unwrap();
+    let iterations = 20;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(type_check(black_box(&ast)).unwrap());
+    }
+    let elapsed = start.elapsed() / iterations;
+    println!("{name:<24} {:>10.3} ms", elapsed.as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    for n in [250, 500, 1000, 2000] {
+        bench(&format!("lets/{n}"), &lets(n));
+        bench(&format!("lambdas/{n}"), &lambdas(n));
+    }
+    for name in ["linkedlst", "queue", "reverse", "tuple"] {
+        match example(name) {
+            Some(program) => bench(&format!("examples/{name}"), &program),
+            None => println!(
+                "{:<24} skipped: python3 examples/{name}.py failed",
+                format!("examples/{name}")
+            ),
+        }
+    }
+    for path in env::args().skip(1).filter(|arg| !arg.starts_with("--")) {
+        bench(&path, &read_to_string(&path).unwrap());
+    }
+}
@@ -0,0 +1,642 @@
+//! An arena-allocated copy of the AST for `--arena`, `--share` and the evaluation bench. The type
+//! checker works on `ast::Expr` and shares only the `intern::Interner` with the arena.
+//!
+//! Nodes live in one `Vec` and refer to their children, their type annotations and their
+//! variables by index, so a node is small and cheap to copy. A step allocates new nodes for
+//! the path from the root to the redex and shares everything else with the previous
+//! expression, and substitution shares every subterm in which the variable does not occur.
+//! Stepping follows `rules`, like the tree evaluator.
+//!
+//! Allocation only appends, so the nodes of earlier expressions pile up. `reclaim` copies the
+//! nodes the current expression reaches into a fresh table once the others outnumber them,
+//! which keeps memory proportional to the live term at the cost of the copy.
+//!
+//! An arena made with `Arena::hash_consed` also looks every new node up in a table, so that
+//! equal subterms are a single node and a term is a DAG. Arguments that substitution copies
+//! into several places are then stored once, however often they are duplicated.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    ast::*,
+    intern::{Interner, Sym},
+    rules::{self, Shape, Store},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeId(u32);
+
+/// `ast::Expr` with children, types and variables replaced by handles into the arena
+pub type Node = Shape<ExprId, TypeId, Sym>;
+
+/// A child of a node, in the order `dotgen` draws them
+pub enum Child {
+    Expr(ExprId),
+    Type(TypeId),
+    Var(Sym),
+}
+
+impl Node {
+    pub fn children(&self) -> Vec<Child> {
+        let mut children = Vec::new();
+        self.clone().map(
+            &mut children,
+            |children, e| children.push(Child::Expr(e)),
+            |children, tau| children.push(Child::Type(tau)),
+            |children, x| children.push(Child::Var(x)),
+        );
+        children
+    }
+}
+
+pub type Outcome = rules::Outcome<ExprId>;
+
+/// `reclaim` leaves an arena of fewer nodes alone, where copying costs more than it saves
+const MIN_RECLAIM: usize = 1 << 12;
+
+#[derive(Default)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    types: Vec<Type>,
+    names: Interner,
+    sharing: Option<Sharing>,
+    /// A shared subterm may be reached along exponentially many paths
+    free_vars: RefCell<HashMap<ExprId, HashSet<Sym>>>,
+    /// Number of nodes that survived the last `reclaim`
+    live: usize,
+    /// Largest `bytes` before a `reclaim`
+    peak: usize,
+}
+
+/// Lookup tables of a hash-consed arena
+#[derive(Default)]
+struct Sharing {
+    nodes: HashMap<Node, ExprId>,
+    types: HashMap<Type, TypeId>,
+    /// Results of the current `substitute` call, which visits a shared subterm once
+    subst: HashMap<(ExprId, Sym, ExprId), Option<ExprId>>,
+}
+
+/// Substitute into the children of a node without binders, or return `None` if none of
+/// them changed
+macro_rules! subst_children {
+    ($self:ident, $x:ident, $v:ident, $fv_v:ident; $ty:tt { $($i:ident),+ ; $($rest:ident),* }) => {{
+        $(let $i = $self.subst($i, $x, $v, $fv_v).ok_or($i);)+
+        if $($i.is_err())&&+ {
+            return None;
+        }
+        Node::$ty { $($i: $i.unwrap_or_else(|i| i),)+ $($rest,)* }
+    }};
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An arena in which equal nodes are allocated once
+    pub fn hash_consed() -> Self {
+        Arena {
+            sharing: Some(Sharing::default()),
+            ..Self::default()
+        }
+    }
+
+    /// Number of nodes allocated so far
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Bytes used by the node and type tables, not counting the strings inside them
+    pub fn bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node>()
+            + self.types.capacity() * std::mem::size_of::<Type>()
+    }
+
+    /// The most `bytes` the arena has used
+    pub fn peak_bytes(&self) -> usize {
+        self.peak.max(self.bytes())
+    }
+
+    /// Drop the nodes `root` does not reach once they outnumber the ones it does, and return
+    /// the new id of `root`. Every other `ExprId` into the arena is invalid afterwards.
+    pub fn reclaim(&mut self, root: ExprId) -> ExprId {
+        if self.nodes.len() < MIN_RECLAIM.max(2 * self.live) {
+            return root;
+        }
+        self.peak = self.peak_bytes();
+        let old = std::mem::take(&mut self.nodes);
+        let mut moved = HashMap::new();
+        let root = self.copy_from(&old, root, &mut moved);
+        self.live = self.nodes.len();
+        self.free_vars.borrow_mut().clear();
+        if let Some(sharing) = &mut self.sharing {
+            sharing.subst.clear();
+            sharing.nodes = self.nodes.iter().cloned().zip((0..).map(ExprId)).collect();
+        }
+        root
+    }
+
+    /// Copy the nodes `id` reaches in `old` to the end of the node table, once each
+    fn copy_from(
+        &mut self,
+        old: &[Node],
+        id: ExprId,
+        moved: &mut HashMap<ExprId, ExprId>,
+    ) -> ExprId {
+        if let Some(id) = moved.get(&id) {
+            return *id;
+        }
+        let node = old[id.0 as usize].clone().map(
+            &mut *self,
+            |arena, e| arena.copy_from(old, e, moved),
+            |_, tau| tau,
+            |_, x| x,
+        );
+        let new_id = ExprId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        moved.insert(id, new_id);
+        new_id
+    }
+
+    pub fn alloc(&mut self, node: Node) -> ExprId {
+        let id = ExprId(self.nodes.len() as u32);
+        if let Some(sharing) = &mut self.sharing {
+            match sharing.nodes.get(&node) {
+                Some(id) => return *id,
+                None => sharing.nodes.insert(node.clone(), id),
+            };
+        }
+        self.nodes.push(node);
+        id
+    }
+
+    pub fn alloc_type(&mut self, tau: Type) -> TypeId {
+        let id = TypeId(self.types.len() as u32);
+        if let Some(sharing) = &mut self.sharing {
+            match sharing.types.get(&tau) {
+                Some(id) => return *id,
+                None => sharing.types.insert(tau.clone(), id),
+            };
+        }
+        self.types.push(tau);
+        id
+    }
+
+    pub fn node(&self, id: ExprId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    pub fn ty(&self, id: TypeId) -> &Type {
+        &self.types[id.0 as usize]
+    }
+
+    pub fn intern(&mut self, x: &Variable) -> Sym {
+        self.names.intern(x)
+    }
+
+    pub fn name(&self, x: Sym) -> &str {
+        self.names.name(x)
+    }
+
+    /// Number of nodes in the expression when shared subterms are counted once per use,
+    /// saturating at `u64::MAX`
+    pub fn tree_size(&self, id: ExprId) -> u64 {
+        fn size(arena: &Arena, id: ExprId, memo: &mut HashMap<ExprId, u64>) -> u64 {
+            if let Some(n) = memo.get(&id) {
+                return *n;
+            }
+            let n = arena
+                .node(id)
+                .children()
+                .into_iter()
+                .filter_map(|child| match child {
+                    Child::Expr(e) => Some(size(arena, e, memo)),
+                    _ => None,
+                })
+                .fold(1u64, u64::saturating_add);
+            memo.insert(id, n);
+            n
+        }
+        size(self, id, &mut HashMap::new())
+    }
+
+    /// Number of distinct nodes reachable from `id`
+    pub fn dag_size(&self, id: ExprId) -> usize {
+        let (mut seen, mut stack) = (HashSet::new(), vec![id]);
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                stack.extend(self.node(id).children().into_iter().filter_map(
+                    |child| match child {
+                        Child::Expr(e) => Some(e),
+                        _ => None,
+                    },
+                ));
+            }
+        }
+        seen.len()
+    }
+
+    fn var(&self, x: Sym) -> Variable {
+        Variable::from(self.names.name(x))
+    }
+
+    /// Copy an expression into the arena
+    pub fn from_expr(&mut self, e: &Expr) -> ExprId {
+        let node = Shape::of(e).map(
+            self,
+            |arena, e| arena.from_expr(e),
+            |arena, tau| arena.alloc_type(tau.clone()),
+            |arena, x| arena.names.intern(x),
+        );
+        self.alloc(node)
+    }
+
+    /// Copy an expression out of the arena. Shared subterms are copied once per use.
+    pub fn to_expr(&self, id: ExprId) -> Expr {
+        self.node(id)
+            .clone()
+            .map(
+                &mut (),
+                |_, e| self.to_expr(e),
+                |_, tau| self.ty(tau).clone(),
+                |_, x| self.var(x),
+            )
+            .into()
+    }
+
+    /// Term variables that are not bound inside the expression
+    pub fn free_vars(&self, id: ExprId) -> HashSet<Sym> {
+        if let Some(vars) = self.free_vars.borrow().get(&id) {
+            return vars.clone();
+        }
+        let vars = self.collect_free_vars(id);
+        self.free_vars.borrow_mut().insert(id, vars.clone());
+        vars
+    }
+
+    fn collect_free_vars(&self, id: ExprId) -> HashSet<Sym> {
+        let without = |vars: HashSet<Sym>, x: &Sym| {
+            let mut vars = vars;
+            vars.remove(x);
+            vars
+        };
+        match self.node(id) {
+            Node::Num(_) | Node::True | Node::False | Node::Unit => HashSet::new(),
+            Node::Var(x) => HashSet::from([*x]),
+            Node::Lam { x, e, .. } | Node::LinLam { x, e, .. } | Node::Fix { x, e, .. } => {
+                without(self.free_vars(*e), x)
+            }
+            Node::App {
+                lam: left,
+                arg: right,
+            }
+            | Node::Addop { left, right, .. }
+            | Node::Mulop { left, right, .. }
+            | Node::Relop { left, right, .. }
+            | Node::And { left, right }
+            | Node::Or { left, right }
+            | Node::Pair { left, right } => {
+                let mut vars = self.free_vars(*left);
+                vars.extend(self.free_vars(*right));
+                vars
+            }
+            Node::If { cond, then_, else_ } => {
+                let mut vars = self.free_vars(*cond);
+                vars.extend(self.free_vars(*then_));
+                vars.extend(self.free_vars(*else_));
+                vars
+            }
+            Node::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+            } => {
+                let mut vars = self.free_vars(*e);
+                vars.extend(without(self.free_vars(*eleft), xleft));
+                vars.extend(without(self.free_vars(*eright), xright));
+                vars
+            }
+            Node::Project { e, .. }
+            | Node::Inject { e, .. }
+            | Node::TyLam { e, .. }
+            | Node::TyApp { e, .. }
+            | Node::Fold { e, .. }
+            | Node::Unfold(e)
+            | Node::Export { e, .. }
+            | Node::Cast { e, .. } => self.free_vars(*e),
+            Node::Import {
+                x, e_mod, e_body, ..
+            } => {
+                let mut vars = self.free_vars(*e_mod);
+                vars.extend(without(self.free_vars(*e_body), x));
+                vars
+            }
+            Node::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+            } => {
+                let mut vars = self.free_vars(*e_pair);
+                vars.extend(without(without(self.free_vars(*e_body), x), y));
+                vars
+            }
+        }
+    }
+
+    /// A variant of `x` that does not occur in `avoid`, named like `ast_util`'s fresh names
+    fn fresh(&mut self, x: Sym, avoid: &HashSet<Sym>) -> Sym {
+        let name = self.names.name(x).to_string();
+        let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        let base = if base.is_empty() { name.as_str() } else { base };
+        (1..)
+            .map(|n| Variable::from(format!("{base}{n}")))
+            .find(|y| self.names.get(y).is_none_or(|y| !avoid.contains(&y)))
+            .map(|y| self.names.intern(&y))
+            .unwrap()
+    }
+
+    /// `e[x := v]`, capture-avoiding like `Symbol::substitute`
+    pub fn substitute(&mut self, e: ExprId, x: Sym, v: ExprId) -> ExprId {
+        let fv_v = self.free_vars(v);
+        if let Some(sharing) = &mut self.sharing {
+            sharing.subst.clear();
+        }
+        self.subst(e, x, v, &fv_v).unwrap_or(e)
+    }
+
+    /// Substitute under a binder of `y`, or return `None` if the body doesn't change
+    fn bind(
+        &mut self,
+        y: Sym,
+        body: ExprId,
+        x: Sym,
+        v: ExprId,
+        fv_v: &HashSet<Sym>,
+    ) -> Option<(Sym, ExprId)> {
+        if y == x {
+            return None;
+        }
+        if !fv_v.contains(&y) {
+            return Some((y, self.subst(body, x, v, fv_v)?));
+        }
+        // `v` mentions `y`: rename the binder if `x` occurs below it
+        let mut avoid = self.free_vars(body);
+        if !avoid.contains(&x) {
+            return None;
+        }
+        avoid.extend(fv_v);
+        let new_y = self.fresh(y, &avoid);
+        let var = self.alloc(Node::Var(new_y));
+        let body = self
+            .subst(body, y, var, &HashSet::from([new_y]))
+            .unwrap_or(body);
+        let body = self.subst(body, x, v, fv_v).unwrap_or(body);
+        Some((new_y, body))
+    }
+
+    /// Like `bind`, under the two binders of a `LetPair`
+    fn bind_pair(
+        &mut self,
+        (y1, y2): (Sym, Sym),
+        body: ExprId,
+        x: Sym,
+        v: ExprId,
+        fv_v: &HashSet<Sym>,
+    ) -> Option<((Sym, Sym), ExprId)> {
+        if y1 == y2 {
+            return self
+                .bind(y2, body, x, v, fv_v)
+                .map(|(y, body)| ((y, y), body));
+        }
+        if y1 == x || y2 == x {
+            return None;
+        }
+        if !fv_v.contains(&y1) && !fv_v.contains(&y2) {
+            return Some(((y1, y2), self.subst(body, x, v, fv_v)?));
+        }
+        let mut avoid = self.free_vars(body);
+        if !avoid.contains(&x) {
+            return None;
+        }
+        avoid.extend(fv_v);
+        avoid.extend([y1, y2]);
+        let mut body = body;
+        let mut rename = |y: Sym| match fv_v.contains(&y) {
+            true => {
+                let new_y = self.fresh(y, &avoid);
+                avoid.insert(new_y);
+                let var = self.alloc(Node::Var(new_y));
+                body = self
+                    .subst(body, y, var, &HashSet::from([new_y]))
+                    .unwrap_or(body);
+                new_y
+            }
+            false => y,
+        };
+        let names = (rename(y1), rename(y2));
+        let body = self.subst(body, x, v, fv_v).unwrap_or(body);
+        Some((names, body))
+    }
+
+    /// Returns `None` if `x` does not occur free in `e`, so that `e` can be shared
+    fn subst(&mut self, e: ExprId, x: Sym, v: ExprId, fv_v: &HashSet<Sym>) -> Option<ExprId> {
+        let Some(sharing) = &self.sharing else {
+            return self.subst_node(e, x, v, fv_v);
+        };
+        if let Some(result) = sharing.subst.get(&(e, x, v)) {
+            return *result;
+        }
+        let result = self.subst_node(e, x, v, fv_v);
+        if let Some(sharing) = &mut self.sharing {
+            sharing.subst.insert((e, x, v), result);
+        }
+        result
+    }
+
+    fn subst_node(&mut self, e: ExprId, x: Sym, v: ExprId, fv_v: &HashSet<Sym>) -> Option<ExprId> {
+        let node = match self.node(e).clone() {
+            Node::Num(_) | Node::True | Node::False | Node::Unit => return None,
+            Node::Var(y) => return (y == x).then_some(v),
+            Node::Addop { binop, left, right } => {
+                subst_children!(self, x, v, fv_v; Addop { left, right; binop })
+            }
+            Node::Mulop { binop, left, right } => {
+                subst_children!(self, x, v, fv_v; Mulop { left, right; binop })
+            }
+            Node::Relop { relop, left, right } => {
+                subst_children!(self, x, v, fv_v; Relop { left, right; relop })
+            }
+            Node::If { cond, then_, else_ } => {
+                subst_children!(self, x, v, fv_v; If { cond, then_, else_; })
+            }
+            Node::And { left, right } => subst_children!(self, x, v, fv_v; And { left, right; }),
+            Node::Or { left, right } => subst_children!(self, x, v, fv_v; Or { left, right; }),
+            Node::App { lam, arg } => subst_children!(self, x, v, fv_v; App { lam, arg; }),
+            Node::Pair { left, right } => subst_children!(self, x, v, fv_v; Pair { left, right; }),
+            Node::Project { e, d } => subst_children!(self, x, v, fv_v; Project { e; d }),
+            Node::Inject { e, d, tau } => subst_children!(self, x, v, fv_v; Inject { e; d, tau }),
+            // type variables live in a separate namespace from term variables
+            Node::TyLam { a, e } => subst_children!(self, x, v, fv_v; TyLam { e; a }),
+            Node::TyApp { e, tau } => subst_children!(self, x, v, fv_v; TyApp { e; tau }),
+            Node::Fold { e, tau } => subst_children!(self, x, v, fv_v; Fold { e; tau }),
+            Node::Unfold(e) => Node::Unfold(self.subst(e, x, v, fv_v)?),
+            Node::Export {
+                e,
+                tau_adt,
+                tau_mod,
+            } => subst_children!(self, x, v, fv_v; Export { e; tau_adt, tau_mod }),
+            Node::Cast { e, from, to, blame } => {
+                subst_children!(self, x, v, fv_v; Cast { e; from, to, blame })
+            }
+            Node::Lam { x: y, tau, e } => {
+                let (y, e) = self.bind(y, e, x, v, fv_v)?;
+                Node::Lam { x: y, tau, e }
+            }
+            Node::LinLam { x: y, tau, e } => {
+                let (y, e) = self.bind(y, e, x, v, fv_v)?;
+                Node::LinLam { x: y, tau, e }
+            }
+            Node::Fix { x: y, tau, e } => {
+                let (y, e) = self.bind(y, e, x, v, fv_v)?;
+                Node::Fix { x: y, tau, e }
+            }
+            Node::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+            } => {
+                let new_e = self.subst(e, x, v, fv_v);
+                let left = self.bind(xleft, eleft, x, v, fv_v);
+                let right = self.bind(xright, eright, x, v, fv_v);
+                if new_e.is_none() && left.is_none() && right.is_none() {
+                    return None;
+                }
+                let (xleft, eleft) = left.unwrap_or((xleft, eleft));
+                let (xright, eright) = right.unwrap_or((xright, eright));
+                Node::Case {
+                    e: new_e.unwrap_or(e),
+                    xleft,
+                    eleft,
+                    xright,
+                    eright,
+                }
+            }
+            Node::Import {
+                x: y,
+                a,
+                e_mod,
+                e_body,
+            } => {
+                let new_mod = self.subst(e_mod, x, v, fv_v);
+                let body = self.bind(y, e_body, x, v, fv_v);
+                if new_mod.is_none() && body.is_none() {
+                    return None;
+                }
+                let (y, e_body) = body.unwrap_or((y, e_body));
+                Node::Import {
+                    x: y,
+                    a,
+                    e_mod: new_mod.unwrap_or(e_mod),
+                    e_body,
+                }
+            }
+            Node::LetPair {
+                x: y1,
+                y: y2,
+                e_pair,
+                e_body,
+            } => {
+                let new_pair = self.subst(e_pair, x, v, fv_v);
+                let body = self.bind_pair((y1, y2), e_body, x, v, fv_v);
+                if new_pair.is_none() && body.is_none() {
+                    return None;
+                }
+                let ((y1, y2), e_body) = body.unwrap_or(((y1, y2), e_body));
+                Node::LetPair {
+                    x: y1,
+                    y: y2,
+                    e_pair: new_pair.unwrap_or(e_pair),
+                    e_body,
+                }
+            }
+        };
+        Some(self.alloc(node))
+    }
+
+    /// Evaluate to a value with the rules of `evaluate::try_step`
+    pub fn eval(&mut self, e: ExprId) -> Result<ExprId, Blame> {
+        let mut e = e;
+        loop {
+            match self.step(e) {
+                Outcome::Step(next) => e = self.reclaim(next),
+                Outcome::Value => return Ok(e),
+                Outcome::Blame(blame) => return Err(blame),
+            }
+        }
+    }
+
+    pub fn step(&mut self, id: ExprId) -> Outcome {
+        rules::step(self, id)
+    }
+}
+
+impl Store for Arena {
+    type E<'a> = ExprId;
+    type T<'a> = TypeId;
+    type V<'a> = Sym;
+    type Term = ExprId;
+    type Ty = TypeId;
+    type Var = Sym;
+
+    fn view<'a>(&self, e: Self::E<'a>) -> Shape<Self::E<'a>, Self::T<'a>, Self::V<'a>> {
+        self.node(e).clone()
+    }
+
+    fn ty<'b, 'a: 'b>(&'b self, tau: TypeId) -> &'b Type {
+        Arena::ty(self, tau)
+    }
+
+    fn name(&self, x: Sym) -> Variable {
+        self.var(x)
+    }
+
+    fn term(&self, e: ExprId) -> ExprId {
+        e
+    }
+
+    fn own_type(&self, tau: TypeId) -> TypeId {
+        tau
+    }
+
+    fn own_var(&self, x: Sym) -> Sym {
+        x
+    }
+
+    fn alloc(&mut self, node: Node) -> ExprId {
+        Arena::alloc(self, node)
+    }
+
+    fn alloc_type(&mut self, tau: Type) -> TypeId {
+        Arena::alloc_type(self, tau)
+    }
+
+    fn substitute(&mut self, e: ExprId, x: Sym, v: ExprId) -> ExprId {
+        Arena::substitute(self, e, x, v)
+    }
+}
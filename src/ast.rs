@@ -1,3 +1,11 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    pretty::{simplified_doc, simplified_type_doc},
+    rules::Shape,
+    span::Span,
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Variable(pub String);
 
@@ -16,38 +24,99 @@ where
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Types are locally nameless: a variable bound by `rec`, `forall` or `exists` is a de Bruijn
+/// index `Bound(i)` counting the binders between it and its binder, while `Var` is always a
+/// free variable. The binder's name is only a hint for printing and is ignored by `==`, which
+/// makes equality alpha-equivalence. Use `Type::rec`, `Type::forall` and `Type::exists` to
+/// bind a named variable.
+#[derive(Debug, Clone)]
 pub enum Type {
     Num,
     Bool,
     Unit,
     Var(Variable),
+    Bound(u32),
     Fn { arg: Box<Type>, ret: Box<Type> },
+    LinFn { arg: Box<Type>, ret: Box<Type> },
     Product { left: Box<Type>, right: Box<Type> },
     Sum { left: Box<Type>, right: Box<Type> },
     Rec { a: Variable, tau: Box<Type> },
     Forall { a: Variable, tau: Box<Type> },
     Exists { a: Variable, tau: Box<Type> },
+    Dyn,
 }
 
-impl std::fmt::Display for Type {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Type::Num, Type::Num)
+            | (Type::Bool, Type::Bool)
+            | (Type::Unit, Type::Unit)
+            | (Type::Dyn, Type::Dyn) => true,
+            (Type::Var(a), Type::Var(b)) => a == b,
+            (Type::Bound(i), Type::Bound(j)) => i == j,
+            (Type::Fn { arg: a1, ret: r1 }, Type::Fn { arg: a2, ret: r2 })
+            | (Type::LinFn { arg: a1, ret: r1 }, Type::LinFn { arg: a2, ret: r2 })
+            | (
+                Type::Product {
+                    left: a1,
+                    right: r1,
+                },
+                Type::Product {
+                    left: a2,
+                    right: r2,
+                },
+            )
+            | (
+                Type::Sum {
+                    left: a1,
+                    right: r1,
+                },
+                Type::Sum {
+                    left: a2,
+                    right: r2,
+                },
+            ) => a1 == a2 && r1 == r2,
+            (Type::Rec { tau: t1, .. }, Type::Rec { tau: t2, .. })
+            | (Type::Forall { tau: t1, .. }, Type::Forall { tau: t2, .. })
+            | (Type::Exists { tau: t1, .. }, Type::Exists { tau: t2, .. }) => t1 == t2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Type {}
+
+/// Consistent with `==`: binder names are not hashed
+impl std::hash::Hash for Type {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
         match self {
-            Type::Num => write!(f, "num"),
-            Type::Bool => write!(f, "bool"),
-            Type::Unit => write!(f, "()"),
-            Type::Var(v) => write!(f, "{}", v.0),
-            Type::Fn { arg, ret } => write!(f, "{} → {}", arg, ret),
-            Type::Product { left, right } => write!(f, "{} * {}", left, right),
-            Type::Sum { left, right } => write!(f, "{} + {}", left, right),
-            Type::Rec { a, tau } => write!(f, "μ {} . {}", a.0, tau),
-            Type::Forall { a, tau } => write!(f, "∀ {} . {}", a.0, tau),
-            Type::Exists { a, tau } => write!(f, "∃ {} . {}", a.0, tau),
+            Type::Num | Type::Bool | Type::Unit | Type::Dyn => {}
+            Type::Var(a) => a.hash(state),
+            Type::Bound(i) => i.hash(state),
+            Type::Fn { arg, ret } | Type::LinFn { arg, ret } => {
+                arg.hash(state);
+                ret.hash(state);
+            }
+            Type::Product { left, right } | Type::Sum { left, right } => {
+                left.hash(state);
+                right.hash(state);
+            }
+            Type::Rec { tau, .. } | Type::Forall { tau, .. } | Type::Exists { tau, .. } => {
+                tau.hash(state)
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", simplified_type_doc(self).flat())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AddOp {
     Add,
     Sub,
@@ -62,7 +131,7 @@ impl std::fmt::Display for AddOp {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MulOp {
     Mul,
     Div,
@@ -77,7 +146,7 @@ impl std::fmt::Display for MulOp {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RelOp {
     Lt,
     Gt,
@@ -94,68 +163,149 @@ impl std::fmt::Display for RelOp {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Direction {
     Left,
     Right,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Identifies a node through clones, moves and substitution, so that what is known about a parsed
+/// node (where it is, what type it has) can be found from any copy of it. The parser and the
+/// evaluator give out fresh ids; `NodeId::default()` is for nodes that nothing is known about.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u64);
+
+impl NodeId {
+    pub fn fresh() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        NodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Which side of a cast is at fault when it fails: the term in the cast, or the context that
+/// uses it, which for a function cast is whoever passed the argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Polarity {
+    Positive,
+    Negative,
+}
+
+/// Where a runtime cast was inserted, reported when the cast fails
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Blame {
+    /// The annotation the cast enforces, or the term it was put around when the type comes
+    /// from an operator or a branch, with its `line:col`
+    pub label: String,
+    /// Where that annotation or term is
+    pub span: Option<Span>,
+    pub polarity: Polarity,
+}
+
+impl Blame {
+    /// The same cast, blaming the other side
+    pub fn flip(&self) -> Blame {
+        Blame {
+            polarity: match self.polarity {
+                Polarity::Positive => Polarity::Negative,
+                Polarity::Negative => Polarity::Positive,
+            },
+            ..self.clone()
+        }
+    }
+}
+
+impl std::fmt::Display for Blame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.polarity {
+            Polarity::Positive => write!(f, "{}", self.label),
+            Polarity::Negative => write!(f, "context of {}", self.label),
+        }
+    }
+}
+
+/// Every node carries a `NodeId`, which `==` and `Debug` ignore
+#[derive(Clone)]
 pub enum Expr {
-    Num(i32),
+    Num(i32, NodeId),
     Addop {
         binop: AddOp,
         left: Box<Expr>,
         right: Box<Expr>,
+        id: NodeId,
     },
     Mulop {
         binop: MulOp,
         left: Box<Expr>,
         right: Box<Expr>,
+        id: NodeId,
     },
-    True,
-    False,
+    True(NodeId),
+    False(NodeId),
     If {
         cond: Box<Expr>,
         then_: Box<Expr>,
         else_: Box<Expr>,
+        id: NodeId,
     },
     Relop {
         relop: RelOp,
         left: Box<Expr>,
         right: Box<Expr>,
+        id: NodeId,
     },
     And {
         left: Box<Expr>,
         right: Box<Expr>,
+        id: NodeId,
     },
     Or {
         left: Box<Expr>,
         right: Box<Expr>,
+        id: NodeId,
     },
-    Var(Variable),
+    Var(Variable, NodeId),
     Lam {
         x: Variable,
         tau: Box<Type>,
         e: Box<Expr>,
+        id: NodeId,
+    },
+    /// A function that uses its argument exactly once
+    LinLam {
+        x: Variable,
+        tau: Box<Type>,
+        e: Box<Expr>,
+        id: NodeId,
     },
     App {
         lam: Box<Expr>,
         arg: Box<Expr>,
+        id: NodeId,
     },
-    Unit,
+    Unit(NodeId),
     Pair {
         left: Box<Expr>,
         right: Box<Expr>,
+        id: NodeId,
     },
     Project {
         e: Box<Expr>,
         d: Direction,
+        id: NodeId,
+    },
+    /// `let (x, y) = e_pair in e_body`, which uses both components of the pair
+    LetPair {
+        x: Variable,
+        y: Variable,
+        e_pair: Box<Expr>,
+        e_body: Box<Expr>,
+        id: NodeId,
     },
     Inject {
         e: Box<Expr>,
         d: Direction,
         tau: Box<Type>,
+        id: NodeId,
     },
     Case {
         e: Box<Expr>,
@@ -163,90 +313,137 @@ pub enum Expr {
         eleft: Box<Expr>,
         xright: Variable,
         eright: Box<Expr>,
+        id: NodeId,
     },
     Fix {
         x: Variable,
         tau: Box<Type>,
         e: Box<Expr>,
+        id: NodeId,
     },
     TyLam {
         a: Variable,
         e: Box<Expr>,
+        id: NodeId,
     },
     TyApp {
         e: Box<Expr>,
         tau: Box<Type>,
+        id: NodeId,
     },
     Fold {
         e: Box<Expr>,
         tau: Box<Type>,
+        id: NodeId,
     },
-    Unfold(Box<Expr>),
+    Unfold(Box<Expr>, NodeId),
     Export {
         e: Box<Expr>,
         tau_adt: Box<Type>,
         tau_mod: Box<Type>,
+        id: NodeId,
     },
     Import {
         x: Variable,
         a: Variable,
         e_mod: Box<Expr>,
         e_body: Box<Expr>,
+        id: NodeId,
+    },
+    /// Runtime type check inserted by the gradual typing elaboration
+    Cast {
+        e: Box<Expr>,
+        from: Box<Type>,
+        to: Box<Type>,
+        blame: Blame,
+        id: NodeId,
     },
 }
 
-impl std::fmt::Display for Expr {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Expr {
+    pub fn id(&self) -> NodeId {
         match self {
-            Expr::Var(v) => write!(f, "{}", v.0),
-            Expr::Num(n) => write!(f, "{}", n),
-            Expr::True => write!(f, "true"),
-            Expr::False => write!(f, "false"),
-            Expr::Unit => write!(f, "()"),
-            Expr::Addop { binop, left, right } => write!(f, "({} {} {})", left, binop, right),
-            Expr::Mulop { binop, left, right } => write!(f, "({} {} {})", left, binop, right),
-            Expr::If { cond, then_, else_ } => {
-                write!(f, "if {} then {} else {}", cond, then_, else_)
-            }
-            Expr::Relop { relop, left, right } => write!(f, "({} {} {})", left, relop, right),
-            Expr::And { left, right } => write!(f, "({} && {})", left, right),
-            Expr::Or { left, right } => write!(f, "({} || {})", left, right),
-            Expr::Pair { left, right } => write!(f, "({} , {})", left, right),
-            Expr::Project { e, d } => match (e.as_ref(), d) {
-                (Expr::Pair { left, .. }, Direction::Left) => write!(f, "{}", left),
-                (Expr::Pair { right, .. }, Direction::Right) => write!(f, "{}", right),
-                _ => write!(f, "{:?}", self),
-            },
-            Expr::Inject { e, .. } => write!(f, "{}", e),
-            Expr::Case {
-                e,
-                xleft,
-                eleft,
-                xright,
-                eright,
-            } => write!(
-                f,
-                "case {} of L({}) -> {} | R({}) -> {}",
-                e, xleft.0, eleft, xright.0, eright
-            ),
-            Expr::App { lam, arg } => write!(f, "({} {})", lam, arg),
-            Expr::Lam { x, tau, e } => write!(f, "λ ({} : {}) -> {}", x.0, tau, e),
-            Expr::TyLam { a, e } => write!(f, "Λ {} -> {}", a.0, e),
-            Expr::TyApp { e, tau } => write!(f, "({} {})", e, tau),
-            Expr::Fix { x, tau, e } => write!(f, "fix ({} : {}) -> {}", x.0, tau, e),
-            Expr::Fold { e, .. } => write!(f, "fold {} as ...", e),
-            Expr::Unfold(e) => write!(f, "unfold {}", e),
-            Expr::Export {
-                e,
-                tau_adt,
-                tau_mod,
-            } => write!(f, "export {} without {} as {}", e, tau_adt, tau_mod),
-            Expr::Import {
-                x,
-                a,
-                e_mod,
-                e_body,
-            } => write!(f, "import ({}, {}) = {} in {}", x.0, a.0, e_mod, e_body),
+            Expr::Num(_, id)
+            | Expr::True(id)
+            | Expr::False(id)
+            | Expr::Var(_, id)
+            | Expr::Unit(id)
+            | Expr::Unfold(_, id)
+            | Expr::Addop { id, .. }
+            | Expr::Mulop { id, .. }
+            | Expr::If { id, .. }
+            | Expr::Relop { id, .. }
+            | Expr::And { id, .. }
+            | Expr::Or { id, .. }
+            | Expr::Lam { id, .. }
+            | Expr::LinLam { id, .. }
+            | Expr::App { id, .. }
+            | Expr::Pair { id, .. }
+            | Expr::Project { id, .. }
+            | Expr::LetPair { id, .. }
+            | Expr::Inject { id, .. }
+            | Expr::Case { id, .. }
+            | Expr::Fix { id, .. }
+            | Expr::TyLam { id, .. }
+            | Expr::TyApp { id, .. }
+            | Expr::Fold { id, .. }
+            | Expr::Export { id, .. }
+            | Expr::Import { id, .. }
+            | Expr::Cast { id, .. } => *id,
         }
     }
+
+    /// The same node under another id
+    pub fn with_id(mut self, new: NodeId) -> Self {
+        match &mut self {
+            Expr::Num(_, id)
+            | Expr::True(id)
+            | Expr::False(id)
+            | Expr::Var(_, id)
+            | Expr::Unit(id)
+            | Expr::Unfold(_, id)
+            | Expr::Addop { id, .. }
+            | Expr::Mulop { id, .. }
+            | Expr::If { id, .. }
+            | Expr::Relop { id, .. }
+            | Expr::And { id, .. }
+            | Expr::Or { id, .. }
+            | Expr::Lam { id, .. }
+            | Expr::LinLam { id, .. }
+            | Expr::App { id, .. }
+            | Expr::Pair { id, .. }
+            | Expr::Project { id, .. }
+            | Expr::LetPair { id, .. }
+            | Expr::Inject { id, .. }
+            | Expr::Case { id, .. }
+            | Expr::Fix { id, .. }
+            | Expr::TyLam { id, .. }
+            | Expr::TyApp { id, .. }
+            | Expr::Fold { id, .. }
+            | Expr::Export { id, .. }
+            | Expr::Import { id, .. }
+            | Expr::Cast { id, .. } => *id = new,
+        }
+        self
+    }
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        Shape::of(self) == Shape::of(other)
+    }
+}
+
+impl Eq for Expr {}
+
+impl std::fmt::Debug for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Shape::of(self).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", simplified_doc(self).flat())
+    }
 }
@@ -1,9 +1,49 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::ast::{Expr, Type, Variable};
+use crate::ast::{Expr, NodeId, Type, Variable};
 
-fn fresh(v: &Variable) -> Variable {
-    Variable::from(format!("{}_", v.0))
+/// A variant of `v` that does not occur in `avoid`: the name with its numeric suffix
+/// replaced by the smallest counter that is not taken
+fn fresh(v: &Variable, avoid: &HashSet<Variable>) -> Variable {
+    let base = v.0.trim_end_matches(|c: char| c.is_ascii_digit());
+    let base = if base.is_empty() { v.0.as_str() } else { base };
+    (1..)
+        .map(|n| Variable::from(format!("{base}{n}")))
+        .find(|x| !avoid.contains(x))
+        .unwrap()
+}
+
+/// Go under a binder of `x` while substituting `rename` into `body`.
+///
+/// `x` shadows any pending substitution for itself, and is only renamed when one of the
+/// replacements mentions `x` free (which would otherwise be captured). Returns the name
+/// the binder should have and the substitution to apply to the body.
+fn bind<T: Symbol>(
+    x: Variable,
+    rename: HashMap<Variable, T>,
+    body: &T,
+) -> (Variable, HashMap<Variable, T>) {
+    let mut rename = rename;
+    rename.remove(&x);
+    let replacements: HashSet<Variable> = rename.values().flat_map(T::free_vars).collect();
+    if !replacements.contains(&x) {
+        return (x, rename);
+    }
+    let mut avoid = body.free_vars();
+    avoid.extend(replacements);
+    let new_x = fresh(&x, &avoid);
+    rename.insert(x, T::var(new_x.clone()));
+    (new_x, rename)
+}
+
+fn union<const N: usize>(sets: [HashSet<Variable>; N]) -> HashSet<Variable> {
+    sets.into_iter().flatten().collect()
+}
+
+fn without(vars: HashSet<Variable>, x: &Variable) -> HashSet<Variable> {
+    let mut vars = vars;
+    vars.remove(x);
+    vars
 }
 
 fn add_depth<I>(depth: HashMap<Variable, u32>, it: I) -> HashMap<Variable, u32>
@@ -30,7 +70,203 @@ macro_rules! trivial {
     };
 }
 
+impl Type {
+    /// Bind `a` in `tau` with a `rec`
+    pub fn rec(a: Variable, tau: Type) -> Type {
+        let mut tau = tau;
+        tau.close(&a);
+        Type::Rec {
+            a,
+            tau: Box::new(tau),
+        }
+    }
+
+    /// Bind `a` in `tau` with a `forall`
+    pub fn forall(a: Variable, tau: Type) -> Type {
+        let mut tau = tau;
+        tau.close(&a);
+        Type::Forall {
+            a,
+            tau: Box::new(tau),
+        }
+    }
+
+    /// Bind `a` in `tau` with an `exists`
+    pub fn exists(a: Variable, tau: Type) -> Type {
+        let mut tau = tau;
+        tau.close(&a);
+        Type::Exists {
+            a,
+            tau: Box::new(tau),
+        }
+    }
+
+    /// Turn the free occurrences of `a` into references to a binder directly around `self`.
+    pub fn close(&mut self, a: &Variable) {
+        self.close_at(a, 0)
+    }
+
+    fn close_at(&mut self, a: &Variable, k: u32) {
+        match self {
+            Type::Var(b) if b == a => *self = Type::Bound(k),
+            Type::Num | Type::Bool | Type::Unit | Type::Dyn | Type::Var(_) | Type::Bound(_) => {}
+            Type::Fn { arg, ret } | Type::LinFn { arg, ret } => {
+                arg.close_at(a, k);
+                ret.close_at(a, k)
+            }
+            Type::Product { left, right } | Type::Sum { left, right } => {
+                left.close_at(a, k);
+                right.close_at(a, k)
+            }
+            Type::Rec { tau, .. } | Type::Forall { tau, .. } | Type::Exists { tau, .. } => {
+                tau.close_at(a, k + 1)
+            }
+        }
+    }
+
+    /// Instantiate the variable bound by the binder directly around `self` (the body of a
+    /// `rec`, `forall` or `exists`) with `tau`
+    pub fn open(&self, tau: &Type) -> Type {
+        self.open_at(tau, 0)
+    }
+
+    fn open_at(&self, tau: &Type, k: u32) -> Type {
+        match self {
+            Type::Bound(i) if *i == k => tau.clone(),
+            Type::Num | Type::Bool | Type::Unit | Type::Dyn | Type::Var(_) | Type::Bound(_) => {
+                self.clone()
+            }
+            Type::Fn { arg, ret } => Type::Fn {
+                arg: Box::new(arg.open_at(tau, k)),
+                ret: Box::new(ret.open_at(tau, k)),
+            },
+            Type::LinFn { arg, ret } => Type::LinFn {
+                arg: Box::new(arg.open_at(tau, k)),
+                ret: Box::new(ret.open_at(tau, k)),
+            },
+            Type::Product { left, right } => Type::Product {
+                left: Box::new(left.open_at(tau, k)),
+                right: Box::new(right.open_at(tau, k)),
+            },
+            Type::Sum { left, right } => Type::Sum {
+                left: Box::new(left.open_at(tau, k)),
+                right: Box::new(right.open_at(tau, k)),
+            },
+            Type::Rec { a, tau: body } => Type::Rec {
+                a: a.clone(),
+                tau: Box::new(body.open_at(tau, k + 1)),
+            },
+            Type::Forall { a, tau: body } => Type::Forall {
+                a: a.clone(),
+                tau: Box::new(body.open_at(tau, k + 1)),
+            },
+            Type::Exists { a, tau: body } => Type::Exists {
+                a: a.clone(),
+                tau: Box::new(body.open_at(tau, k + 1)),
+            },
+        }
+    }
+}
+
+impl Expr {
+    /// The direct subexpressions, left to right as they appear in the source
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) | Expr::Var(..) => {
+                vec![]
+            }
+            Expr::Addop { left, right, .. }
+            | Expr::Mulop { left, right, .. }
+            | Expr::Relop { left, right, .. }
+            | Expr::And { left, right, .. }
+            | Expr::Or { left, right, .. }
+            | Expr::Pair { left, right, .. }
+            | Expr::App {
+                lam: left,
+                arg: right,
+                ..
+            }
+            | Expr::Import {
+                e_mod: left,
+                e_body: right,
+                ..
+            }
+            | Expr::LetPair {
+                e_pair: left,
+                e_body: right,
+                ..
+            } => vec![left, right],
+            Expr::If {
+                cond, then_, else_, ..
+            } => vec![cond, then_, else_],
+            Expr::Case {
+                e, eleft, eright, ..
+            } => vec![e, eleft, eright],
+            Expr::Lam { e, .. }
+            | Expr::LinLam { e, .. }
+            | Expr::Fix { e, .. }
+            | Expr::Project { e, .. }
+            | Expr::Inject { e, .. }
+            | Expr::TyLam { e, .. }
+            | Expr::TyApp { e, .. }
+            | Expr::Fold { e, .. }
+            | Expr::Unfold(e, _)
+            | Expr::Export { e, .. }
+            | Expr::Cast { e, .. } => vec![e],
+        }
+    }
+
+    /// Mutable `children`
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) | Expr::Var(..) => {
+                vec![]
+            }
+            Expr::Addop { left, right, .. }
+            | Expr::Mulop { left, right, .. }
+            | Expr::Relop { left, right, .. }
+            | Expr::And { left, right, .. }
+            | Expr::Or { left, right, .. }
+            | Expr::Pair { left, right, .. }
+            | Expr::App {
+                lam: left,
+                arg: right,
+                ..
+            }
+            | Expr::Import {
+                e_mod: left,
+                e_body: right,
+                ..
+            }
+            | Expr::LetPair {
+                e_pair: left,
+                e_body: right,
+                ..
+            } => vec![left, right],
+            Expr::If {
+                cond, then_, else_, ..
+            } => vec![cond, then_, else_],
+            Expr::Case {
+                e, eleft, eright, ..
+            } => vec![e, eleft, eright],
+            Expr::Lam { e, .. }
+            | Expr::LinLam { e, .. }
+            | Expr::Fix { e, .. }
+            | Expr::Project { e, .. }
+            | Expr::Inject { e, .. }
+            | Expr::TyLam { e, .. }
+            | Expr::TyApp { e, .. }
+            | Expr::Fold { e, .. }
+            | Expr::Unfold(e, _)
+            | Expr::Export { e, .. }
+            | Expr::Cast { e, .. } => vec![e],
+        }
+    }
+}
+
 pub trait Symbol: Sized {
+    fn var(v: Variable) -> Self;
+    fn free_vars(&self) -> HashSet<Variable>;
     fn to_debruijn_map(self, depth: HashMap<Variable, u32>) -> Self;
     fn to_debruijn(self) -> Self {
         self.to_debruijn_map(HashMap::new())
@@ -43,9 +279,30 @@ pub trait Symbol: Sized {
 }
 
 impl Symbol for Type {
+    fn var(a: Variable) -> Self {
+        Type::Var(a)
+    }
+
+    /// Type variables that are not bound by a quantifier or `rec` inside this type
+    fn free_vars(&self) -> HashSet<Variable> {
+        match self {
+            Type::Num | Type::Bool | Type::Unit | Type::Dyn | Type::Bound(_) => HashSet::new(),
+            Type::Var(a) => HashSet::from([a.clone()]),
+            Type::Fn { arg, ret } | Type::LinFn { arg, ret } => {
+                union([arg.free_vars(), ret.free_vars()])
+            }
+            Type::Product { left, right } | Type::Sum { left, right } => {
+                union([left.free_vars(), right.free_vars()])
+            }
+            Type::Rec { tau, .. } | Type::Forall { tau, .. } | Type::Exists { tau, .. } => {
+                tau.free_vars()
+            }
+        }
+    }
+
     fn to_debruijn_map(self, depth: HashMap<Variable, u32>) -> Self {
         match self {
-            Type::Num | Type::Bool | Type::Unit => self,
+            Type::Num | Type::Bool | Type::Unit | Type::Dyn | Type::Bound(_) => self,
             Type::Product { left, right } => {
                 trivial!(Type, Product, depth, to_debruijn_map;; left, right;)
             }
@@ -54,39 +311,34 @@ impl Symbol for Type {
                 None => v, // v is a free variable
                 Some(depth) => Variable::from(*depth),
             }),
-            Type::Forall { a, tau } => {
-                let depth = add_depth(depth, [a]);
-                Type::Forall {
-                    a: Variable::from("_"),
-                    tau: Box::new(tau.to_debruijn_map(depth)),
-                }
-            }
-            Type::Rec { a, tau } => {
-                let depth = add_depth(depth, [a]);
-                Type::Rec {
-                    a: Variable::from("_"),
-                    tau: Box::new(tau.to_debruijn_map(depth)),
-                }
-            }
+            // bound type variables already are indices: only the printing hint goes
+            Type::Forall { tau, .. } => Type::Forall {
+                a: Variable::from("_"),
+                tau: Box::new(tau.to_debruijn_map(depth)),
+            },
+            Type::Rec { tau, .. } => Type::Rec {
+                a: Variable::from("_"),
+                tau: Box::new(tau.to_debruijn_map(depth)),
+            },
             Type::Fn { arg, ret } => trivial!(Type, Fn, depth, to_debruijn_map;; arg, ret;),
-            Type::Exists { a, tau } => {
-                let depth = add_depth(depth, [a]);
-                Type::Exists {
-                    a: Variable::from("_"),
-                    tau: Box::new(tau.to_debruijn_map(depth)),
-                }
-            }
+            Type::LinFn { arg, ret } => trivial!(Type, LinFn, depth, to_debruijn_map;; arg, ret;),
+            Type::Exists { tau, .. } => Type::Exists {
+                a: Variable::from("_"),
+                tau: Box::new(tau.to_debruijn_map(depth)),
+            },
         }
     }
 
     fn alpha_equiv(e1: Self, e2: Self) -> bool {
-        e1.to_debruijn() == e2.to_debruijn()
+        e1 == e2
     }
 
+    /// Replacements must not contain dangling `Bound` indices, so no renaming is ever needed
     fn substitute_map(self, rename: HashMap<Variable, Type>) -> Type {
         match self {
-            Type::Num | Type::Bool | Type::Unit => self,
+            Type::Num | Type::Bool | Type::Unit | Type::Dyn | Type::Bound(_) => self,
             Type::Fn { arg, ret } => trivial!(Type, Fn, rename, substitute_map;; arg, ret;),
+            Type::LinFn { arg, ret } => trivial!(Type, LinFn, rename, substitute_map;; arg, ret;),
             Type::Product { left, right } => {
                 trivial!(Type, Product, rename, substitute_map;; left, right;)
             }
@@ -95,74 +347,170 @@ impl Symbol for Type {
                 Some(val) => val.clone(),
                 None => Type::Var(v),
             },
-            Type::Forall { a, tau } => {
-                let mut rename = rename;
-                let new_a = fresh(&a);
-                rename.insert(a, Type::Var(new_a.clone()));
-                Type::Forall {
-                    a: new_a,
-                    tau: Box::new(tau.substitute_map(rename)),
-                }
-            }
-            Type::Rec { a, tau } => {
-                let mut rename = rename;
-                let new_a = fresh(&a);
-                rename.insert(a, Type::Var(new_a.clone()));
-                Type::Rec {
-                    a: new_a,
-                    tau: Box::new(tau.substitute_map(rename)),
-                }
-            }
-            Type::Exists { a, tau } => {
-                let mut rename = rename;
-                let new_a = fresh(&a);
-                rename.insert(a, Type::Var(new_a.clone()));
-                Type::Exists {
-                    a: new_a,
-                    tau: Box::new(tau.substitute_map(rename)),
-                }
-            }
+            Type::Forall { a, tau } => trivial!(Type, Forall, rename, substitute_map; a; tau;),
+            Type::Rec { a, tau } => trivial!(Type, Rec, rename, substitute_map; a; tau;),
+            Type::Exists { a, tau } => trivial!(Type, Exists, rename, substitute_map; a; tau;),
         }
     }
 }
 
 impl Symbol for Expr {
+    fn var(x: Variable) -> Self {
+        Expr::Var(x, NodeId::default())
+    }
+
+    /// Term variables that are not bound inside this expression. Type annotations are
+    /// not inspected: term substitution never touches them.
+    fn free_vars(&self) -> HashSet<Variable> {
+        match self {
+            Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) => HashSet::new(),
+            Expr::Var(x, _) => HashSet::from([x.clone()]),
+            Expr::Lam { x, e, .. } | Expr::LinLam { x, e, .. } | Expr::Fix { x, e, .. } => {
+                without(e.free_vars(), x)
+            }
+            Expr::App {
+                lam: left,
+                arg: right,
+                ..
+            }
+            | Expr::Addop { left, right, .. }
+            | Expr::Mulop { left, right, .. }
+            | Expr::Relop { left, right, .. }
+            | Expr::And { left, right, .. }
+            | Expr::Or { left, right, .. }
+            | Expr::Pair { left, right, .. } => union([left.free_vars(), right.free_vars()]),
+            Expr::If {
+                cond, then_, else_, ..
+            } => union([cond.free_vars(), then_.free_vars(), else_.free_vars()]),
+            Expr::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+                ..
+            } => union([
+                e.free_vars(),
+                without(eleft.free_vars(), xleft),
+                without(eright.free_vars(), xright),
+            ]),
+            Expr::Project { e, .. }
+            | Expr::Inject { e, .. }
+            | Expr::TyLam { e, .. }
+            | Expr::TyApp { e, .. }
+            | Expr::Fold { e, .. }
+            | Expr::Unfold(e, _)
+            | Expr::Export { e, .. }
+            | Expr::Cast { e, .. } => e.free_vars(),
+            Expr::Import {
+                x, e_mod, e_body, ..
+            } => union([e_mod.free_vars(), without(e_body.free_vars(), x)]),
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                ..
+            } => union([
+                e_pair.free_vars(),
+                without(without(e_body.free_vars(), x), y),
+            ]),
+        }
+    }
+
     fn to_debruijn_map(self, depth: HashMap<Variable, u32>) -> Self {
         match self {
-            Expr::Num(_) | Expr::True | Expr::False | Expr::Unit => self.clone(),
-            Expr::Var(v) => Expr::Var(match depth.get(&v) {
-                None => v.clone(), // v is a free variable
-                Some(depth) => Variable::from(*depth),
-            }),
-            Expr::Lam { x, tau, e } => {
+            Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) => self.clone(),
+            Expr::Var(v, id) => Expr::Var(
+                match depth.get(&v) {
+                    None => v.clone(), // v is a free variable
+                    Some(depth) => Variable::from(*depth),
+                },
+                id,
+            ),
+            Expr::Lam { x, tau, e, id } => {
                 let depth = add_depth(depth, [x.clone()]);
                 Expr::Lam {
                     x: Variable::from("_"),
                     tau: Box::new(tau.to_debruijn_map(depth.clone())),
                     e: Box::new(e.to_debruijn_map(depth)),
+                    id,
                 }
             }
-            Expr::App { lam, arg } => trivial!(Expr, App, depth, to_debruijn_map;; lam, arg;),
-            Expr::Addop { binop, left, right } => {
-                trivial!(Expr, Addop, depth, to_debruijn_map; binop; left, right;)
+            Expr::LinLam { x, tau, e, id } => {
+                let depth = add_depth(depth, [x.clone()]);
+                Expr::LinLam {
+                    x: Variable::from("_"),
+                    tau: Box::new(tau.to_debruijn_map(depth.clone())),
+                    e: Box::new(e.to_debruijn_map(depth)),
+                    id,
+                }
             }
-            Expr::Mulop { binop, left, right } => {
-                trivial!(Expr, Mulop, depth, to_debruijn_map; binop; left, right;)
+            Expr::App { lam, arg, id } => {
+                trivial!(Expr, App, depth, to_debruijn_map;; lam, arg; id)
             }
-            Expr::Relop { relop, left, right } => {
-                trivial!(Expr, Relop, depth, to_debruijn_map; relop; left, right;)
+            Expr::Addop {
+                binop,
+                left,
+                right,
+                id,
+            } => {
+                trivial!(Expr, Addop, depth, to_debruijn_map; binop; left, right; id)
             }
-            Expr::If { cond, then_, else_ } => {
-                trivial!(Expr, If, depth, to_debruijn_map;; cond, then_, else_;)
+            Expr::Mulop {
+                binop,
+                left,
+                right,
+                id,
+            } => {
+                trivial!(Expr, Mulop, depth, to_debruijn_map; binop; left, right; id)
+            }
+            Expr::Relop {
+                relop,
+                left,
+                right,
+                id,
+            } => {
+                trivial!(Expr, Relop, depth, to_debruijn_map; relop; left, right; id)
             }
-            Expr::And { left, right } => trivial!(Expr, And, depth, to_debruijn_map;; left, right;),
-            Expr::Or { left, right } => trivial!(Expr, Or, depth, to_debruijn_map;; left, right;),
-            Expr::Pair { left, right } => {
-                trivial!(Expr, Pair, depth, to_debruijn_map;; left, right;)
+            Expr::If {
+                cond,
+                then_,
+                else_,
+                id,
+            } => {
+                trivial!(Expr, If, depth, to_debruijn_map;; cond, then_, else_; id)
             }
-            Expr::Project { e, d } => trivial!(Expr, Project, depth, to_debruijn_map;; e; d),
-            Expr::Inject { e, d, tau } => {
-                trivial!(Expr, Inject, depth, to_debruijn_map;; e; d, tau)
+            Expr::And { left, right, id } => {
+                trivial!(Expr, And, depth, to_debruijn_map;; left, right; id)
+            }
+            Expr::Or { left, right, id } => {
+                trivial!(Expr, Or, depth, to_debruijn_map;; left, right; id)
+            }
+            Expr::Pair { left, right, id } => {
+                trivial!(Expr, Pair, depth, to_debruijn_map;; left, right; id)
+            }
+            Expr::Project { e, d, id } => {
+                trivial!(Expr, Project, depth, to_debruijn_map;; e; d, id)
+            }
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                id,
+            } => {
+                let depth_body = add_depth(add_depth(depth.clone(), [x]), [y]);
+                Expr::LetPair {
+                    x: Variable::from("_"),
+                    y: Variable::from("_"),
+                    e_pair: Box::new(e_pair.to_debruijn_map(depth)),
+                    e_body: Box::new(e_body.to_debruijn_map(depth_body)),
+                    id,
+                }
+            }
+            Expr::Inject { e, d, tau, id } => {
+                trivial!(Expr, Inject, depth, to_debruijn_map;; e; d, tau, id)
             }
             Expr::Case {
                 e,
@@ -170,44 +518,53 @@ impl Symbol for Expr {
                 eleft,
                 xright,
                 eright,
+                id,
             } => {
-                let depth_new = add_depth(depth.clone(), [xleft, xright]);
+                let depth_left = add_depth(depth.clone(), [xleft]);
+                let depth_right = add_depth(depth.clone(), [xright]);
                 Expr::Case {
                     e: Box::new(e.to_debruijn_map(depth)),
                     xleft: Variable::from("_"),
-                    eleft: Box::new(eleft.to_debruijn_map(depth_new.clone())),
+                    eleft: Box::new(eleft.to_debruijn_map(depth_left)),
                     xright: Variable::from("_"),
-                    eright: Box::new(eright.to_debruijn_map(depth_new)),
+                    eright: Box::new(eright.to_debruijn_map(depth_right)),
+                    id,
                 }
             }
-            Expr::Fix { x, tau, e } => {
+            Expr::Fix { x, tau, e, id } => {
                 let depth = add_depth(depth, [x]);
                 Expr::Fix {
                     x: Variable::from("_"),
                     tau: Box::new(tau.to_debruijn_map(depth.clone())),
                     e: Box::new(e.to_debruijn_map(depth)),
+                    id,
                 }
             }
-            Expr::TyApp { e, tau } => trivial!(Expr, TyApp, depth, to_debruijn_map;; e, tau;),
-            Expr::Fold { e, tau } => trivial!(Expr, Fold, depth, to_debruijn_map;; e, tau;),
-            Expr::TyLam { a, e } => {
+            Expr::TyApp { e, tau, id } => {
+                trivial!(Expr, TyApp, depth, to_debruijn_map;; e, tau; id)
+            }
+            Expr::Fold { e, tau, id } => trivial!(Expr, Fold, depth, to_debruijn_map;; e, tau; id),
+            Expr::TyLam { a, e, id } => {
                 let depth = add_depth(depth, [a]);
                 Expr::TyLam {
                     a: Variable::from("_"),
                     e: Box::new(e.to_debruijn_map(depth)),
+                    id,
                 }
             }
-            Expr::Unfold(e) => Expr::Unfold(Box::new(e.to_debruijn_map(depth))),
+            Expr::Unfold(e, id) => Expr::Unfold(Box::new(e.to_debruijn_map(depth)), id),
             Expr::Export {
                 e,
                 tau_adt,
                 tau_mod,
-            } => trivial!(Expr, Export, depth, to_debruijn_map;; e, tau_adt, tau_mod;),
+                id,
+            } => trivial!(Expr, Export, depth, to_debruijn_map;; e, tau_adt, tau_mod; id),
             Expr::Import {
                 x,
                 a,
                 e_mod,
                 e_body,
+                id,
             } => {
                 let depth = add_depth(depth, [x, a]);
                 Expr::Import {
@@ -215,8 +572,18 @@ impl Symbol for Expr {
                     a: Variable::from("_"),
                     e_mod: Box::new(e_mod.to_debruijn_map(depth.clone())),
                     e_body: Box::new(e_body.to_debruijn_map(depth)),
+                    id,
                 }
             }
+            Expr::Cast {
+                e,
+                from,
+                to,
+                blame,
+                id,
+            } => {
+                trivial!(Expr, Cast, depth, to_debruijn_map;; e, from, to; blame, id)
+            }
         }
     }
 
@@ -226,42 +593,95 @@ impl Symbol for Expr {
 
     fn substitute_map(self, rename: HashMap<Variable, Expr>) -> Expr {
         match self {
-            Expr::Num(_) | Expr::True | Expr::False | Expr::Unit => self.clone(),
-            Expr::Addop { binop, left, right } => {
-                trivial!(Expr, Addop, rename, substitute_map; binop; left, right;)
+            Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) => self.clone(),
+            Expr::Addop {
+                binop,
+                left,
+                right,
+                id,
+            } => {
+                trivial!(Expr, Addop, rename, substitute_map; binop; left, right; id)
+            }
+            Expr::Mulop {
+                binop,
+                left,
+                right,
+                id,
+            } => {
+                trivial!(Expr, Mulop, rename, substitute_map; binop; left, right; id)
+            }
+            Expr::If {
+                cond,
+                then_,
+                else_,
+                id,
+            } => {
+                trivial!(Expr, If, rename, substitute_map;; cond, then_, else_; id)
             }
-            Expr::Mulop { binop, left, right } => {
-                trivial!(Expr, Mulop, rename, substitute_map; binop; left, right;)
+            Expr::Relop {
+                relop,
+                left,
+                right,
+                id,
+            } => {
+                trivial!(Expr, Relop, rename, substitute_map; relop; left, right; id)
             }
-            Expr::If { cond, then_, else_ } => {
-                trivial!(Expr, If, rename, substitute_map;; cond, then_, else_;)
+            Expr::And { left, right, id } => {
+                trivial!(Expr, And, rename, substitute_map;; left, right; id)
             }
-            Expr::Relop { relop, left, right } => {
-                trivial!(Expr, Relop, rename, substitute_map; relop; left, right;)
+            Expr::Or { left, right, id } => {
+                trivial!(Expr, Or, rename, substitute_map;; left, right; id)
             }
-            Expr::And { left, right } => trivial!(Expr, And, rename, substitute_map;; left, right;),
-            Expr::Or { left, right } => trivial!(Expr, Or, rename, substitute_map;; left, right;),
-            Expr::Lam { x, tau, e } => {
-                let mut rename = rename;
-                let new_x = fresh(&x);
-                rename.insert(x, Expr::Var(new_x.clone()));
+            Expr::Lam { x, tau, e, id } => {
+                let (x, rename) = bind(x, rename, &e);
                 Expr::Lam {
-                    x: new_x,
+                    x,
+                    tau,
+                    e: Box::new(e.substitute_map(rename)),
+                    id,
+                }
+            }
+            Expr::LinLam { x, tau, e, id } => {
+                let (x, rename) = bind(x, rename, &e);
+                Expr::LinLam {
+                    x,
                     tau,
                     e: Box::new(e.substitute_map(rename)),
+                    id,
                 }
             }
-            Expr::App { lam, arg } => trivial!(Expr, App, rename, substitute_map;; lam, arg;),
-            Expr::Var(v) => match rename.get(&v.clone()) {
+            Expr::App { lam, arg, id } => {
+                trivial!(Expr, App, rename, substitute_map;; lam, arg; id)
+            }
+            Expr::Var(v, id) => match rename.get(&v.clone()) {
                 Some(val) => val.clone(),
-                None => Expr::Var(v),
+                None => Expr::Var(v, id),
             },
-            Expr::Pair { left, right } => {
-                trivial!(Expr, Pair, rename, substitute_map;; left, right;)
+            Expr::Pair { left, right, id } => {
+                trivial!(Expr, Pair, rename, substitute_map;; left, right; id)
             }
-            Expr::Project { e, d } => trivial!(Expr, Project, rename, substitute_map;; e; d),
-            Expr::Inject { e, d, tau } => {
-                trivial!(Expr, Inject, rename, substitute_map;; e; d, tau)
+            Expr::Project { e, d, id } => {
+                trivial!(Expr, Project, rename, substitute_map;; e; d, id)
+            }
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                id,
+            } => {
+                let (x, rename_body) = bind(x, rename.clone(), &e_body);
+                let (y, rename_body) = bind(y, rename_body, &e_body);
+                Expr::LetPair {
+                    x,
+                    y,
+                    e_pair: Box::new(e_pair.substitute_map(rename)),
+                    e_body: Box::new(e_body.substitute_map(rename_body)),
+                    id,
+                }
+            }
+            Expr::Inject { e, d, tau, id } => {
+                trivial!(Expr, Inject, rename, substitute_map;; e; d, tau, id)
             }
             Expr::Case {
                 e,
@@ -269,69 +689,66 @@ impl Symbol for Expr {
                 eleft,
                 xright,
                 eright,
+                id,
             } => {
-                let mut rename = rename;
-                let new_xleft = fresh(&xleft);
-                let new_xright = fresh(&xright);
-                rename.extend([
-                    (xleft.clone(), Expr::Var(new_xleft.clone())),
-                    (xright.clone(), Expr::Var(new_xright.clone())),
-                ]);
+                let (xleft, rename_left) = bind(xleft, rename.clone(), &eleft);
+                let (xright, rename_right) = bind(xright, rename.clone(), &eright);
                 Expr::Case {
-                    e: Box::new(e.substitute_map(rename.clone())),
-                    xleft: new_xleft,
-                    eleft: Box::new(eleft.substitute_map(rename.clone())),
-                    xright: new_xright,
-                    eright: Box::new(eright.substitute_map(rename)),
+                    e: Box::new(e.substitute_map(rename)),
+                    xleft,
+                    eleft: Box::new(eleft.substitute_map(rename_left)),
+                    xright,
+                    eright: Box::new(eright.substitute_map(rename_right)),
+                    id,
                 }
             }
-            Expr::Fix { x, tau, e } => {
-                let mut rename = rename;
-                let new_x = fresh(&x);
-                rename.insert(x, Expr::Var(new_x.clone()));
+            Expr::Fix { x, tau, e, id } => {
+                let (x, rename) = bind(x, rename, &e);
                 Expr::Fix {
-                    x: new_x,
+                    x,
                     tau,
                     e: Box::new(e.substitute_map(rename)),
+                    id,
                 }
             }
-            Expr::TyLam { a, e } => {
-                let mut rename = rename;
-                let new_a = fresh(&a);
-                rename.insert(a, Expr::Var(new_a.clone()));
-                Expr::TyLam {
-                    a: new_a,
-                    e: Box::new(e.substitute_map(rename)),
-                }
+            // type variables live in a separate namespace from term variables
+            Expr::TyLam { a, e, id } => trivial!(Expr, TyLam, rename, substitute_map; a; e; id),
+            Expr::TyApp { e, tau, id } => {
+                trivial!(Expr, TyApp, rename, substitute_map;; e; tau, id)
             }
-            Expr::TyApp { e, tau } => trivial!(Expr, TyApp, rename, substitute_map;; e; tau),
-            Expr::Fold { e, tau } => trivial!(Expr, Fold, rename, substitute_map;; e; tau),
-            Expr::Unfold(e) => Expr::Unfold(Box::new(e.substitute_map(rename))),
+            Expr::Fold { e, tau, id } => trivial!(Expr, Fold, rename, substitute_map;; e; tau, id),
+            Expr::Unfold(e, id) => Expr::Unfold(Box::new(e.substitute_map(rename)), id),
             Expr::Export {
                 e,
                 tau_adt,
                 tau_mod,
-            } => trivial!(Expr, Export, rename, substitute_map;; e; tau_adt, tau_mod),
+                id,
+            } => trivial!(Expr, Export, rename, substitute_map;; e; tau_adt, tau_mod, id),
             Expr::Import {
                 x,
                 a,
                 e_mod,
                 e_body,
+                id,
             } => {
-                let mut rename = rename;
-                let new_x = fresh(&x);
-                let new_a = fresh(&a);
-                rename.extend([
-                    (x.clone(), Expr::Var(new_x.clone())),
-                    (a.clone(), Expr::Var(new_a.clone())),
-                ]);
+                let (x, rename_body) = bind(x, rename.clone(), &e_body);
                 Expr::Import {
-                    x: new_x,
-                    a: new_a,
-                    e_mod: Box::new(e_mod.substitute_map(rename.clone())),
-                    e_body: Box::new(e_body.substitute_map(rename)),
+                    x,
+                    a,
+                    e_mod: Box::new(e_mod.substitute_map(rename)),
+                    e_body: Box::new(e_body.substitute_map(rename_body)),
+                    id,
                 }
             }
+            Expr::Cast {
+                e,
+                from,
+                to,
+                blame,
+                id,
+            } => {
+                trivial!(Expr, Cast, rename, substitute_map;; e; from, to, blame, id)
+            }
         }
     }
 }
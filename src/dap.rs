@@ -0,0 +1,405 @@
+//! A Debug Adapter Protocol server on stdin/stdout for stepping through programs in editors.
+//!
+//! A step is one `try_step` rule, as in `debugger`. The source line of a step is that of its
+//! redex, whose node id survives substitution.
+
+use std::{
+    collections::HashSet,
+    fs::read_to_string,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    ast::*,
+    debugger::Debugger,
+    flags::{format_ast, format_type, Format, OutputMode},
+    gradual::elaborate,
+    parser::parse_with_spans,
+    span::{SourceMap, Span},
+    typecheck::type_check,
+};
+
+const THREAD: i64 = 1;
+const SUBSTITUTIONS: i64 = 1;
+const STEP: i64 = 2;
+
+/// Read a message, or `None` at the end of the input
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(n) = line.strip_prefix("Content-Length:") {
+            length = n.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+/// Where a redex comes from in the source. Nodes keep their ids through substitution; a node
+/// made during evaluation, such as a cast inserted by elaboration, takes the position of the
+/// first of its subterms that came from the source.
+fn origin(spans: &SourceMap, redex: &Expr) -> Option<Span> {
+    spans.expr_span(redex).or_else(|| {
+        redex
+            .children()
+            .into_iter()
+            .find_map(|child| origin(spans, child))
+    })
+}
+
+/// The lines on which an expression starts
+fn lines(source: &Expr, spans: &SourceMap, lines: &mut HashSet<usize>) {
+    if let Some(span) = spans.expr_span(source) {
+        lines.insert(spans.line_col(span.start).0);
+    }
+    for child in source.children() {
+        self::lines(child, spans, lines)
+    }
+}
+
+/// The launched program
+struct Program {
+    path: String,
+    source: Box<Expr>,
+    spans: SourceMap,
+    debugger: Debugger,
+}
+
+impl Program {
+    fn load(path: &str, format: Format) -> Result<Self, String> {
+        let input = read_to_string(path).map_err(|err| format!("I/O error: {err}"))?;
+        let (source, spans) =
+            parse_with_spans(&input).map_err(|err| format!("Parse error: {err}"))?;
+        type_check(&source).map_err(|err| format!("Type error: {err}"))?;
+        let debugger = Debugger::new(elaborate(&source, &spans), format);
+        Ok(Program {
+            path: path.to_string(),
+            source,
+            spans,
+            debugger,
+        })
+    }
+
+    /// The line and column of the current step in the source
+    fn position(&self) -> Option<(usize, usize)> {
+        let redex = &self.debugger.record()?.redex;
+        let span = origin(&self.spans, redex)?;
+        Some(self.spans.line_col(span.start))
+    }
+}
+
+struct Server<W: Write> {
+    output: W,
+    format: Format,
+    seq: i64,
+    program: Option<Program>,
+    stop_on_entry: bool,
+    /// Lines with breakpoints
+    breakpoints: HashSet<usize>,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({"type": "event", "event": event, "body": body}))
+    }
+
+    fn format(&self, e: &Expr) -> String {
+        format_ast(e, self.format, None)
+    }
+
+    fn at_breakpoint(&self, program: &Program) -> bool {
+        program
+            .position()
+            .is_some_and(|(line, _)| self.breakpoints.contains(&line))
+    }
+
+    /// Step until `stop` holds before a step, a breakpoint is reached or evaluation ends, then
+    /// report where
+    fn run(
+        &mut self,
+        reason: &str,
+        forward: bool,
+        stop: impl Fn(&Debugger) -> bool,
+    ) -> io::Result<()> {
+        let Some(mut program) = self.program.take() else {
+            return Ok(());
+        };
+        let reason = loop {
+            let moved = match forward {
+                true => program.debugger.forward(),
+                false => {
+                    let current = program.debugger.current();
+                    program.debugger.back(1);
+                    program.debugger.current() != current
+                }
+            };
+            if !moved || program.debugger.record().is_none() {
+                break reason;
+            }
+            if self.at_breakpoint(&program) {
+                break "breakpoint";
+            }
+            if stop(&program.debugger) {
+                break reason;
+            }
+        };
+        self.report(program, reason)
+    }
+
+    /// Send a stopped event, or the result and a terminated event if evaluation has ended
+    fn report(&mut self, program: Program, reason: &str) -> io::Result<()> {
+        let ended = program.debugger.record().is_none();
+        let (category, result, code) = match program.debugger.blame() {
+            None => ("stdout", self.format(program.debugger.term()), 0),
+            Some(blame) => ("stderr", format!("Cast failed: {blame}"), 1),
+        };
+        self.program = Some(program);
+        match ended {
+            false => self.event(
+                "stopped",
+                json!({"reason": reason, "threadId": THREAD, "allThreadsStopped": true}),
+            ),
+            true => {
+                let output = json!({"category": category, "output": format!("{result}\n")});
+                self.event("output", output)?;
+                self.event("exited", json!({ "exitCode": code }))?;
+                self.event("terminated", json!({}))
+            }
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let program = self.program.as_ref().ok_or("No program is running")?;
+        let name = match program.debugger.record() {
+            Some(record) => record.rule,
+            None => "value",
+        };
+        let (line, column) = program.position().unwrap_or((0, 0));
+        Ok(json!({
+            "stackFrames": [{
+                "id": 1,
+                "name": name,
+                "line": line,
+                "column": column,
+                "source": {"path": program.path},
+            }],
+            "totalFrames": 1,
+        }))
+    }
+
+    fn variables(&self, reference: i64) -> Result<Value, String> {
+        let program = self.program.as_ref().ok_or("No program is running")?;
+        let variable = |name: &str, value: String| json!({"name": name, "value": value, "variablesReference": 0});
+        let variables: Vec<Value> = match reference {
+            SUBSTITUTIONS => program
+                .debugger
+                .substitutions()
+                .iter()
+                .map(|(x, e)| variable(&x.0, self.format(e)))
+                .collect(),
+            STEP => match program.debugger.record() {
+                Some(record) => vec![
+                    variable("rule", record.rule.to_string()),
+                    variable("redex", self.format(&record.redex)),
+                    variable("result", self.format(&record.result)),
+                    variable(
+                        "type",
+                        type_check(&record.redex)
+                            .map_or_else(|err| err, |tau| format_type(&tau, self.format, None)),
+                    ),
+                    variable("step", program.debugger.current().to_string()),
+                ],
+                None => vec![variable("value", self.format(program.debugger.term()))],
+            },
+            _ => return Err(format!("No variables with reference {reference}")),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Handle a request. Returns `false` once the client disconnects.
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let args = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        let path = || {
+            self.program
+                .as_ref()
+                .and_then(|program| program.debugger.record())
+                .map_or(Vec::new(), |record| record.path.clone())
+        };
+        match command {
+            "initialize" => {
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsStepBack": true,
+                });
+                self.respond(request, Ok(capabilities))?;
+                self.event("initialized", json!({}))?;
+            }
+            "launch" => {
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                let program = args["program"].as_str().unwrap_or_default();
+                let result = Program::load(program, self.format).map(|program| {
+                    self.program = Some(program);
+                    json!({})
+                });
+                self.respond(request, result)?;
+            }
+            "setBreakpoints" => {
+                let requested: Vec<usize> = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|b| b["line"].as_u64().map(|line| line as usize))
+                    .collect();
+                let mut valid = HashSet::new();
+                if let Some(program) = &self.program {
+                    lines(&program.source, &program.spans, &mut valid)
+                }
+                let breakpoints: Vec<Value> = requested
+                    .iter()
+                    .map(|line| json!({"verified": valid.contains(line), "line": line}))
+                    .collect();
+                self.breakpoints = requested.into_iter().collect();
+                self.respond(request, Ok(json!({ "breakpoints": breakpoints })))?;
+            }
+            "setExceptionBreakpoints" => self.respond(request, Ok(json!({})))?,
+            "configurationDone" => {
+                self.respond(request, Ok(json!({})))?;
+                match self.program.take() {
+                    Some(program)
+                        if self.stop_on_entry
+                            || program.debugger.record().is_none()
+                            || self.at_breakpoint(&program) =>
+                    {
+                        self.report(program, "entry")?
+                    }
+                    program => {
+                        self.program = program;
+                        self.run("step", true, |_| false)?
+                    }
+                }
+            }
+            "threads" => {
+                let threads = json!({"threads": [{"id": THREAD, "name": "main"}]});
+                self.respond(request, Ok(threads))?
+            }
+            "stackTrace" => self.respond(request, self.stack_trace())?,
+            "scopes" => {
+                let scopes = json!({"scopes": [
+                    {"name": "Substitutions", "variablesReference": SUBSTITUTIONS, "expensive": false},
+                    {"name": "Step", "variablesReference": STEP, "expensive": false},
+                ]});
+                self.respond(request, Ok(scopes))?
+            }
+            "variables" => {
+                let reference = args["variablesReference"].as_i64().unwrap_or_default();
+                self.respond(request, self.variables(reference))?
+            }
+            "continue" => {
+                self.respond(request, Ok(json!({"allThreadsContinued": true})))?;
+                self.run("step", true, |_| false)?
+            }
+            "stepIn" => {
+                self.respond(request, Ok(json!({})))?;
+                self.run("step", true, |_| true)?
+            }
+            // run until the subterm the redex is in, or the redex itself, is done
+            "next" | "stepOut" => {
+                let mut path = path();
+                if command == "stepOut" {
+                    path.pop();
+                }
+                self.respond(request, Ok(json!({})))?;
+                self.run("step", true, |debugger| {
+                    debugger
+                        .record()
+                        .is_some_and(|record| !record.path.starts_with(&path))
+                })?
+            }
+            "stepBack" => {
+                self.respond(request, Ok(json!({})))?;
+                self.run("step", false, |_| true)?
+            }
+            "reverseContinue" => {
+                self.respond(request, Ok(json!({})))?;
+                self.run("entry", false, |_| false)?
+            }
+            "pause" => {
+                // stepping never runs in the background, so the program is already stopped
+                self.respond(request, Ok(json!({})))?;
+                self.event("stopped", json!({"reason": "pause", "threadId": THREAD}))?
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            _ => self.respond(request, Err(format!("Unsupported request `{command}`")))?,
+        }
+        Ok(true)
+    }
+}
+
+/// Serve one debugging session
+pub fn serve(mut input: impl BufRead, output: impl Write, format: Format) -> io::Result<()> {
+    let mut server = Server {
+        output,
+        // expressions are shown on one line
+        format: match format.output {
+            OutputMode::Graphviz => Format {
+                output: OutputMode::Simplified,
+                ..format
+            },
+            _ => format,
+        },
+        seq: 0,
+        program: None,
+        stop_on_entry: false,
+        breakpoints: HashSet::new(),
+    };
+    while let Some(request) = read_message(&mut input)? {
+        if !server.handle(&request)? {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,339 @@
+//! An interactive stepper over the call-by-name rules of `try_step`. Every term stepped
+//! through is kept, so it can go back and jump to any earlier step.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    ast::*,
+    evaluate::{highlight, try_step, try_step_traced, Outcome, StepRecord},
+    flags::{format_ast, format_type, Format},
+    typecheck::type_check,
+};
+
+const HELP: &str = "\
+step [n]           take n steps (s)
+back [n]           go back n steps (b)
+continue           run to the next breakpoint or the end (c)
+goto <n>           jump to step n (g)
+break rule <rule>  stop before a rule fires, e.g. `break rule E-Fix`
+break var <x>      stop before a variable named x is substituted
+delete [n]         delete breakpoint n, or all of them
+breakpoints        list the breakpoints
+print              print the term with the redex highlighted (p)
+redex              print the redex, its rule, its result and its type (r)
+context            print the evaluation context around the redex (ctx)
+help               print this message (h)
+quit               stop debugging (q)";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    /// Before the rule fires
+    Rule(String),
+    /// Before a variable with this name is substituted
+    Var(Variable),
+}
+
+impl Breakpoint {
+    fn hit(&self, record: &StepRecord) -> bool {
+        match self {
+            Breakpoint::Rule(rule) => record.rule == rule,
+            Breakpoint::Var(x) => substitution(&record.redex).is_some_and(|(y, _)| y == x),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Rule(rule) => write!(f, "rule {rule}"),
+            Breakpoint::Var(x) => write!(f, "var {}", x.0),
+        }
+    }
+}
+
+/// The variable a redex substitutes for, and what it substitutes
+pub fn substitution(redex: &Expr) -> Option<(&Variable, &Expr)> {
+    match redex {
+        Expr::App { lam, arg, .. } => match lam.as_ref() {
+            Expr::Lam { x, .. } | Expr::LinLam { x, .. } => Some((x, arg)),
+            _ => None,
+        },
+        Expr::Case {
+            e, xleft, xright, ..
+        } => match e.as_ref() {
+            Expr::Inject {
+                e,
+                d: Direction::Left,
+                ..
+            } => Some((xleft, e)),
+            Expr::Inject { e, .. } => Some((xright, e)),
+            _ => None,
+        },
+        Expr::Fix { x, .. } => Some((x, redex)),
+        Expr::Import { x, e_mod, .. } => match e_mod.as_ref() {
+            Expr::Export { e, .. } => Some((x, e)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A term the debugger has stepped through
+struct State {
+    term: Expr,
+    /// The step taken from the term, or how evaluation ended
+    next: Result<StepRecord, Option<Blame>>,
+}
+
+impl State {
+    fn new(term: Expr) -> Self {
+        let next = match try_step_traced(&term) {
+            (Outcome::Step(_), record) => Ok(record.expect("a step has a record")),
+            (Outcome::Value, _) => Err(None),
+            (Outcome::Blame(blame), _) => Err(Some(blame)),
+        };
+        State { term, next }
+    }
+}
+
+pub struct Debugger {
+    format: Format,
+    history: Vec<State>,
+    current: usize,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new(e: Expr, output: impl Into<Format>) -> Self {
+        Debugger {
+            format: output.into(),
+            history: vec![State::new(e)],
+            current: 0,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// The number of the current step
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn term(&self) -> &Expr {
+        &self.history[self.current].term
+    }
+
+    /// The step about to be taken, if evaluation hasn't ended
+    pub fn record(&self) -> Option<&StepRecord> {
+        self.history[self.current].next.as_ref().ok()
+    }
+
+    /// The cast that failed, if evaluation ended that way
+    pub fn blame(&self) -> Option<&Blame> {
+        self.history[self.current].next.as_ref().err()?.as_ref()
+    }
+
+    /// The latest value substituted for each variable before the current step
+    pub fn substitutions(&self) -> Vec<(Variable, Expr)> {
+        let mut substitutions: Vec<(Variable, Expr)> = Vec::new();
+        for state in &self.history[..self.current] {
+            let Some((x, e)) = state
+                .next
+                .as_ref()
+                .ok()
+                .and_then(|r| substitution(&r.redex))
+            else {
+                continue;
+            };
+            substitutions.retain(|(y, _)| y != x);
+            substitutions.push((x.clone(), e.clone()));
+        }
+        substitutions
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Take a step, unless evaluation has ended. Returns whether a step was taken.
+    pub fn forward(&mut self) -> bool {
+        if self.record().is_none() {
+            return false;
+        }
+        if self.current + 1 == self.history.len() {
+            let Outcome::Step(next) = try_step(self.term()) else {
+                unreachable!()
+            };
+            self.history.push(State::new(next));
+        }
+        self.current += 1;
+        true
+    }
+
+    pub fn back(&mut self, n: usize) {
+        self.current = self.current.saturating_sub(n)
+    }
+
+    /// Go to step `n`, or as close as evaluation gets
+    pub fn goto(&mut self, n: usize) {
+        if n < self.history.len() {
+            self.current = n
+        }
+        while self.current < n && self.forward() {}
+    }
+
+    /// Step until a breakpoint is about to be hit or evaluation ends. Returns the breakpoint.
+    pub fn resume(&mut self) -> Option<usize> {
+        while self.forward() {
+            let record = self.record()?;
+            if let Some(i) = self.breakpoints.iter().position(|b| b.hit(record)) {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// Run one command, returning what to print, or `None` to quit
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let count = |word: Option<&&str>| match word {
+            None => Ok(1),
+            Some(n) => n.parse().map_err(|_| format!("`{n}` is not a number")),
+        };
+        let result = match words.as_slice() {
+            [] => Ok(String::new()),
+            ["q" | "quit"] => return None,
+            ["h" | "help"] => Ok(String::from(HELP)),
+            ["s" | "step", rest @ ..] if rest.len() <= 1 => count(rest.first()).map(|n| {
+                for _ in 0..n {
+                    self.forward();
+                }
+                self.status()
+            }),
+            ["b" | "back", rest @ ..] if rest.len() <= 1 => count(rest.first()).map(|n| {
+                self.back(n);
+                self.status()
+            }),
+            ["g" | "goto", n] => count(Some(n)).map(|n| {
+                self.goto(n);
+                self.status()
+            }),
+            ["c" | "continue"] => Ok(match self.resume() {
+                Some(i) => format!(
+                    "breakpoint {i} ({})\n{}",
+                    self.breakpoints[i],
+                    self.status()
+                ),
+                None => self.status(),
+            }),
+            ["break", "rule", rule] => Ok(self.add(Breakpoint::Rule(rule.to_string()))),
+            ["break", "var", x] => Ok(self.add(Breakpoint::Var(Variable::from(*x)))),
+            ["delete"] => {
+                self.breakpoints.clear();
+                Ok(String::from("deleted all breakpoints"))
+            }
+            ["delete", n] => count(Some(n)).and_then(|i| {
+                if i < self.breakpoints.len() {
+                    Ok(format!("deleted breakpoint {}", self.breakpoints.remove(i)))
+                } else {
+                    Err(format!("There is no breakpoint {i}"))
+                }
+            }),
+            ["breakpoints"] => Ok(self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(i, b)| format!("{i}: {b}"))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ["p" | "print"] => Ok(self.status()),
+            ["r" | "redex"] => Ok(self.redex()),
+            ["ctx" | "context"] => Ok(self.context()),
+            _ => Err(format!("Unknown command `{line}`, try `help`")),
+        };
+        Some(result.unwrap_or_else(|err| err))
+    }
+
+    fn add(&mut self, breakpoint: Breakpoint) -> String {
+        self.breakpoints.push(breakpoint);
+        format!(
+            "breakpoint {}: {}",
+            self.breakpoints.len() - 1,
+            self.breakpoints.last().unwrap()
+        )
+    }
+
+    /// The current step and term
+    fn status(&self) -> String {
+        let state = &self.history[self.current];
+        match &state.next {
+            Ok(record) => format!(
+                "step {}: {} at {:?}\n{}",
+                self.current,
+                record.rule,
+                record.path,
+                highlight(&state.term, &record.path, self.format)
+            ),
+            Err(None) => format!(
+                "step {}: value\n{}",
+                self.current,
+                format_ast(&state.term, self.format, None)
+            ),
+            Err(Some(blame)) => format!(
+                "step {}: cast failed: {blame}\n{}",
+                self.current,
+                format_ast(&state.term, self.format, None)
+            ),
+        }
+    }
+
+    fn redex(&self) -> String {
+        let Some(record) = self.record() else {
+            return String::from("Evaluation has ended");
+        };
+        // the redex is closed: evaluation doesn't go under binders
+        let tau = match type_check(&record.redex) {
+            Ok(tau) => format_type(&tau, self.format, None),
+            Err(err) => format!("unknown ({err})"),
+        };
+        format!(
+            "{}: {}\n  ⟶ {}\n  : {tau}",
+            record.rule,
+            format_ast(&record.redex, self.format, None),
+            format_ast(&record.result, self.format, None)
+        )
+    }
+
+    /// The term with a hole in place of the redex
+    fn context(&self) -> String {
+        let Some(record) = self.record() else {
+            return String::from("Evaluation has ended");
+        };
+        let mut context = self.term().clone();
+        let mut hole = &mut context;
+        for &i in &record.path {
+            hole = hole.children_mut().swap_remove(i);
+        }
+        *hole = Expr::Var(Variable::from("□"), NodeId::default());
+        format_ast(&context, self.format, None)
+    }
+
+    /// Read commands until `quit` or the end of the input
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.status())?;
+        write!(output, "(debug) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            match self.command(&line?) {
+                Some(response) if response.is_empty() => (),
+                Some(response) => writeln!(output, "{response}")?,
+                None => return Ok(()),
+            }
+            write!(output, "(debug) ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+}
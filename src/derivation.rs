@@ -0,0 +1,169 @@
+//! Renderings of typing derivations: an indented tree for the terminal, proof trees for LaTeX
+//! with the `bussproofs` or `mathpartir` package, and a Graphviz graph.
+
+use clap::ValueEnum;
+
+use crate::{
+    ast::{Expr, NodeId, Type},
+    flags::{format_ast, format_type, OutputMode},
+    pretty::{latex_expr, latex_type, pretty_width},
+    typecheck::{Assumption, Derivation},
+};
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum LatexStyle {
+    /// `prooftree` environments of the `bussproofs` package
+    #[default]
+    Bussproofs,
+
+    /// Nested `\inferrule*` of the `mathpartir` package
+    Mathpartir,
+}
+
+/// `context ⊢ e : tau` with expressions and types in `output` notation, on one line. The
+/// simplified notation leaves out the annotations rules look at, so expressions are in source
+/// notation instead.
+fn judgment(d: &Derivation, output: OutputMode) -> String {
+    let expr = |e: &Expr| match output {
+        OutputMode::Simplified => pretty_width(e, usize::MAX),
+        _ => format_ast(e, output, None),
+    };
+    let ty = |tau: &Type| match output {
+        OutputMode::Simplified => tau.to_string(),
+        _ => format_type(tau, output, None),
+    };
+    let context: Vec<String> = d
+        .context
+        .iter()
+        .map(|assumption| match assumption {
+            Assumption::Var(x, tau) => format!("{} : {}", x.0, ty(tau)),
+            Assumption::TyVar(a) => a.0.clone(),
+        })
+        .collect();
+    let turnstile = match context.is_empty() {
+        true => String::from("⊢"),
+        false => context.join(", ") + " ⊢",
+    };
+    format!("{turnstile} {} : {}", expr(&d.e), ty(&d.tau))
+}
+
+/// The derivation as a tree with one judgment per line, conclusions above their premises
+pub fn to_text(d: &Derivation, output: OutputMode) -> String {
+    let mut out = String::new();
+    text_node(d, output, "", "", &mut out);
+    out.pop();
+    out
+}
+
+/// `first` prefixes the line of the judgment and `rest` those of its premises
+fn text_node(d: &Derivation, output: OutputMode, first: &str, rest: &str, out: &mut String) {
+    *out += &format!("{first}{} {}\n", d.rule, judgment(d, output));
+    for (i, premise) in d.premises.iter().enumerate() {
+        let (branch, continuation) = match i + 1 == d.premises.len() {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+        let first = format!("{rest}{branch}");
+        text_node(
+            premise,
+            output,
+            &first,
+            &format!("{rest}{continuation}"),
+            out,
+        );
+    }
+}
+
+/// The derivation as a Graphviz graph with a box for each judgment and edges from conclusions to
+/// their premises. Contexts and terms are in simplified notation.
+pub fn to_dot(d: &Derivation) -> String {
+    let mut out = String::from("digraph Derivation {\n\tnode [shape=box];\n\trankdir=BT;\n");
+    dot_node(d, &mut 0, &mut out);
+    out + "}"
+}
+
+/// Write the node of `d` and its premises, numbering them from `next`, and return its number
+fn dot_node(d: &Derivation, next: &mut usize, out: &mut String) -> usize {
+    let idx = *next;
+    *next += 1;
+    let label = format!("{}\n{}", d.rule, judgment(d, OutputMode::Simplified));
+    let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+    *out += &format!("\t{idx} [label=\"{}\"];\n", label.replace('\n', "\\n"));
+    for premise in &d.premises {
+        let premise = dot_node(premise, next, out);
+        *out += &format!("\t{premise} -> {idx};\n");
+    }
+    idx
+}
+
+fn latex_judgment(d: &Derivation) -> String {
+    let context: Vec<String> = d
+        .context
+        .iter()
+        .map(|assumption| match assumption {
+            Assumption::Var(x, tau) => {
+                format!(
+                    "{} : {}",
+                    latex_expr(&Expr::Var(x.clone(), NodeId::default())),
+                    latex_type(tau)
+                )
+            }
+            Assumption::TyVar(a) => latex_type(&Type::Var(a.clone())),
+        })
+        .collect();
+    let turnstile = match context.is_empty() {
+        true => String::from("\\vdash"),
+        false => context.join(", ") + " \\vdash",
+    };
+    format!("{turnstile} {} : {}", latex_expr(&d.e), latex_type(&d.tau))
+}
+
+/// The derivation as LaTeX source
+pub fn to_latex(d: &Derivation, style: LatexStyle) -> String {
+    let mut out = String::new();
+    match style {
+        LatexStyle::Bussproofs => {
+            out += "\\begin{prooftree}\n";
+            bussproofs(d, &mut out);
+            out += "\\end{prooftree}";
+        }
+        LatexStyle::Mathpartir => {
+            out += "\\begin{mathpar}\n";
+            mathpartir(d, 1, &mut out);
+            out += "\n\\end{mathpar}";
+        }
+    }
+    out
+}
+
+/// Premises first: `bussproofs` keeps the proofs built so far on a stack
+fn bussproofs(d: &Derivation, out: &mut String) {
+    for premise in &d.premises {
+        bussproofs(premise, out);
+    }
+    let inference = match d.premises.len() {
+        0 => {
+            *out += "\\AxiomC{}\n";
+            "Unary"
+        }
+        1 => "Unary",
+        2 => "Binary",
+        _ => "Trinary",
+    };
+    *out += &format!("\\RightLabel{{\\scriptsize {}}}\n", d.rule);
+    *out += &format!("\\{inference}InfC{{${}$}}\n", latex_judgment(d));
+}
+
+fn mathpartir(d: &Derivation, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    *out += &format!("{indent}\\inferrule*[right={}]\n{indent}  {{", d.rule);
+    for (i, premise) in d.premises.iter().enumerate() {
+        *out += if i == 0 { "\n" } else { " \\\\\n" };
+        mathpartir(premise, depth + 2, out);
+    }
+    match d.premises.is_empty() {
+        true => *out += " }\n",
+        false => *out += &format!("\n{indent}  }}\n"),
+    }
+    *out += &format!("{indent}  {{{}}}", latex_judgment(d));
+}
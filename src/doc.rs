@@ -0,0 +1,153 @@
+//! Documents that lay themselves out within a line width, after Wadler's "A prettier printer".
+//!
+//! A document is text with possible line breaks. The breaks of a group are all taken or none
+//! are: a group is printed on one line when it fits, with the text up to the next break after
+//! it, and broken up otherwise.
+
+use std::ops::Add;
+
+#[derive(Clone, Debug)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a line break followed by the indentation when the group is broken up
+    Line,
+    /// Indent the lines after breaks in the document
+    Nest(usize, Box<Doc>),
+    /// Indent the lines after breaks in the document to the column it starts at
+    Align(Box<Doc>),
+    Concat(Vec<Doc>),
+    Group(Box<Doc>),
+    /// A group that is printed on one line when it fits by itself, whatever text follows it
+    Alone(Box<Doc>),
+}
+
+pub fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+pub fn line() -> Doc {
+    Doc::Line
+}
+
+pub fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, Box::new(doc))
+}
+
+pub fn align(doc: Doc) -> Doc {
+    Doc::Align(Box::new(doc))
+}
+
+pub fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+pub fn alone(doc: Doc) -> Doc {
+    Doc::Alone(Box::new(doc))
+}
+
+impl Add for Doc {
+    type Output = Doc;
+
+    fn add(self, other: Doc) -> Doc {
+        match self {
+            Doc::Concat(mut docs) => {
+                docs.push(other);
+                Doc::Concat(docs)
+            }
+            doc => Doc::Concat(vec![doc, other]),
+        }
+    }
+}
+
+impl Add<&str> for Doc {
+    type Output = Doc;
+
+    fn add(self, other: &str) -> Doc {
+        self + text(other)
+    }
+}
+
+/// A document still to print, with its indentation and whether its group is on one line
+type Item<'a> = (usize, bool, &'a Doc);
+
+impl Doc {
+    /// Print the document on one line
+    pub fn flat(&self) -> String {
+        let mut out = String::new();
+        let mut stack = vec![self];
+        while let Some(doc) = stack.pop() {
+            match doc {
+                Doc::Text(s) => out += s,
+                Doc::Line => out.push(' '),
+                Doc::Nest(_, doc) | Doc::Align(doc) | Doc::Group(doc) | Doc::Alone(doc) => {
+                    stack.push(doc)
+                }
+                Doc::Concat(docs) => stack.extend(docs.iter().rev()),
+            }
+        }
+        out
+    }
+
+    /// Lay out the document in `width` columns, as far as its text allows
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        let mut stack: Vec<Item> = vec![(0, false, self)];
+        while let Some((indent, flat, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(s) => {
+                    out += s;
+                    column += s.chars().count();
+                }
+                Doc::Line if flat => {
+                    out.push(' ');
+                    column += 1;
+                }
+                Doc::Line => {
+                    out.push('\n');
+                    out.extend(std::iter::repeat_n(' ', indent));
+                    column = indent;
+                }
+                Doc::Nest(i, doc) => stack.push((indent + i, flat, doc)),
+                Doc::Align(doc) => stack.push((column, flat, doc)),
+                Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, flat, doc))),
+                Doc::Group(doc) => {
+                    let flat = flat || fits(width.saturating_sub(column), doc, &stack);
+                    stack.push((indent, flat, doc))
+                }
+                Doc::Alone(doc) => {
+                    let flat = flat || fits(width.saturating_sub(column), doc, &[]);
+                    stack.push((indent, flat, doc))
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Whether `doc` on one line, followed by `rest` up to its next line break, fits in `width`
+/// columns. Groups in `rest` count as if they were on one line.
+fn fits(mut width: usize, doc: &Doc, rest: &[Item]) -> bool {
+    let mut stack = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+    while let Some((flat, doc)) = stack
+        .pop()
+        .or_else(|| rest.next().map(|&(_, flat, doc)| (flat, doc)))
+    {
+        match doc {
+            Doc::Text(s) => match width.checked_sub(s.chars().count()) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::Line if flat => match width.checked_sub(1) {
+                Some(left) => width = left,
+                None => return false,
+            },
+            Doc::Line => return true,
+            Doc::Nest(_, doc) | Doc::Align(doc) => stack.push((flat, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (flat, doc))),
+            Doc::Group(doc) | Doc::Alone(doc) => stack.push((true, doc)),
+        }
+    }
+    true
+}
@@ -1,25 +1,48 @@
-use crate::{ast::*, do_, monad::Monad};
+use std::{cell::Cell, collections::HashMap, fmt, rc::Rc};
 
-static mut COUNTER: u32 = 0;
-
-unsafe fn inc() -> u32 {
-    unsafe {
-        COUNTER += 1;
-        COUNTER
-    }
-}
+use crate::{
+    arena::{Arena, Child, ExprId, Node},
+    ast::*,
+    do_,
+    monad::Monad,
+};
 
+/// A node of a graph being drawn. The nodes of a graph are numbered by a counter of its own, and
+/// those of a subgraph are prefixed with its name, so that subgraphs can be drawn side by side.
 #[derive(Debug, Clone)]
 struct NodeIndex {
     name: Option<String>,
     idx: u32,
+    counter: Rc<Cell<u32>>,
 }
 
 impl NodeIndex {
-    fn new(name: Option<String>) -> Self {
+    /// The root of a new graph
+    fn root(name: Option<String>) -> Self {
         NodeIndex {
             name,
-            idx: unsafe { inc() },
+            idx: 0,
+            counter: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// A new node of the same graph
+    fn next(&self) -> Self {
+        let idx = self.counter.get() + 1;
+        self.counter.set(idx);
+        NodeIndex {
+            name: self.name.clone(),
+            idx,
+            counter: self.counter.clone(),
+        }
+    }
+}
+
+impl fmt::Display for NodeIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}_{}", self.idx),
+            None => write!(f, "{}", self.idx),
         }
     }
 }
@@ -51,25 +74,127 @@ impl<T> Monad<T> for Writer<T> {
 pub fn to_dot(ast: &Expr, name: Option<String>) -> String {
     match name {
         Some(name) => {
-            let root = NodeIndex::new(Some(name.clone()));
+            let root = NodeIndex::root(Some(name.clone()));
             format!(
                 "subgraph {} {{\n\t{} [shape=point, width=0.1];\n{}\n}}",
                 name,
-                root.clone().idx,
+                root.clone(),
                 ast.to_graph(root).output
             )
         }
         None => {
-            let root = NodeIndex::new(None);
+            let root = NodeIndex::root(None);
             format!(
                 "digraph {{\n\t{} [shape=point, width=0.1];\n{}\n}}",
-                root.clone().idx,
+                root.clone(),
                 ast.to_graph(root).output
             )
         }
     }
 }
 
+/// Like `to_dot`, for a type. A bound variable is a dashed edge back to the node of its binder.
+pub fn type_to_dot(tau: &Type, name: Option<String>) -> String {
+    let root = NodeIndex::root(name.clone());
+    let graph = tau.to_graph(root.clone()).output;
+    match name {
+        Some(name) => format!(
+            "subgraph {} {{\n\t{} [shape=point, width=0.1];\n{}\n}}",
+            name, root, graph
+        ),
+        None => format!(
+            "digraph {{\n\t{} [shape=point, width=0.1];\n{}\n}}",
+            root, graph
+        ),
+    }
+}
+
+/// Like `to_dot`, but a node of a hash-consed arena is drawn once with an edge from each parent
+pub fn to_dot_shared(arena: &Arena, root: ExprId, name: Option<String>) -> String {
+    let parent = NodeIndex::root(name.clone());
+    let graph = shared_graph(arena, root, parent.clone(), &mut HashMap::new());
+    match name {
+        Some(name) => format!(
+            "subgraph {} {{\n\t{} [shape=point, width=0.1];\n{}\n}}",
+            name, parent, graph
+        ),
+        None => format!(
+            "digraph {{\n\t{} [shape=point, width=0.1];\n{}\n}}",
+            parent, graph
+        ),
+    }
+}
+
+fn shared_label(arena: &Arena, id: ExprId) -> String {
+    match arena.node(id) {
+        Node::Num(_) | Node::True | Node::False | Node::Unit | Node::Var(_) => {
+            arena.to_expr(id).to_string()
+        }
+        Node::Addop { binop, .. } => binop.to_string(),
+        Node::Mulop { binop, .. } => binop.to_string(),
+        Node::Relop { relop, .. } => relop.to_string(),
+        Node::If { .. } => String::from("if"),
+        Node::And { .. } => String::from("&&"),
+        Node::Or { .. } => String::from("||"),
+        Node::Pair { .. } => String::from("pair"),
+        Node::App { .. } => String::from("app"),
+        Node::Lam { .. } => String::from("λ"),
+        Node::LinLam { .. } => String::from("λ⊸"),
+        Node::Fix { .. } => String::from("fix"),
+        Node::Project {
+            d: Direction::Left, ..
+        } => String::from("P_left"),
+        Node::Project {
+            d: Direction::Right,
+            ..
+        } => String::from("P_right"),
+        Node::LetPair { .. } => String::from("letpair"),
+        Node::Inject {
+            d: Direction::Left, ..
+        } => String::from("I_left"),
+        Node::Inject {
+            d: Direction::Right,
+            ..
+        } => String::from("I_right"),
+        Node::Case { .. } => String::from("case"),
+        Node::TyApp { .. } => String::from("tyapp"),
+        Node::TyLam { .. } => String::from("Λ"),
+        Node::Fold { .. } => String::from("fold"),
+        Node::Unfold(_) => String::from("unfold"),
+        Node::Import { .. } => String::from("import"),
+        Node::Export { .. } => String::from("export"),
+        Node::Cast { .. } => String::from("cast"),
+    }
+}
+
+fn shared_graph(
+    arena: &Arena,
+    id: ExprId,
+    parent: NodeIndex,
+    drawn: &mut HashMap<ExprId, NodeIndex>,
+) -> String {
+    let label = shared_label(arena, id);
+    if let Some(cur) = drawn.get(&id) {
+        return format!(
+            "\t{} -> {} [label=\"{}\", arrowhead=none, color=\"red\", fontcolor=\"red\"];\n",
+            parent, cur, label
+        );
+    }
+    let Writer {
+        value: cur,
+        mut output,
+    } = new_node(label, parent, "red");
+    drawn.insert(id, cur.clone());
+    for child in arena.node(id).children() {
+        output += &match child {
+            Child::Expr(e) => shared_graph(arena, e, cur.clone(), drawn),
+            Child::Type(tau) => arena.ty(tau).to_graph(cur.clone()).output,
+            Child::Var(x) => Variable::from(arena.name(x)).to_graph(cur.clone()).output,
+        };
+    }
+    output
+}
+
 trait ToGraph {
     fn to_graph(&self, parent: NodeIndex) -> Writer<()>;
 }
@@ -78,15 +203,15 @@ fn new_node<T>(name: T, parent: NodeIndex, color: &str) -> Writer<NodeIndex>
 where
     T: ToString,
 {
-    let cur = NodeIndex::new(parent.name.clone());
+    let cur = parent.next();
     Writer {
         value: cur.clone(),
         output: format!(
             "\t{} [shape=point, width=0.1, color=\"{}\"];\n\t{} -> {} [label=\"{}\", arrowhead=none, color=\"{}\", fontcolor=\"{}\"];\n",
-            cur.clone().idx,
+            cur.clone(),
             color,
-            parent.idx,
-            cur.idx,
+            parent,
+            cur,
             name.to_string(),
             color,
             color,
@@ -103,71 +228,97 @@ impl ToGraph for Variable {
 impl ToGraph for Expr {
     fn to_graph(&self, parent: NodeIndex) -> Writer<()> {
         match self {
-            Expr::Var(_) | Expr::Num(_) | Expr::True | Expr::False | Expr::Unit => do_!(
-                new_node(self, parent, "red"),
-                Writer::ret(())
-            ),
-            Expr::Addop { binop, left, right } => do_!(
+            Expr::Var(..) | Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) => {
+                do_!(new_node(self, parent, "red"), Writer::ret(()))
+            }
+            Expr::Addop {
+                binop, left, right, ..
+            } => do_!(
                 new_node(binop, parent, "red") => cur,
                 left.to_graph(cur.clone()),
                 right.to_graph(cur)
             ),
-            Expr::Mulop { binop, left, right } => do_!(
+            Expr::Mulop {
+                binop, left, right, ..
+            } => do_!(
                 new_node(binop, parent, "red") => cur,
                 left.to_graph(cur.clone()),
                 right.to_graph(cur)
             ),
-            Expr::If { cond, then_, else_ } => do_!(
+            Expr::If {
+                cond, then_, else_, ..
+            } => do_!(
                 new_node("if", parent, "red") => cur,
                 cond.to_graph(cur.clone()),
                 then_.to_graph(cur.clone()),
                 else_.to_graph(cur)
             ),
-            Expr::Relop { left, right, relop } => do_!(
+            Expr::Relop {
+                left, right, relop, ..
+            } => do_!(
                 new_node(relop, parent, "red") => cur,
                 left.to_graph(cur.clone()),
                 right.to_graph(cur)
             ),
-            Expr::And { left, right } => do_!(
+            Expr::And { left, right, .. } => do_!(
                 new_node("&&", parent, "red") => cur,
                 left.to_graph(cur.clone()),
                 right.to_graph(cur)
             ),
-            Expr::Or { left, right } => do_!(
+            Expr::Or { left, right, .. } => do_!(
                 new_node("||", parent, "red") => cur,
                 left.to_graph(cur.clone()),
                 right.to_graph(cur)
             ),
-            Expr::Pair { left, right } => do_!(
+            Expr::Pair { left, right, .. } => do_!(
                 new_node("pair", parent, "red") => cur,
                 left.to_graph(cur.clone()),
                 right.to_graph(cur)
             ),
-            Expr::App { lam, arg } => do_!(
+            Expr::App { lam, arg, .. } => do_!(
                 new_node("app", parent, "red") => cur,
                 lam.to_graph(cur.clone()),
                 arg.to_graph(cur)
             ),
-            Expr::Lam { x, tau, e } => do_!(
+            Expr::Lam { x, tau, e, .. } => do_!(
                 new_node("λ", parent, "red") => cur,
                 x.to_graph(cur.clone()),
                 tau.to_graph(cur.clone()),
                 e.to_graph(cur)
             ),
-            Expr::Fix { x, tau, e } => do_!(
+            Expr::LinLam { x, tau, e, .. } => do_!(
+                new_node("λ⊸", parent, "red") => cur,
+                x.to_graph(cur.clone()),
+                tau.to_graph(cur.clone()),
+                e.to_graph(cur)
+            ),
+            Expr::Fix { x, tau, e, .. } => do_!(
                 new_node("fix", parent, "red") => cur,
                 x.to_graph(cur.clone()),
                 tau.to_graph(cur.clone()),
                 e.to_graph(cur)
             ),
-            Expr::Project { e, d } => do_!(
+            Expr::Project { e, d, .. } => do_!(
                 new_node(match d {
                     Direction::Left => "P_left",
                     Direction::Right => "P_right",
                 }, parent, "red") => cur,
                 e.to_graph(cur)
             ),
-            Expr::Inject { e, d, tau } => do_!(
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                ..
+            } => do_!(
+                new_node("letpair", parent, "red") => cur,
+                x.to_graph(cur.clone()),
+                y.to_graph(cur.clone()),
+                e_pair.to_graph(cur.clone()),
+                e_body.to_graph(cur)
+            ),
+            Expr::Inject { e, d, tau, .. } => do_!(
                 new_node(match d {
                     Direction::Left => "I_left",
                     Direction::Right => "I_right",
@@ -181,6 +332,7 @@ impl ToGraph for Expr {
                 eleft,
                 xright,
                 eright,
+                ..
             } => do_!(
                 new_node("case", parent, "red") => cur,
                 e.to_graph(cur.clone()),
@@ -189,22 +341,22 @@ impl ToGraph for Expr {
                 xright.to_graph(cur.clone()),
                 eright.to_graph(cur)
             ),
-            Expr::TyApp { e, tau } => do_!(
+            Expr::TyApp { e, tau, .. } => do_!(
                 new_node("tyapp", parent, "red") => cur,
                 e.to_graph(cur.clone()),
                 tau.to_graph(cur)
             ),
-            Expr::TyLam { a, e } => do_!(
+            Expr::TyLam { a, e, .. } => do_!(
                 new_node("Λ", parent, "red") => cur,
                 a.to_graph(cur.clone()),
                 e.to_graph(cur)
             ),
-            Expr::Fold { e, tau } => do_!(
+            Expr::Fold { e, tau, .. } => do_!(
                 new_node("fold", parent, "red") => cur,
                 e.to_graph(cur.clone()),
                 tau.to_graph(cur)
             ),
-            Expr::Unfold(e) => do_!(
+            Expr::Unfold(e, _) => do_!(
                 new_node("unfold", parent, "red") => cur,
                 e.to_graph(cur)
             ),
@@ -213,6 +365,7 @@ impl ToGraph for Expr {
                 a,
                 e_mod,
                 e_body,
+                ..
             } => do_!(
                 new_node("import", parent, "red") => cur,
                 x.to_graph(cur.clone()),
@@ -224,53 +377,75 @@ impl ToGraph for Expr {
                 e,
                 tau_adt,
                 tau_mod,
+                ..
             } => do_!(
                 new_node("export", parent, "red") => cur,
                 e.to_graph(cur.clone()),
                 tau_adt.to_graph(cur.clone()),
                 tau_mod.to_graph(cur)
             ),
+            Expr::Cast { e, from, to, .. } => do_!(
+                new_node("cast", parent, "red") => cur,
+                e.to_graph(cur.clone()),
+                from.to_graph(cur.clone()),
+                to.to_graph(cur)
+            ),
         }
     }
 }
 
 impl ToGraph for Type {
     fn to_graph(&self, parent: NodeIndex) -> Writer<()> {
-        match self {
-            Type::Num | Type::Bool | Type::Unit | Type::Var(_) => do_!(
-                new_node(self, parent, "blue"),
-                Writer::ret(())
-            ),
-            Type::Product { left, right } => do_!(
-                new_node("*", parent, "blue") => cur,
-                left.to_graph(cur.clone()),
-                right.to_graph(cur)
-            ),
-            Type::Sum { left, right } => do_!(
-                new_node("+", parent, "blue") => cur,
-                left.to_graph(cur.clone()),
-                right.to_graph(cur)
-            ),
-            Type::Fn { arg, ret } => do_!(
-                new_node("→", parent, "blue") => cur,
-                arg.to_graph(cur.clone()),
-                ret.to_graph(cur)
-            ),
-            Type::Rec { a, tau } => do_!(
-                new_node("μ", parent, "blue") => cur,
-                a.to_graph(cur.clone()),
-                tau.to_graph(cur)
-            ),
-            Type::Forall { a, tau } => do_!(
-                new_node("∀", parent, "blue") => cur,
-                a.to_graph(cur.clone()),
-                tau.to_graph(cur)
-            ),
-            Type::Exists { a, tau } => do_!(
-                new_node("∃", parent, "blue") => cur,
-                a.to_graph(cur.clone()),
-                tau.to_graph(cur)
-            ),
+        type_graph(self, parent, Vec::new())
+    }
+}
+
+/// `binders` holds the nodes and names of the enclosing `μ`, `∀` and `∃`, innermost last
+fn type_graph(tau: &Type, parent: NodeIndex, binders: Vec<(NodeIndex, String)>) -> Writer<()> {
+    match tau {
+        Type::Bound(i) if (*i as usize) < binders.len() => {
+            let (binder, name) = &binders[binders.len() - *i as usize - 1];
+            Writer {
+                value: (),
+                output: format!(
+                    "\t{} -> {} [label=\"{}\", style=dashed, constraint=false, color=\"blue\", fontcolor=\"blue\"];\n",
+                    parent, binder, name
+                ),
+            }
         }
+        Type::Num | Type::Bool | Type::Unit | Type::Var(_) | Type::Bound(_) | Type::Dyn => {
+            do_!(new_node(tau, parent, "blue"), Writer::ret(()))
+        }
+        Type::Product { left, right } => do_!(
+            new_node("*", parent, "blue") => cur,
+            type_graph(left, cur.clone(), binders.clone()),
+            type_graph(right, cur, binders)
+        ),
+        Type::Sum { left, right } => do_!(
+            new_node("+", parent, "blue") => cur,
+            type_graph(left, cur.clone(), binders.clone()),
+            type_graph(right, cur, binders)
+        ),
+        Type::Fn { arg, ret } => do_!(
+            new_node("→", parent, "blue") => cur,
+            type_graph(arg, cur.clone(), binders.clone()),
+            type_graph(ret, cur, binders)
+        ),
+        Type::LinFn { arg, ret } => do_!(
+            new_node("⊸", parent, "blue") => cur,
+            type_graph(arg, cur.clone(), binders.clone()),
+            type_graph(ret, cur, binders)
+        ),
+        Type::Rec { a, tau: body }
+        | Type::Forall { a, tau: body }
+        | Type::Exists { a, tau: body } => do_!(
+            new_node(match tau {
+                Type::Rec { .. } => "μ",
+                Type::Forall { .. } => "∀",
+                _ => "∃",
+            }, parent, "blue") => cur,
+            a.to_graph(cur.clone()),
+            type_graph(body, cur.clone(), [binders, vec![(cur, a.0.clone())]].concat())
+        ),
     }
 }
@@ -1,272 +1,554 @@
+pub use crate::rules::Outcome;
 use crate::{
+    arena::{self, Arena, ExprId},
     ast::*,
     ast_util::Symbol,
-    flags::{format_ast, Mode, OutputMode},
+    flags::{format_ast, format_shared, Format, Mode, OutputMode, Strategy, Trace},
+    gradual::ground,
+    rules::{self, Shape, Store},
+    serialize::expr_to_json,
+    stepdiff::Frame,
+};
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    io::{stdout, IsTerminal},
 };
 
-pub enum Outcome {
-    Step(Expr),
-    Value,
+/// Evaluate a program that cannot fail at runtime, i.e. one without casts
+pub fn eval(e: &Expr, mode: Mode, output: impl Into<Format>) -> Expr {
+    try_eval(e, mode, output).unwrap_or_else(|blame| panic!("Cast failed: {blame}"))
 }
 
-/// The `|->` operator
-fn fall_through(
-    (e, hole): (&Expr, impl FnOnce(Expr) -> Expr),
-    next: impl FnOnce() -> Outcome,
-) -> Outcome {
-    match try_step(e) {
-        Outcome::Step(next_e) => Outcome::Step(hole(next_e)),
-        Outcome::Value => next(),
-    }
+pub fn try_eval(e: &Expr, mode: Mode, output: impl Into<Format>) -> Result<Expr, Blame> {
+    try_eval_with(e, mode, output, Strategy::Name)
 }
 
-/// Syntax sugar for `fall_through`
-macro_rules! free_fall {
-  // Base case
-  ($e:expr) => { $e };
-
-  // Recursive case
-  ($e:expr, $($rest:tt)*) => {
-    fall_through($e, ||{ free_fall!($($rest)*) })
-  };
+pub fn try_eval_with(
+    e: &Expr,
+    mode: Mode,
+    output: impl Into<Format>,
+    strategy: Strategy,
+) -> Result<Expr, Blame> {
+    try_eval_traced(e, mode, output, strategy, Trace::Text)
 }
 
-macro_rules! eval_left {
-    ($binop:ident, $left:ident, $right:ident, $op:tt) => {
-        ($left, |l| Expr::$op {
-            $binop: $binop.clone(),
-            left: Box::new(l),
-            right: $right.clone(),
-        })
+/// Evaluate the contents of the data constructors in a value, which call-by-name leaves as they
+/// are, so that lists built by recursion print in full. Doesn't return for infinite data.
+pub fn force(v: Expr) -> Result<Expr, Blame> {
+    let inner = |e: Box<Expr>| -> Result<Box<Expr>, Blame> {
+        Ok(Box::new(force(try_eval(
+            &e,
+            Mode::Eval,
+            OutputMode::Full,
+        )?)?))
     };
+    Ok(match v {
+        Expr::Pair { left, right, id } => Expr::Pair {
+            left: inner(left)?,
+            right: inner(right)?,
+            id,
+        },
+        Expr::Inject { e, d, tau, id } => Expr::Inject {
+            e: inner(e)?,
+            d,
+            tau,
+            id,
+        },
+        Expr::Fold { e, tau, id } => Expr::Fold {
+            e: inner(e)?,
+            tau,
+            id,
+        },
+        Expr::Export {
+            e,
+            tau_adt,
+            tau_mod,
+            id,
+        } => Expr::Export {
+            e: inner(e)?,
+            tau_adt,
+            tau_mod,
+            id,
+        },
+        Expr::Cast {
+            e,
+            from,
+            to,
+            blame,
+            id,
+        } => Expr::Cast {
+            e: inner(e)?,
+            from,
+            to,
+            blame,
+            id,
+        },
+        v => v,
+    })
 }
 
-macro_rules! eval_right {
-    ($binop:ident, $left:ident, $right:ident, $op:tt) => {
-        ($right, |r| Expr::$op {
-            $binop: $binop.clone(),
-            left: $left.clone(),
-            right: Box::new(r),
-        })
-    };
+/// Evaluate, printing the steps in `trace` format in very verbose mode. Pictures of the steps
+/// are drawn by `try_eval_frames` instead.
+pub fn try_eval_traced(
+    e: &Expr,
+    mode: Mode,
+    output: impl Into<Format>,
+    strategy: Strategy,
+    trace: Trace,
+) -> Result<Expr, Blame> {
+    let format = output.into();
+    let machine = Machine::new(strategy);
+    let mut e = e.clone();
+    let mut n = 0;
+    loop {
+        // the thunks as they are before the step, printed along with the expression
+        let heap = match mode {
+            Mode::VeryVerbose if format.output != OutputMode::Graphviz => machine.heap(),
+            _ => Vec::new(),
+        };
+        match machine.step_traced(&e) {
+            (Outcome::Step(e_stepped), record) => {
+                if let (Mode::VeryVerbose, Some(record)) = (mode, record) {
+                    n += 1;
+                    match trace {
+                        Trace::Text => print_step(n, &e, &heap, &record, format),
+                        Trace::Json => println!("{}", json_step(n, &e, &record, format)),
+                        Trace::Html | Trace::Frames => {}
+                    }
+                }
+                e = e_stepped
+            }
+            (Outcome::Value, _) => return Ok(machine.read_back(e)),
+            (Outcome::Blame(blame), _) => return Err(blame),
+        }
+    }
 }
 
-static mut COUNTER: u32 = 0;
+/// Evaluate, drawing each step. The frames drawn before a cast failed are returned too.
+pub fn try_eval_frames(e: &Expr, strategy: Strategy) -> (Vec<Frame>, Result<Expr, Blame>) {
+    let machine = Machine::new(strategy);
+    let mut e = e.clone();
+    let mut frames = Vec::new();
+    loop {
+        let heap = machine.heap();
+        match machine.step_traced(&e) {
+            (Outcome::Step(e_stepped), record) => {
+                if let Some(record) = record {
+                    let n = frames.len() as u32 + 1;
+                    // under call-by-need, the thunk the step happened in
+                    frames.push(match record.thunk {
+                        None => Frame::new(n, &e, &record, &e_stepped),
+                        Some(i) => Frame::new(n, &heap[i], &record, &machine.heap()[i]),
+                    });
+                }
+                e = e_stepped
+            }
+            (Outcome::Value, _) => return (frames, Ok(machine.read_back(e))),
+            (Outcome::Blame(blame), _) => return (frames, Err(blame)),
+        }
+    }
+}
 
-unsafe fn inc() -> u32 {
-    unsafe {
-        COUNTER += 1;
-        COUNTER
+/// An expression with the subterm at `path` colored, if printing to a terminal
+pub(crate) fn highlight(e: &Expr, path: &[usize], format: Format) -> String {
+    if !stdout().is_terminal()
+        || !matches!(format.output, OutputMode::Full | OutputMode::Simplified)
+    {
+        return format_ast(e, format, None);
+    }
+    // print a placeholder in place of the redex, then the redex in its place
+    let placeholder = "⟦redex⟧";
+    let mut e = e.clone();
+    let mut redex = &mut e;
+    for &i in path {
+        redex = redex.children_mut().swap_remove(i);
     }
+    let redex = std::mem::replace(
+        redex,
+        Expr::Var(Variable::from(placeholder), NodeId::default()),
+    );
+    format_ast(&e, format, None).replacen(
+        placeholder,
+        &format!("\x1b[1;31m{}\x1b[0m", format_ast(&redex, format, None)),
+        1,
+    )
 }
 
-pub fn eval(e: &Expr, mode: Mode, output: OutputMode) -> Expr {
-    match try_step(e) {
-        Outcome::Step(e_stepped) => {
-            if mode == Mode::VeryVerbose {
-                println!(
-                    "{}",
-                    format_ast(e, output, Some(format!("step{}", unsafe { inc() })))
-                )
-            }
-            eval(&e_stepped, mode, output)
-        }
-        Outcome::Value => e.clone(),
+fn print_step(n: u32, e: &Expr, heap: &[Expr], record: &StepRecord, format: Format) {
+    if format.output == OutputMode::Graphviz {
+        println!("{}", format_ast(e, format, Some(format!("step{n}"))));
+        return println!("// step{n}: {} at {:?}", record.rule, record.path);
+    }
+    match record.thunk {
+        None => println!("{}", highlight(e, &record.path, format)),
+        Some(_) => println!("{}", format_ast(e, format, None)),
+    }
+    match record.thunk {
+        None => println!("  {} at {:?}", record.rule, record.path),
+        Some(i) => println!(
+            "  {} in {} at {:?}",
+            record.rule,
+            location(i).0,
+            record.path
+        ),
+    }
+    for (i, thunk) in heap.iter().enumerate() {
+        let thunk = match record.thunk {
+            Some(j) if i == j => highlight(thunk, &record.path, format),
+            _ => format_ast(thunk, format, None),
+        };
+        println!("  {} = {}", location(i).0, thunk)
     }
 }
 
-pub fn try_step(expr: &Expr) -> Outcome {
-    match expr {
-        Expr::Lam { .. }
-        | Expr::Num { .. }
-        | Expr::True
-        | Expr::False
-        | Expr::Pair { .. }
-        | Expr::Unit
-        | Expr::Inject { .. }
-        | Expr::TyLam { .. }
-        | Expr::Export { .. }
-        | Expr::Fold { .. } => Outcome::Value,
-        // 1. arithmetic
-        Expr::Addop { binop, left, right } => free_fall!(
-            eval_left!(binop, left, right, Addop),
-            eval_right!(binop, left, right, Addop),
-            if let (Expr::Num(l), Expr::Num(r)) = (left.as_ref(), right.as_ref()) {
-                match binop {
-                    AddOp::Add => Outcome::Step(Expr::Num(l + r)),
-                    AddOp::Sub => Outcome::Step(Expr::Num(l - r)),
+/// The step object described in `serialize`
+fn json_step(n: u32, e: &Expr, record: &StepRecord, format: Format) -> Value {
+    // expressions are printed in the selected output mode, except that pictures are unwieldy
+    let format = match format.output {
+        OutputMode::Graphviz | OutputMode::Svg | OutputMode::Html => Format {
+            output: OutputMode::Full,
+            ..format
+        },
+        _ => format,
+    };
+    // JSON terms are embedded as trees, the other formats as strings
+    let term = |e: &Expr| match format.output {
+        OutputMode::Json => expr_to_json(e),
+        _ => Value::from(format_ast(e, format, None)),
+    };
+    json!({
+        "step": n,
+        "rule": record.rule,
+        "path": record.path,
+        "thunk": record.thunk,
+        "term": term(e),
+        "redex": term(&record.redex),
+        "result": term(&record.result),
+    })
+}
+
+/// Evaluate with call-by-name on `arena`, which is hash-consed for `--share`. Reports the size
+/// of the result as a tree and as a DAG in verbose modes.
+pub fn try_eval_arena(
+    e: &Expr,
+    mut arena: Arena,
+    mode: Mode,
+    format: Format,
+) -> Result<(Arena, ExprId), Blame> {
+    let mut e = arena.from_expr(e);
+    for step in 1.. {
+        match arena.step(e) {
+            arena::Outcome::Step(next) => {
+                if mode == Mode::VeryVerbose {
+                    println!(
+                        "{}",
+                        format_shared(&arena, e, format, Some(format!("step{step}")))
+                    )
                 }
-            } else {
-                unreachable!()
+                e = arena.reclaim(next)
             }
-        ),
-        Expr::Mulop { binop, left, right } => free_fall!(
-            eval_left!(binop, left, right, Mulop),
-            eval_right!(binop, left, right, Mulop),
-            if let (Expr::Num(l), Expr::Num(r)) = (left.as_ref(), right.as_ref()) {
-                match binop {
-                    MulOp::Mul => Outcome::Step(Expr::Num(l * r)),
-                    MulOp::Div => Outcome::Step(Expr::Num(l / r)),
-                }
+            arena::Outcome::Value => break,
+            arena::Outcome::Blame(blame) => return Err(blame),
+        }
+    }
+    if mode >= Mode::Verbose {
+        println!(
+            "{}size: {} nodes as a tree, {} shared ({} in the arena)",
+            if format.output == OutputMode::Graphviz {
+                "// "
             } else {
-                unreachable!()
-            }
-        ),
-        // 2. conditionals
-        Expr::If { cond, then_, else_ } => free_fall!(
-            (cond, |c| Expr::If {
-                cond: Box::new(c),
-                then_: then_.clone(),
-                else_: else_.clone(),
-            }),
-            match cond.as_ref() {
-                Expr::True => Outcome::Step(*then_.clone()),
-                Expr::False => Outcome::Step(*else_.clone()),
-                _ => unreachable!(),
+                ""
+            },
+            arena.tree_size(e),
+            arena.dag_size(e),
+            arena.len()
+        )
+    }
+    Ok((arena, e))
+}
+
+/// Apply a cast to a value
+pub(crate) fn cast_value(v: &Expr, from: &Type, to: &Type, blame: &Blame) -> Outcome {
+    rules::cast_value(&mut &Machine::new(Strategy::Name), v, from, to, blame)
+}
+
+/// One step with the call-by-name rules
+pub fn try_step(expr: &Expr) -> Outcome {
+    Machine::new(Strategy::Name).step(expr)
+}
+
+/// `try_step` along with which rule fired where
+pub fn try_step_traced(expr: &Expr) -> (Outcome, Option<StepRecord>) {
+    Machine::new(Strategy::Name).step_traced(expr)
+}
+
+/// The variable standing for the `i`th thunk. `ℓ` can't appear in source programs.
+fn location(i: usize) -> Variable {
+    Variable::from(format!("ℓ{i}"))
+}
+
+fn thunk_index(x: &Variable) -> Option<usize> {
+    x.0.strip_prefix('ℓ')?.parse().ok()
+}
+
+/// Which step was taken
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepRecord {
+    /// The name of the rule, e.g. `E-AppLam`
+    pub rule: &'static str,
+    /// The indices into `Expr::children` leading from the term, or from the thunk, to the redex
+    pub path: Vec<usize>,
+    /// The thunk the redex is in, under call-by-need
+    pub thunk: Option<usize>,
+    pub redex: Expr,
+    /// What the redex was contracted to
+    pub result: Expr,
+}
+
+/// The rule that contracts a redex
+fn rule(redex: &Expr) -> &'static str {
+    match redex {
+        Expr::Addop { binop, .. } => match binop {
+            AddOp::Add => "E-Add",
+            AddOp::Sub => "E-Sub",
+        },
+        Expr::Mulop { binop, .. } => match binop {
+            MulOp::Mul => "E-Mul",
+            MulOp::Div => "E-Div",
+        },
+        Expr::Relop { relop, .. } => match relop {
+            RelOp::Lt => "E-Lt",
+            RelOp::Gt => "E-Gt",
+            RelOp::Eq => "E-Eq",
+        },
+        Expr::If { cond, .. } => match cond.as_ref() {
+            Expr::True(_) => "E-IfTrue",
+            _ => "E-IfFalse",
+        },
+        Expr::And { .. } => "E-And",
+        Expr::Or { .. } => "E-Or",
+        Expr::App { lam, .. } => match lam.as_ref() {
+            Expr::Cast { .. } => "E-AppCast",
+            _ => "E-AppLam",
+        },
+        // a thunk that is done
+        Expr::Var(..) => "E-Lookup",
+        Expr::Project { d, .. } => match d {
+            Direction::Left => "E-ProjL",
+            Direction::Right => "E-ProjR",
+        },
+        Expr::LetPair { .. } => "E-LetPair",
+        Expr::Case { e, .. } => match e.as_ref() {
+            Expr::Inject {
+                d: Direction::Left, ..
+            } => "E-CaseInl",
+            _ => "E-CaseInr",
+        },
+        Expr::Fix { .. } => "E-Fix",
+        Expr::TyApp { .. } => "E-TyAppTyLam",
+        Expr::Unfold(..) => "E-UnfoldFold",
+        Expr::Import { .. } => "E-ImportExport",
+        Expr::Cast { from, to, .. } => match (from.as_ref(), to.as_ref()) {
+            _ if Type::alpha_equiv(*from.clone(), *to.clone()) => "E-CastId",
+            (_, Type::Dyn) => "E-CastGround",
+            (Type::Dyn, _) if ground(to) == **to => "E-CastCollapse",
+            (Type::Dyn, _) => "E-CastExpand",
+            (Type::Product { .. }, _) => "E-CastPair",
+            (Type::Sum { .. }, _) => "E-CastInj",
+            (Type::Rec { .. }, _) => "E-CastFold",
+            (Type::Forall { .. }, _) => "E-CastTyLam",
+            _ => "E-CastExport",
+        },
+        _ => unreachable!("{redex:?} is a value"),
+    }
+}
+
+/// Small-step evaluator for one strategy. Under call-by-need, arguments are bound to thunks
+/// in `heap`: a thunk is a variable `ℓi` that steps its expression in place when evaluated,
+/// so that the work is shared by every occurrence.
+pub struct Machine {
+    strategy: Strategy,
+    heap: RefCell<Vec<Expr>>,
+    /// The step being recorded, whose path is built from the redex outwards
+    pending: RefCell<Option<StepRecord>>,
+}
+
+impl Machine {
+    pub fn new(strategy: Strategy) -> Self {
+        Machine {
+            strategy,
+            heap: RefCell::new(Vec::new()),
+            pending: RefCell::new(None),
+        }
+    }
+
+    /// The expressions of the thunks allocated so far
+    pub fn heap(&self) -> Vec<Expr> {
+        self.heap.borrow().clone()
+    }
+
+    /// What a bound variable is replaced with
+    fn pass(&self, arg: &Expr) -> Expr {
+        match (self.strategy, arg) {
+            (
+                Strategy::Need,
+                Expr::Var(..)
+                | Expr::Lam { .. }
+                | Expr::LinLam { .. }
+                | Expr::Num(..)
+                | Expr::True(_)
+                | Expr::False(_)
+                | Expr::Unit(_)
+                | Expr::TyLam { .. },
+            )
+            | (Strategy::Value | Strategy::Name, _) => arg.clone(),
+            (Strategy::Need, _) => {
+                let mut heap = self.heap.borrow_mut();
+                heap.push(arg.clone());
+                Expr::Var(location(heap.len() - 1), NodeId::default())
             }
-        ),
-        Expr::Relop { relop, left, right } => free_fall!(
-            eval_left!(relop, left, right, Relop),
-            eval_right!(relop, left, right, Relop),
-            if let (Expr::Num(l), Expr::Num(r)) = (left.as_ref(), right.as_ref()) {
-                let result = match relop {
-                    RelOp::Lt => l < r,
-                    RelOp::Gt => l > r,
-                    RelOp::Eq => l == r,
-                };
-                match result {
-                    true => Outcome::Step(Expr::True),
-                    false => Outcome::Step(Expr::False),
+        }
+    }
+
+    /// Step the `i`th thunk, or replace the variable with its value if it is done
+    fn force(&self, x: &Variable, i: usize) -> Outcome {
+        let thunk = self.heap.borrow()[i].clone();
+        match self.step_in(&thunk) {
+            Outcome::Step(next) => {
+                if let Some(record) = self.pending.borrow_mut().as_mut() {
+                    // the innermost thunk, if they are nested
+                    record.thunk.get_or_insert(i);
                 }
-            } else {
-                unreachable!()
-            }
-        ),
-        Expr::And { left, right } => free_fall!(
-            (left, |l| Expr::And {
-                left: Box::new(l),
-                right: right.clone(),
-            }),
-            (right, |r| Expr::And {
-                left: left.clone(),
-                right: Box::new(r),
-            }),
-            match (left.as_ref(), right.as_ref()) {
-                (Expr::True, Expr::True) => Outcome::Step(Expr::True),
-                (Expr::False, _) => Outcome::Step(Expr::False),
-                (Expr::True, Expr::False) => Outcome::Step(Expr::False),
-                _ => unreachable!("{left:?} {right:?}"),
-            }
-        ),
-        Expr::Or { left, right } => free_fall!(
-            (left, |l| Expr::Or {
-                left: Box::new(l),
-                right: right.clone(),
-            }),
-            (right, |r| Expr::Or {
-                left: left.clone(),
-                right: Box::new(r),
-            }),
-            match (left.as_ref(), right.as_ref()) {
-                (Expr::False, Expr::False) => Outcome::Step(Expr::False),
-                (Expr::True, _) => Outcome::Step(Expr::True),
-                (Expr::False, Expr::True) => Outcome::Step(Expr::True),
-                _ => unreachable!("{left:?} {right:?}"),
-            }
-        ),
-        // 3. functions
-        Expr::App { lam, arg } => free_fall!(
-            (lam, |l| Expr::App {
-                lam: Box::new(l),
-                arg: arg.clone(),
-            }),
-            match lam.as_ref() {
-                Expr::Lam { x, e, .. } =>
-                    Outcome::Step(e.clone().substitute(x.clone(), *arg.clone())),
-                _ => unreachable!(),
-            }
-        ),
-        Expr::Var(x) => unreachable!("Free variable {x:?} should be found in type checking"),
-        // 4. product types
-        Expr::Project { e, d } => free_fall!(
-            (e, |e| Expr::Project {
-                e: Box::new(e),
-                d: d.clone()
-            }),
-            match e.as_ref() {
-                Expr::Pair { left, right } => match d {
-                    Direction::Left => Outcome::Step(*left.clone()),
-                    Direction::Right => Outcome::Step(*right.clone()),
-                },
-                _ => unreachable!(),
+                self.heap.borrow_mut()[i] = next;
+                Outcome::Step(Expr::Var(x.clone(), NodeId::default()))
             }
-        ),
-        // 5. sum types
-        Expr::Case {
-            e,
-            xleft,
-            eleft,
-            xright,
-            eright,
-        } => {
-            free_fall!(
-                (e, |e| Expr::Case {
-                    e: Box::new(e),
-                    xleft: xleft.clone(),
-                    eleft: eleft.clone(),
-                    xright: xright.clone(),
-                    eright: eright.clone(),
-                }),
-                match e.as_ref() {
-                    Expr::Inject { e, d, .. } => match d {
-                        Direction::Left =>
-                            Outcome::Step(eleft.clone().substitute(xleft.clone(), *e.clone())),
-                        Direction::Right =>
-                            Outcome::Step(eright.clone().substitute(xright.clone(), *e.clone())),
-                    },
-                    _ => unreachable!(),
-                }
-            )
+            Outcome::Value => Outcome::Step(thunk),
+            Outcome::Blame(blame) => Outcome::Blame(blame),
         }
-        // 6. fixpoints
-        Expr::Fix { x, e, .. } => Outcome::Step(e.clone().substitute(x.clone(), expr.clone())),
-        // 7. polymorphism
-        Expr::TyApp { e, tau } => free_fall!(
-            (e, |e| Expr::TyApp {
-                e: Box::new(e),
-                tau: tau.clone(),
-            }),
-            match e.as_ref() {
-                Expr::TyLam { e, .. } => Outcome::Step(*e.clone()),
-                _ => unreachable!("{e:?}"),
-            }
-        ),
-        // 8. recursive types
-        Expr::Unfold(e) => free_fall!(
-            (e, |e| Expr::Unfold(Box::new(e))),
-            match e.as_ref() {
-                Expr::Fold { e, .. } => Outcome::Step(*e.clone()),
-                _ => unreachable!(),
+    }
+
+    /// Replace the thunks left in a value with their expressions
+    pub fn read_back(&self, e: Expr) -> Expr {
+        let mut e = e;
+        while let Some((x, i)) = e
+            .free_vars()
+            .into_iter()
+            .find_map(|x| thunk_index(&x).map(|i| (x, i)))
+        {
+            e = e.substitute(x, self.heap.borrow()[i].clone());
+        }
+        e
+    }
+
+    pub fn step(&self, expr: &Expr) -> Outcome {
+        self.step_traced(expr).0
+    }
+
+    /// A step along with which rule fired where
+    pub fn step_traced(&self, expr: &Expr) -> (Outcome, Option<StepRecord>) {
+        self.pending.take();
+        let outcome = self.step_in(expr);
+        let record = self.pending.take().map(|mut record| {
+            record.path.reverse();
+            record
+        });
+        (outcome, record)
+    }
+
+    /// Step a subterm, recording the redex if it is one
+    fn step_in(&self, expr: &Expr) -> Outcome {
+        let outcome = rules::step(&mut &*self, expr);
+        if let Outcome::Step(result) = &outcome {
+            let mut pending = self.pending.borrow_mut();
+            if pending.is_none() {
+                *pending = Some(StepRecord {
+                    rule: rule(expr),
+                    path: Vec::new(),
+                    thunk: None,
+                    redex: expr.clone(),
+                    result: result.clone(),
+                });
             }
-        ),
-        // 9. existential types
-        Expr::Import {
-            x,
-            a,
-            e_mod,
-            e_body,
-        } => free_fall!(
-            (e_mod, |e_mod| Expr::Import {
-                x: x.clone(),
-                a: a.clone(),
-                e_mod: Box::new(e_mod),
-                e_body: e_body.clone(),
-            }),
-            match e_mod.as_ref() {
-                Expr::Export { e, .. } =>
-                    Outcome::Step(e_body.clone().substitute(x.clone(), *e.clone())),
-                _ => unreachable!(),
+        }
+        outcome
+    }
+}
+
+impl Store for &Machine {
+    type E<'a> = &'a Expr;
+    type T<'a> = &'a Type;
+    type V<'a> = &'a Variable;
+    type Term = Expr;
+    type Ty = Type;
+    type Var = Variable;
+
+    fn view<'a>(&self, e: Self::E<'a>) -> Shape<Self::E<'a>, Self::T<'a>, Self::V<'a>> {
+        Shape::of(e)
+    }
+
+    fn ty<'b, 'a: 'b>(&'b self, tau: &'a Type) -> &'b Type {
+        tau
+    }
+
+    fn name(&self, x: &Variable) -> Variable {
+        x.clone()
+    }
+
+    fn term(&self, e: &Expr) -> Expr {
+        e.clone()
+    }
+
+    fn own_type(&self, tau: &Type) -> Type {
+        tau.clone()
+    }
+
+    fn own_var(&self, x: &Variable) -> Variable {
+        x.clone()
+    }
+
+    fn alloc(&mut self, shape: Shape<Expr, Type, Variable>) -> Expr {
+        Expr::from(shape).with_id(NodeId::fresh())
+    }
+
+    fn alloc_type(&mut self, tau: Type) -> Type {
+        tau
+    }
+
+    /// The node keeps its id
+    fn rebuild(&mut self, e: &Expr, shape: Shape<Expr, Type, Variable>) -> Expr {
+        Expr::from(shape).with_id(e.id())
+    }
+
+    fn substitute(&mut self, e: Expr, x: Variable, v: Expr) -> Expr {
+        e.substitute(x, v)
+    }
+
+    fn lazy(&self) -> bool {
+        self.strategy != Strategy::Value
+    }
+
+    fn pass(&mut self, arg: &Expr) -> Expr {
+        Machine::pass(self, arg)
+    }
+
+    fn lookup(&mut self, x: &Variable) -> Outcome {
+        match thunk_index(x) {
+            Some(i) => self.force(x, i),
+            None => unreachable!("Free variable {x:?} should be found in type checking"),
+        }
+    }
+
+    fn step_in(&mut self, e: &Expr) -> Outcome {
+        Machine::step_in(self, e)
+    }
+
+    /// The path within a thunk ends at the thunk
+    fn descended(&mut self, i: usize) {
+        if let Some(record) = self.pending.borrow_mut().as_mut() {
+            if record.thunk.is_none() {
+                record.path.push(i);
             }
-        ),
+        }
     }
 }
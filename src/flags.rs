@@ -1,12 +1,44 @@
 use clap::ValueEnum;
 
-use crate::{ast::{Expr, Type}, ast_util::Symbol, dotgen::to_dot};
+use crate::{
+    arena::{Arena, ExprId},
+    ast::{Expr, Type},
+    ast_util::Symbol,
+    derivation::{self, to_latex, to_text, LatexStyle},
+    dotgen::{to_dot, to_dot_shared, type_to_dot},
+    pretty::{latex_expr, latex_type, simplified, simplified_type, value, WIDTH},
+    serialize::{expr_to_json, expr_to_sexp, type_to_json, type_to_sexp},
+    svggen::{expr_tree, to_html, to_svg, type_tree},
+    typecheck::Derivation,
+};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Mode {
     /// Parse and print the input expression
     Parse,
 
+    /// Print the input expression in canonical concrete syntax that parses back to it
+    Fmt,
+
+    /// Type check and print the typing derivation
+    Derivation,
+
+    /// Check whether two programs of the same type have the same beta-eta normal form
+    Equiv,
+
+    /// Run the `.lam` programs of a directory and compare their types, values or errors with
+    /// the expected ones, written in comments or in `.expected` files. See `golden`.
+    Test,
+
+    /// Step through evaluation interactively, reading commands from stdin
+    Debug,
+
+    /// Serve the Debug Adapter Protocol on stdin/stdout. The program is given by `launch`.
+    Dap,
+
+    /// Serve the Language Server Protocol on stdin/stdout
+    Lsp,
+
     /// Evaluate and print the result
     Eval,
 
@@ -30,22 +62,152 @@ pub enum OutputMode {
 
     /// Graphviz code
     Graphviz,
+
+    /// JSON, one value per line. The schema is described in `serialize`.
+    Json,
+
+    /// S-expressions, one per line, with the same nodes as `json`
+    Sexp,
+
+    /// LaTeX: expressions in source notation and a typewriter font, types for math mode
+    Latex,
+
+    /// An SVG picture of the tree, laid out without Graphviz
+    Svg,
+
+    /// A web page with the SVG picture, in which clicking a node collapses its subtrees
+    Html,
 }
 
-pub fn format_ast(ast: &Expr, output_mode: OutputMode, name: Option<String>) -> String {
-    match output_mode {
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// Concrete syntax
+    #[default]
+    Source,
+
+    /// An expression as printed by the `json` output mode
+    Json,
+
+    /// An expression as printed by the `sexp` output mode
+    Sexp,
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Call-by-value: evaluate arguments, and the contents of pairs, injections, folds and exports
+    Value,
+
+    /// Call-by-name: substitute arguments unevaluated
+    #[default]
+    Name,
+
+    /// Call-by-need: substitute a thunk that is evaluated at most once
+    Need,
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum Trace {
+    /// Each term with its redex colored, followed by the rule
+    #[default]
+    Text,
+
+    /// One JSON object per line with the rule, the path to the redex, the redex and its result
+    Json,
+
+    /// An HTML page stepping through pictures of the term before and after each step
+    Html,
+
+    /// A directory with the picture of each step as an SVG file
+    Frames,
+}
+
+/// How the `format_*` functions print
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Format {
+    pub output: OutputMode,
+    /// The line width `OutputMode::Simplified` fits expressions and types into
+    pub width: usize,
+}
+
+/// The output mode at the default width
+impl From<OutputMode> for Format {
+    fn from(output: OutputMode) -> Self {
+        Format {
+            output,
+            width: WIDTH,
+        }
+    }
+}
+
+pub fn format_ast(ast: &Expr, format: impl Into<Format>, name: Option<String>) -> String {
+    let format = format.into();
+    match format.output {
         OutputMode::Full => format!("{:?}", ast),
-        OutputMode::Simplified => format!("{}", ast),
+        OutputMode::Simplified => simplified(ast, format.width),
         OutputMode::DeBruijn => format!("{:?}", ast.clone().to_debruijn()),
         OutputMode::Graphviz => to_dot(ast, name),
+        OutputMode::Json => expr_to_json(ast).to_string(),
+        OutputMode::Sexp => expr_to_sexp(ast),
+        OutputMode::Latex => latex_expr(ast),
+        OutputMode::Svg => to_svg(&expr_tree(ast)),
+        OutputMode::Html => to_html(&expr_tree(ast)),
     }
 }
 
-pub fn format_type(ty: &Type, output_mode: OutputMode) -> String {
-    match output_mode {
+/// Format the result of evaluating a program of type `ty`. Simplified output shows lists, options
+/// and tuples the way they are usually written.
+pub fn format_value(
+    result: &Expr,
+    ty: &Type,
+    format: impl Into<Format>,
+    name: Option<String>,
+) -> String {
+    let format = format.into();
+    match format.output {
+        OutputMode::Simplified => value(result, ty, format.width),
+        _ => format_ast(result, format, name),
+    }
+}
+
+/// Format an expression of a hash-consed arena. Only graphviz output keeps the sharing.
+pub fn format_shared(
+    arena: &Arena,
+    id: ExprId,
+    format: impl Into<Format>,
+    name: Option<String>,
+) -> String {
+    let format = format.into();
+    match format.output {
+        OutputMode::Graphviz => to_dot_shared(arena, id, name),
+        _ => format_ast(&arena.to_expr(id), format, name),
+    }
+}
+
+pub fn format_type(ty: &Type, format: impl Into<Format>, name: Option<String>) -> String {
+    let format = format.into();
+    match format.output {
         OutputMode::Full => format!("{:?}", ty),
-        OutputMode::Simplified => format!("{}", ty),
+        OutputMode::Simplified => simplified_type(ty, format.width),
         OutputMode::DeBruijn => format!("{:?}", ty.clone().to_debruijn()),
-        OutputMode::Graphviz => String::new(),
+        OutputMode::Graphviz => type_to_dot(ty, name),
+        OutputMode::Json => type_to_json(ty).to_string(),
+        OutputMode::Sexp => type_to_sexp(ty),
+        OutputMode::Latex => latex_type(ty),
+        OutputMode::Svg => to_svg(&type_tree(ty)),
+        OutputMode::Html => to_html(&type_tree(ty)),
+    }
+}
+
+/// Format a typing derivation. LaTeX output is a proof tree in `style`, anything else an indented
+/// tree of judgments.
+pub fn format_derivation(
+    derivation: &Derivation,
+    output_mode: OutputMode,
+    style: LatexStyle,
+) -> String {
+    match output_mode {
+        OutputMode::Latex => to_latex(derivation, style),
+        OutputMode::Graphviz => derivation::to_dot(derivation),
+        _ => to_text(derivation, output_mode),
     }
 }
\ No newline at end of file
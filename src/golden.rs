@@ -0,0 +1,174 @@
+//! Golden tests: `.lam` programs along with what running them should give. The expectations are
+//! lines at the top of the program
+//!
+//! ```text
+//! # type: num
+//! # value: 120
+//! ```
+//!
+//! or the same lines without `# ` in a sibling `.expected` file, which takes precedence. A
+//! program that should be rejected expects `error: parse`, `error: type` or `error: blame`.
+//! Only the given expectations are checked, and blessing writes all of them.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    evaluate::{force, try_eval},
+    flags::{Mode, OutputMode},
+    gradual::elaborate,
+    parser::parse_with_spans,
+    pretty::value,
+    typecheck::type_check,
+};
+
+const KEYS: [&str; 3] = ["type", "value", "error"];
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    /// Programs whose expectations were updated by blessing
+    pub blessed: usize,
+}
+
+/// What running a program gives, as expectation lines. Types and values are in simplified
+/// notation on one line.
+pub fn run(program: &str) -> Vec<String> {
+    let (ast, spans) = match parse_with_spans(program) {
+        Ok(parsed) => parsed,
+        Err(_) => return vec![String::from("error: parse")],
+    };
+    let tau = match type_check(&ast) {
+        Ok(tau) => tau,
+        Err(_) => return vec![String::from("error: type")],
+    };
+    match try_eval(&elaborate(&ast, &spans), Mode::Eval, OutputMode::Simplified).and_then(force) {
+        Ok(result) => vec![
+            format!("type: {tau}"),
+            format!("value: {}", value(&result, &tau, usize::MAX)),
+        ],
+        Err(_) => vec![format!("type: {tau}"), String::from("error: blame")],
+    }
+}
+
+/// The key of an expectation line, e.g. `type` for `type: num`
+fn key(line: &str) -> Option<&str> {
+    let (key, _) = line.split_once(": ")?;
+    KEYS.contains(&key).then_some(key)
+}
+
+/// The expectations in the comments at the top of a program, with their line numbers
+fn comments(program: &str) -> Vec<(usize, String)> {
+    program
+        .lines()
+        .enumerate()
+        .map_while(|(i, line)| Some((i, line.strip_prefix('#')?.trim())))
+        .filter(|(_, line)| key(line).is_some())
+        .map(|(i, line)| (i, line.to_string()))
+        .collect()
+}
+
+fn expected_path(path: &Path) -> PathBuf {
+    path.with_extension("expected")
+}
+
+/// The programs in `path`, a `.lam` file or a directory searched recursively, in order
+fn programs(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut found = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(programs(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "lam") {
+            found.push(path);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Replace the expectations of a program. Those in comments are rewritten in place, otherwise the
+/// `.expected` file is.
+fn write_expectations(path: &Path, program: &str, actual: &[String]) -> io::Result<()> {
+    let comments = comments(program);
+    if expected_path(path).exists() || comments.is_empty() {
+        return fs::write(expected_path(path), actual.join("\n") + "\n");
+    }
+    let mut lines: Vec<String> = Vec::new();
+    for (i, line) in program.lines().enumerate() {
+        if i == comments[0].0 {
+            lines.extend(actual.iter().map(|line| format!("# {line}")));
+        }
+        if !comments.iter().any(|(j, _)| *j == i) {
+            lines.push(line.to_string());
+        }
+    }
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+/// Run the programs in `path`, writing a line for each failure with the difference between the
+/// expected and the actual lines, followed by a summary. With `bless`, failing programs get their
+/// actual results as expectations instead.
+pub fn test(path: &Path, bless: bool, out: &mut impl io::Write) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+    for path in programs(path)? {
+        let program = fs::read_to_string(&path)?;
+        let expected: Vec<String> = match fs::read_to_string(expected_path(&path)) {
+            Ok(expected) => expected.lines().map(str::to_string).collect(),
+            Err(_) => comments(&program)
+                .into_iter()
+                .map(|(_, line)| line)
+                .collect(),
+        };
+        let actual = run(&program);
+        // only the expected keys are compared
+        let keys: Vec<&str> = expected.iter().filter_map(|line| key(line)).collect();
+        let compared: Vec<&String> = actual
+            .iter()
+            .filter(|line| key(line).is_some_and(|key| keys.contains(&key)))
+            .collect();
+        if !expected.is_empty() && expected.iter().eq(compared.iter().copied()) {
+            summary.passed += 1;
+            continue;
+        }
+        if bless {
+            write_expectations(&path, &program, &actual)?;
+            writeln!(out, "blessed {}", path.display())?;
+            summary.blessed += 1;
+            continue;
+        }
+        writeln!(out, "FAIL {}", path.display())?;
+        if expected.is_empty() {
+            writeln!(out, "  no expectations")?;
+        }
+        for line in expected.iter().filter(|line| !actual.contains(line)) {
+            writeln!(out, "  - {line}")?;
+        }
+        // the actual lines for the expected keys, or all of them if there are none
+        let shown = match compared.is_empty() {
+            true => actual.iter().collect(),
+            false => compared,
+        };
+        for line in shown.into_iter().filter(|line| !expected.contains(line)) {
+            writeln!(out, "  + {line}")?;
+        }
+        summary.failed += 1;
+    }
+    writeln!(
+        out,
+        "{} passed, {} failed{}",
+        summary.passed,
+        summary.failed,
+        match summary.blessed {
+            0 => String::new(),
+            n => format!(", {n} blessed"),
+        }
+    )?;
+    Ok(summary)
+}
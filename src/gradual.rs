@@ -0,0 +1,633 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::*,
+    ast_util::Symbol,
+    span::{SourceMap, Span},
+};
+
+/// The consistency relation `~`: structural equality where `?` matches anything
+pub fn consistent(t1: &Type, t2: &Type) -> bool {
+    consistent_with(t1, t2, &|_| true)
+}
+
+/// `consistent`, where `?` only matches the types `dyn_matches` accepts
+pub fn consistent_with(t1: &Type, t2: &Type, dyn_matches: &impl Fn(&Type) -> bool) -> bool {
+    let consistent = |t1, t2| consistent_with(t1, t2, dyn_matches);
+    match (t1, t2) {
+        (Type::Dyn, tau) | (tau, Type::Dyn) => dyn_matches(tau),
+        (Type::Fn { arg: a1, ret: r1 }, Type::Fn { arg: a2, ret: r2 })
+        | (Type::LinFn { arg: a1, ret: r1 }, Type::LinFn { arg: a2, ret: r2 })
+        | (
+            Type::Product {
+                left: a1,
+                right: r1,
+            },
+            Type::Product {
+                left: a2,
+                right: r2,
+            },
+        )
+        | (
+            Type::Sum {
+                left: a1,
+                right: r1,
+            },
+            Type::Sum {
+                left: a2,
+                right: r2,
+            },
+        ) => consistent(a1, a2) && consistent(r1, r2),
+        (Type::Rec { tau: t1, .. }, Type::Rec { tau: t2, .. })
+        | (Type::Forall { tau: t1, .. }, Type::Forall { tau: t2, .. })
+        | (Type::Exists { tau: t1, .. }, Type::Exists { tau: t2, .. }) => consistent(t1, t2),
+        _ => t1 == t2,
+    }
+}
+
+/// The most precise type consistent with both (consistent) types, used to join branches
+pub fn meet(t1: &Type, t2: &Type) -> Type {
+    macro_rules! binder {
+        ($ty:tt, $a:ident, $t1:ident, $t2:ident) => {
+            Type::$ty {
+                a: $a.clone(),
+                tau: Box::new(meet($t1, $t2)),
+            }
+        };
+    }
+    match (t1, t2) {
+        (Type::Dyn, t) | (t, Type::Dyn) => t.clone(),
+        (Type::Fn { arg: a1, ret: r1 }, Type::Fn { arg: a2, ret: r2 }) => Type::Fn {
+            arg: Box::new(meet(a1, a2)),
+            ret: Box::new(meet(r1, r2)),
+        },
+        (Type::LinFn { arg: a1, ret: r1 }, Type::LinFn { arg: a2, ret: r2 }) => Type::LinFn {
+            arg: Box::new(meet(a1, a2)),
+            ret: Box::new(meet(r1, r2)),
+        },
+        (
+            Type::Product {
+                left: l1,
+                right: r1,
+            },
+            Type::Product {
+                left: l2,
+                right: r2,
+            },
+        ) => Type::Product {
+            left: Box::new(meet(l1, l2)),
+            right: Box::new(meet(r1, r2)),
+        },
+        (
+            Type::Sum {
+                left: l1,
+                right: r1,
+            },
+            Type::Sum {
+                left: l2,
+                right: r2,
+            },
+        ) => Type::Sum {
+            left: Box::new(meet(l1, l2)),
+            right: Box::new(meet(r1, r2)),
+        },
+        (Type::Rec { a, tau: t1 }, Type::Rec { tau: t2, .. }) => binder!(Rec, a, t1, t2),
+        (Type::Forall { a, tau: t1 }, Type::Forall { tau: t2, .. }) => binder!(Forall, a, t1, t2),
+        (Type::Exists { a, tau: t1 }, Type::Exists { tau: t2, .. }) => binder!(Exists, a, t1, t2),
+        _ => t1.clone(),
+    }
+}
+
+/// The tag a value of type `tau` carries inside `?`.
+///
+/// Type constructors are tagged with their shape (`? -> ?`, `? * ?`, ...); quantified and
+/// recursive types have no such shape and are tagged with themselves.
+pub fn ground(tau: &Type) -> Type {
+    let dyn_ = || Box::new(Type::Dyn);
+    match tau {
+        Type::Fn { .. } => Type::Fn {
+            arg: dyn_(),
+            ret: dyn_(),
+        },
+        Type::LinFn { .. } => Type::LinFn {
+            arg: dyn_(),
+            ret: dyn_(),
+        },
+        Type::Product { .. } => Type::Product {
+            left: dyn_(),
+            right: dyn_(),
+        },
+        Type::Sum { .. } => Type::Sum {
+            left: dyn_(),
+            right: dyn_(),
+        },
+        _ => tau.clone(),
+    }
+}
+
+/// Insert a cast wherever a well-typed program uses a value at a type that is consistent with,
+/// but not equal to, the type it was checked at. Fully annotated programs come back unchanged.
+pub fn elaborate(ast: &Expr, spans: &SourceMap) -> Expr {
+    Elaborator { spans }.elab(ast, HashMap::new()).0
+}
+
+struct Elaborator<'a> {
+    spans: &'a SourceMap,
+}
+
+impl Elaborator<'_> {
+    fn cast(&self, e: Expr, from: &Type, to: &Type, at: Option<Span>, what: &str) -> Expr {
+        if Type::alpha_equiv(from.clone(), to.clone()) {
+            return e;
+        }
+        let label = match at {
+            Some(span) => {
+                let (line, col) = self.spans.line_col(span.start);
+                format!("{what} at {line}:{col}")
+            }
+            None => what.to_string(),
+        };
+        let blame = Blame {
+            label,
+            span: at,
+            polarity: Polarity::Positive,
+        };
+        Expr::Cast {
+            e: Box::new(e),
+            from: Box::new(from.clone()),
+            to: Box::new(to.clone()),
+            blame,
+            id: NodeId::default(),
+        }
+    }
+
+    /// Cast to a type the program spells out: `written` is the `i`th type annotating `node`
+    fn annotated(
+        &self,
+        e: Expr,
+        from: &Type,
+        to: &Type,
+        node: &Expr,
+        i: usize,
+        written: &Type,
+    ) -> Expr {
+        let at = self.spans.type_span(node, i);
+        self.cast(e, from, to, at, &format!("annotation `{written}`"))
+    }
+
+    /// Cast an operand of a primitive operator to the type the operator expects
+    fn operand(&self, ast: &Expr, ctx: &HashMap<Variable, Type>, tau: Type, op: &str) -> Expr {
+        let (e, tau_e) = self.elab(ast, ctx.clone());
+        self.cast(
+            e,
+            &tau_e,
+            &tau,
+            self.spans.expr_span(ast),
+            &format!("operand of `{op}`"),
+        )
+    }
+
+    fn elab(&self, ast: &Expr, ctx: HashMap<Variable, Type>) -> (Expr, Type) {
+        let dyn_ = || Box::new(Type::Dyn);
+        match ast {
+            Expr::Num(..) => (ast.clone(), Type::Num),
+            Expr::True(_) | Expr::False(_) => (ast.clone(), Type::Bool),
+            Expr::Unit(_) => (ast.clone(), Type::Unit),
+            Expr::Var(x, _) => (ast.clone(), ctx[x].clone()),
+            Expr::Addop {
+                binop, left, right, ..
+            } => (
+                Expr::Addop {
+                    binop: binop.clone(),
+                    left: Box::new(self.operand(left, &ctx, Type::Num, &binop.to_string())),
+                    right: Box::new(self.operand(right, &ctx, Type::Num, &binop.to_string())),
+                    id: ast.id(),
+                },
+                Type::Num,
+            ),
+            Expr::Mulop {
+                binop, left, right, ..
+            } => (
+                Expr::Mulop {
+                    binop: binop.clone(),
+                    left: Box::new(self.operand(left, &ctx, Type::Num, &binop.to_string())),
+                    right: Box::new(self.operand(right, &ctx, Type::Num, &binop.to_string())),
+                    id: ast.id(),
+                },
+                Type::Num,
+            ),
+            Expr::Relop {
+                relop, left, right, ..
+            } => (
+                Expr::Relop {
+                    relop: relop.clone(),
+                    left: Box::new(self.operand(left, &ctx, Type::Num, &relop.to_string())),
+                    right: Box::new(self.operand(right, &ctx, Type::Num, &relop.to_string())),
+                    id: ast.id(),
+                },
+                Type::Bool,
+            ),
+            Expr::And { left, right, .. } => (
+                Expr::And {
+                    left: Box::new(self.operand(left, &ctx, Type::Bool, "&&")),
+                    right: Box::new(self.operand(right, &ctx, Type::Bool, "&&")),
+                    id: ast.id(),
+                },
+                Type::Bool,
+            ),
+            Expr::Or { left, right, .. } => (
+                Expr::Or {
+                    left: Box::new(self.operand(left, &ctx, Type::Bool, "||")),
+                    right: Box::new(self.operand(right, &ctx, Type::Bool, "||")),
+                    id: ast.id(),
+                },
+                Type::Bool,
+            ),
+            Expr::If {
+                cond, then_, else_, ..
+            } => {
+                let cond = self.operand(cond, &ctx, Type::Bool, "if");
+                let (e_then, tau_then) = self.elab(then_, ctx.clone());
+                let (e_else, tau_else) = self.elab(else_, ctx);
+                let tau = meet(&tau_then, &tau_else);
+                (
+                    Expr::If {
+                        cond: Box::new(cond),
+                        then_: Box::new(self.cast(
+                            e_then,
+                            &tau_then,
+                            &tau,
+                            self.spans.expr_span(then_),
+                            "then branch",
+                        )),
+                        else_: Box::new(self.cast(
+                            e_else,
+                            &tau_else,
+                            &tau,
+                            self.spans.expr_span(else_),
+                            "else branch",
+                        )),
+                        id: ast.id(),
+                    },
+                    tau,
+                )
+            }
+            Expr::Lam { x, tau, e, .. } => {
+                let mut ctx = ctx;
+                ctx.insert(x.clone(), *tau.clone());
+                let (e, tau_e) = self.elab(e, ctx);
+                (
+                    Expr::Lam {
+                        x: x.clone(),
+                        tau: tau.clone(),
+                        e: Box::new(e),
+                        id: ast.id(),
+                    },
+                    Type::Fn {
+                        arg: tau.clone(),
+                        ret: Box::new(tau_e),
+                    },
+                )
+            }
+            Expr::LinLam { x, tau, e, .. } => {
+                let mut ctx = ctx;
+                ctx.insert(x.clone(), *tau.clone());
+                let (e, tau_e) = self.elab(e, ctx);
+                (
+                    Expr::LinLam {
+                        x: x.clone(),
+                        tau: tau.clone(),
+                        e: Box::new(e),
+                        id: ast.id(),
+                    },
+                    Type::LinFn {
+                        arg: tau.clone(),
+                        ret: Box::new(tau_e),
+                    },
+                )
+            }
+            Expr::App { lam, arg, .. } => {
+                let (e_lam, tau_lam) = self.elab(lam, ctx.clone());
+                let (e_arg, tau_arg) = self.elab(arg, ctx);
+                let at = self.spans.expr_span(arg);
+                match tau_lam {
+                    Type::Fn { arg: tau_x, ret } | Type::LinFn { arg: tau_x, ret } => (
+                        Expr::App {
+                            lam: Box::new(e_lam),
+                            arg: Box::new(match lam.as_ref() {
+                                // a parameter of a function written in place is annotated
+                                Expr::Lam { tau, .. } | Expr::LinLam { tau, .. } => {
+                                    self.annotated(e_arg, &tau_arg, &tau_x, lam, 0, tau)
+                                }
+                                _ => self.cast(e_arg, &tau_arg, &tau_x, at, "argument"),
+                            }),
+                            id: ast.id(),
+                        },
+                        *ret,
+                    ),
+                    Type::Dyn => (
+                        Expr::App {
+                            lam: Box::new(self.cast(
+                                e_lam,
+                                &Type::Dyn,
+                                &Type::Fn {
+                                    arg: dyn_(),
+                                    ret: dyn_(),
+                                },
+                                self.spans.expr_span(lam),
+                                "applied function",
+                            )),
+                            arg: Box::new(self.cast(e_arg, &tau_arg, &Type::Dyn, at, "argument")),
+                            id: ast.id(),
+                        },
+                        Type::Dyn,
+                    ),
+                    _ => unreachable!("Ill-typed application should be found in type checking"),
+                }
+            }
+            Expr::Pair { left, right, .. } => {
+                let (e_left, tau_left) = self.elab(left, ctx.clone());
+                let (e_right, tau_right) = self.elab(right, ctx);
+                (
+                    Expr::Pair {
+                        left: Box::new(e_left),
+                        right: Box::new(e_right),
+                        id: ast.id(),
+                    },
+                    Type::Product {
+                        left: Box::new(tau_left),
+                        right: Box::new(tau_right),
+                    },
+                )
+            }
+            Expr::Project { e, d, .. } => {
+                let (e_pair, tau_e) = match self.elab(e, ctx) {
+                    (e_pair, Type::Dyn) => {
+                        let tau_e = Type::Product {
+                            left: dyn_(),
+                            right: dyn_(),
+                        };
+                        let at = self.spans.expr_span(e);
+                        (
+                            self.cast(e_pair, &Type::Dyn, &tau_e, at, "projection"),
+                            tau_e,
+                        )
+                    }
+                    elaborated => elaborated,
+                };
+                match (tau_e, d) {
+                    (Type::Product { left, .. }, Direction::Left) => (
+                        Expr::Project {
+                            e: Box::new(e_pair),
+                            d: d.clone(),
+                            id: ast.id(),
+                        },
+                        *left,
+                    ),
+                    (Type::Product { right, .. }, Direction::Right) => (
+                        Expr::Project {
+                            e: Box::new(e_pair),
+                            d: d.clone(),
+                            id: ast.id(),
+                        },
+                        *right,
+                    ),
+                    _ => unreachable!("Ill-typed projection should be found in type checking"),
+                }
+            }
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                ..
+            } => {
+                let (e_pair, tau_e) = match self.elab(e_pair, ctx.clone()) {
+                    (e_pair_elab, Type::Dyn) => {
+                        let tau_e = Type::Product {
+                            left: dyn_(),
+                            right: dyn_(),
+                        };
+                        let at = self.spans.expr_span(e_pair);
+                        (
+                            self.cast(e_pair_elab, &Type::Dyn, &tau_e, at, "pair pattern"),
+                            tau_e,
+                        )
+                    }
+                    elaborated => elaborated,
+                };
+                match tau_e {
+                    Type::Product { left, right } => {
+                        let mut ctx = ctx;
+                        ctx.insert(x.clone(), *left);
+                        ctx.insert(y.clone(), *right);
+                        let (e_body, tau_body) = self.elab(e_body, ctx);
+                        (
+                            Expr::LetPair {
+                                x: x.clone(),
+                                y: y.clone(),
+                                e_pair: Box::new(e_pair),
+                                e_body: Box::new(e_body),
+                                id: ast.id(),
+                            },
+                            tau_body,
+                        )
+                    }
+                    _ => unreachable!("Ill-typed pair pattern should be found in type checking"),
+                }
+            }
+            Expr::Inject { e, d, tau, .. } => {
+                let (e_inj, tau_e) = self.elab(e, ctx);
+                let tau_side = match (tau.as_ref(), d) {
+                    (Type::Sum { left, .. }, Direction::Left) => left,
+                    (Type::Sum { right, .. }, Direction::Right) => right,
+                    _ => unreachable!("Ill-typed injection should be found in type checking"),
+                };
+                (
+                    Expr::Inject {
+                        e: Box::new(self.annotated(e_inj, &tau_e, tau_side, ast, 0, tau)),
+                        d: d.clone(),
+                        tau: tau.clone(),
+                        id: ast.id(),
+                    },
+                    *tau.clone(),
+                )
+            }
+            Expr::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+                ..
+            } => {
+                let (e_sum, tau_e) = self.elab(e, ctx.clone());
+                let (e_sum, tau_xleft, tau_xright) = match tau_e {
+                    Type::Sum { left, right } => (e_sum, *left, *right),
+                    Type::Dyn => (
+                        self.cast(
+                            e_sum,
+                            &Type::Dyn,
+                            &Type::Sum {
+                                left: dyn_(),
+                                right: dyn_(),
+                            },
+                            self.spans.expr_span(e),
+                            "case scrutinee",
+                        ),
+                        Type::Dyn,
+                        Type::Dyn,
+                    ),
+                    _ => unreachable!("Ill-typed case should be found in type checking"),
+                };
+                let mut ctx_left = ctx.clone();
+                ctx_left.insert(xleft.clone(), tau_xleft);
+                let (e_left, tau_left) = self.elab(eleft, ctx_left);
+                let mut ctx_right = ctx;
+                ctx_right.insert(xright.clone(), tau_xright);
+                let (e_right, tau_right) = self.elab(eright, ctx_right);
+                let tau = meet(&tau_left, &tau_right);
+                (
+                    Expr::Case {
+                        e: Box::new(e_sum),
+                        xleft: xleft.clone(),
+                        eleft: Box::new(self.cast(
+                            e_left,
+                            &tau_left,
+                            &tau,
+                            self.spans.expr_span(eleft),
+                            "case branch",
+                        )),
+                        xright: xright.clone(),
+                        eright: Box::new(self.cast(
+                            e_right,
+                            &tau_right,
+                            &tau,
+                            self.spans.expr_span(eright),
+                            "case branch",
+                        )),
+                        id: ast.id(),
+                    },
+                    tau,
+                )
+            }
+            Expr::Fix { x, tau, e, .. } => {
+                let mut ctx = ctx;
+                ctx.insert(x.clone(), *tau.clone());
+                let (e_fix, tau_e) = self.elab(e, ctx);
+                (
+                    Expr::Fix {
+                        x: x.clone(),
+                        tau: tau.clone(),
+                        e: Box::new(self.annotated(e_fix, &tau_e, tau, ast, 0, tau)),
+                        id: ast.id(),
+                    },
+                    *tau.clone(),
+                )
+            }
+            Expr::TyLam { a, e, .. } => {
+                let (e, tau_e) = self.elab(e, ctx);
+                (
+                    Expr::TyLam {
+                        a: a.clone(),
+                        e: Box::new(e),
+                        id: ast.id(),
+                    },
+                    Type::forall(a.clone(), tau_e),
+                )
+            }
+            Expr::TyApp { e, tau, .. } => match self.elab(e, ctx) {
+                (e, Type::Forall { tau: tau_body, .. }) => (
+                    Expr::TyApp {
+                        e: Box::new(e),
+                        tau: tau.clone(),
+                        id: ast.id(),
+                    },
+                    tau_body.open(tau),
+                ),
+                _ => unreachable!("Ill-typed type application should be found in type checking"),
+            },
+            Expr::Fold { e, tau, .. } => {
+                let (e_fold, tau_e) = self.elab(e, ctx);
+                let tau_unrolled = match tau.as_ref() {
+                    Type::Rec { tau: tau_body, .. } => tau_body.open(tau),
+                    _ => unreachable!("Ill-typed fold should be found in type checking"),
+                };
+                (
+                    Expr::Fold {
+                        e: Box::new(self.annotated(e_fold, &tau_e, &tau_unrolled, ast, 0, tau)),
+                        tau: tau.clone(),
+                        id: ast.id(),
+                    },
+                    *tau.clone(),
+                )
+            }
+            Expr::Unfold(e, _) => match self.elab(e, ctx) {
+                (e, tau_e @ Type::Rec { .. }) => {
+                    let Type::Rec { tau: tau_body, .. } = &tau_e else {
+                        unreachable!()
+                    };
+                    (Expr::Unfold(Box::new(e), ast.id()), tau_body.open(&tau_e))
+                }
+                _ => unreachable!("Ill-typed unfold should be found in type checking"),
+            },
+            Expr::Export {
+                e,
+                tau_adt,
+                tau_mod,
+                ..
+            } => {
+                let (e_mod, tau_e) = self.elab(e, ctx);
+                let tau_impl = match tau_mod.as_ref() {
+                    Type::Exists { tau, .. } => tau.open(tau_adt),
+                    _ => unreachable!("Ill-typed export should be found in type checking"),
+                };
+                (
+                    Expr::Export {
+                        e: Box::new(self.annotated(e_mod, &tau_e, &tau_impl, ast, 1, tau_mod)),
+                        tau_adt: tau_adt.clone(),
+                        tau_mod: tau_mod.clone(),
+                        id: ast.id(),
+                    },
+                    *tau_mod.clone(),
+                )
+            }
+            Expr::Import {
+                x,
+                a: b,
+                e_mod,
+                e_body,
+                ..
+            } => match self.elab(e_mod, ctx.clone()) {
+                (e_mod, Type::Exists { tau: tau_mod, .. }) => {
+                    let mut ctx = ctx;
+                    ctx.insert(x.clone(), tau_mod.open(&Type::Var(b.clone())));
+                    let (e_body, tau_body) = self.elab(e_body, ctx);
+                    (
+                        Expr::Import {
+                            x: x.clone(),
+                            a: b.clone(),
+                            e_mod: Box::new(e_mod),
+                            e_body: Box::new(e_body),
+                            id: ast.id(),
+                        },
+                        tau_body,
+                    )
+                }
+                _ => unreachable!("Ill-typed import should be found in type checking"),
+            },
+            Expr::Cast {
+                e, from, to, blame, ..
+            } => (
+                Expr::Cast {
+                    e: Box::new(self.elab(e, ctx).0),
+                    from: from.clone(),
+                    to: to.clone(),
+                    blame: blame.clone(),
+                    id: ast.id(),
+                },
+                *to.clone(),
+            ),
+        }
+    }
+}
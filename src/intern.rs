@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use crate::ast::Variable;
+
+/// An interned variable name: cheap to copy, compare and use as an index
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Sym(u32);
+
+impl Sym {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Symbol table mapping each distinct name to a `Sym`
+#[derive(Default)]
+pub struct Interner {
+    ids: HashMap<String, Sym>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, x: &Variable) -> Sym {
+        if let Some(sym) = self.ids.get(&x.0) {
+            return *sym;
+        }
+        let sym = Sym(self.names.len() as u32);
+        self.ids.insert(x.0.clone(), sym);
+        self.names.push(x.0.clone());
+        sym
+    }
+
+    /// The symbol of a name that has been interned before
+    pub fn get(&self, x: &Variable) -> Option<Sym> {
+        self.ids.get(&x.0).copied()
+    }
+
+    pub fn name(&self, sym: Sym) -> &str {
+        &self.names[sym.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
@@ -1,8 +1,25 @@
+pub mod arena;
 pub mod ast;
 pub mod ast_util;
+pub mod dap;
+pub mod debugger;
+pub mod derivation;
+pub mod doc;
 pub mod dotgen;
 pub mod evaluate;
 pub mod flags;
+pub mod golden;
+pub mod gradual;
+pub mod intern;
+pub mod lsp;
 pub mod monad;
+pub mod nbe;
+pub mod normalize;
 pub mod parser;
+pub mod pretty;
+pub mod rules;
+pub mod serialize;
+pub mod span;
+pub mod stepdiff;
+pub mod svggen;
 pub mod typecheck;
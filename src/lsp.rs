@@ -0,0 +1,505 @@
+//! A Language Server Protocol server on stdin/stdout for editing programs.
+//!
+//! Documents are parsed and type checked on every change. The grammar records the span of
+//! every binder in the `SourceMap`, and `Document::bind` looks it up with
+//! `SourceMap::binder_span` to resolve each use of a variable to its binder.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    ast::*,
+    dap::{read_message, write_message},
+    flags::{format_type, Format, OutputMode},
+    parser::parse_located,
+    span::{SourceMap, Span},
+    typecheck::{type_check_recording, Typing},
+};
+
+const ERROR: i64 = 1;
+const VARIABLE: i64 = 13;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The LSP position of a byte offset: a 0-based line and a column in UTF-16 code units
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset.min(text.len())];
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[start..].encode_utf16().count(),
+    })
+}
+
+fn range(text: &str, span: Span) -> Value {
+    json!({"start": position(text, span.start), "end": position(text, span.end)})
+}
+
+/// The byte offset of an LSP position
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    let start = match line {
+        0 => 0,
+        n => match text.match_indices('\n').nth(n - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len(),
+        },
+    };
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+/// A variable bound in the source
+struct Binder {
+    name: Variable,
+    span: Span,
+    tau: Option<Type>,
+}
+
+/// An open document
+struct Document {
+    text: String,
+    /// The parsed program, unless it has a syntax error
+    program: Option<(Box<Expr>, SourceMap)>,
+    typing: Typing,
+    /// The parse or type error
+    error: Option<(String, Span)>,
+    binders: Vec<Binder>,
+    /// Spans of variables and the binders they refer to
+    uses: Vec<(Span, usize)>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let mut document = Document {
+            text,
+            program: None,
+            typing: Typing::default(),
+            error: None,
+            binders: Vec::new(),
+            uses: Vec::new(),
+        };
+        match parse_located(&document.text) {
+            Ok((e, spans)) => {
+                let (result, typing) = type_check_recording(&e);
+                document.typing = typing;
+                if let Err(err) = result {
+                    let span = document.failed(&e, &spans).unwrap_or_default();
+                    document.error = Some((format!("Type error: {}", err.trim()), span));
+                }
+                document.resolve(&e, &spans, &mut Vec::new());
+                document.program = Some((e, spans));
+            }
+            Err((err, span)) => document.error = Some((format!("Parse error: {err}"), span)),
+        }
+        document
+    }
+
+    /// The span of the expression the type error comes from
+    fn failed(&self, e: &Expr, spans: &SourceMap) -> Option<Span> {
+        if self.typing.failed_at(e) {
+            return spans.expr_span(e);
+        }
+        e.children()
+            .into_iter()
+            .find_map(|child| self.failed(child, spans))
+    }
+
+    /// Add the `i`th binder of `e`, or the one already there for `letrec`, which binds its
+    /// variable twice
+    fn bind(
+        &mut self,
+        e: &Expr,
+        i: usize,
+        spans: &SourceMap,
+        x: &Variable,
+        tau: Option<Type>,
+    ) -> usize {
+        let span = spans.binder_span(e, i).unwrap_or_default();
+        match self.binders.iter().position(|b| b.span == span) {
+            Some(i) => i,
+            None => {
+                self.binders.push(Binder {
+                    name: x.clone(),
+                    span,
+                    tau,
+                });
+                self.binders.len() - 1
+            }
+        }
+    }
+
+    /// Resolve variables to their binders, given the binders in scope
+    fn resolve(&mut self, e: &Expr, spans: &SourceMap, scope: &mut Vec<usize>) {
+        match e {
+            Expr::Var(x, _) => {
+                let binder = scope.iter().rev().find(|&&i| self.binders[i].name == *x);
+                if let (Some(span), Some(&i)) = (spans.expr_span(e), binder) {
+                    self.uses.push((span, i))
+                }
+            }
+            Expr::Lam {
+                x, tau, e: body, ..
+            }
+            | Expr::LinLam {
+                x, tau, e: body, ..
+            }
+            | Expr::Fix {
+                x, tau, e: body, ..
+            } => {
+                let binder = self.bind(e, 0, spans, x, Some(*tau.clone()));
+                self.resolve_under(binder, body, spans, scope)
+            }
+            Expr::Case {
+                e: scrutinee,
+                xleft,
+                eleft,
+                xright,
+                eright,
+                ..
+            } => {
+                self.resolve(scrutinee, spans, scope);
+                let (left, right) = match self.typing.type_of(scrutinee) {
+                    Some(Type::Sum { left, right }) => (Some(*left.clone()), Some(*right.clone())),
+                    Some(Type::Dyn) => (Some(Type::Dyn), Some(Type::Dyn)),
+                    _ => (None, None),
+                };
+                let binder = self.bind(e, 0, spans, xleft, left);
+                self.resolve_under(binder, eleft, spans, scope);
+                let binder = self.bind(e, 1, spans, xright, right);
+                self.resolve_under(binder, eright, spans, scope)
+            }
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                ..
+            } => {
+                self.resolve(e_pair, spans, scope);
+                let (left, right) = match self.typing.type_of(e_pair) {
+                    Some(Type::Product { left, right }) => {
+                        (Some(*left.clone()), Some(*right.clone()))
+                    }
+                    Some(Type::Dyn) => (Some(Type::Dyn), Some(Type::Dyn)),
+                    _ => (None, None),
+                };
+                let x = self.bind(e, 0, spans, x, left);
+                let y = self.bind(e, 1, spans, y, right);
+                scope.push(x);
+                self.resolve_under(y, e_body, spans, scope);
+                scope.pop();
+            }
+            Expr::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+                ..
+            } => {
+                self.resolve(e_mod, spans, scope);
+                let tau = match self.typing.type_of(e_mod) {
+                    Some(Type::Exists { tau, .. }) => Some(tau.open(&Type::Var(a.clone()))),
+                    _ => None,
+                };
+                let binder = self.bind(e, 0, spans, x, tau);
+                self.resolve_under(binder, e_body, spans, scope)
+            }
+            _ => {
+                for child in e.children() {
+                    self.resolve(child, spans, scope)
+                }
+            }
+        }
+    }
+
+    fn resolve_under(
+        &mut self,
+        binder: usize,
+        e: &Expr,
+        spans: &SourceMap,
+        scope: &mut Vec<usize>,
+    ) {
+        scope.push(binder);
+        self.resolve(e, spans, scope);
+        scope.pop();
+    }
+
+    /// The binder at `offset`, or the variable there and its binder
+    fn binder_at(&self, offset: usize) -> Option<(usize, Span)> {
+        let binders = self.binders.iter().enumerate().map(|(i, b)| (i, b.span));
+        let uses = self.uses.iter().map(|&(span, i)| (i, span));
+        binders
+            .chain(uses)
+            .find(|&(_, span)| contains(span, offset))
+    }
+
+    /// The innermost expression at `offset` that has a type, and its type
+    fn typed_at(&self, offset: usize) -> Option<(Span, &Type)> {
+        let (e, spans) = self.program.as_ref()?;
+        self.innermost(e, spans, offset)
+    }
+
+    fn innermost(&self, e: &Expr, spans: &SourceMap, offset: usize) -> Option<(Span, &Type)> {
+        let span = spans.expr_span(e).filter(|&span| contains(span, offset))?;
+        // a `let` is a function applied to the definition, and both have the span of the `let`
+        e.children()
+            .into_iter()
+            .filter_map(|child| self.innermost(child, spans, offset))
+            .min_by_key(|(span, _)| span.end - span.start)
+            .or_else(|| Some((span, self.typing.type_of(e)?)))
+    }
+
+    /// The `let`s at the top of the program: their binders and spans up to `in`
+    fn top_level(&self) -> Vec<(usize, Span)> {
+        let mut lets = Vec::new();
+        let Some((mut e, spans)) = self.program.as_ref().map(|(e, spans)| (e.as_ref(), spans))
+        else {
+            return lets;
+        };
+        while let Expr::App { lam, arg, .. } = e {
+            let Expr::Lam { x, e: body, .. } = lam.as_ref() else {
+                break;
+            };
+            let (Some(span), true) = (
+                spans.expr_span(e),
+                spans.expr_span(lam) == spans.expr_span(e),
+            ) else {
+                break;
+            };
+            // the definition of a `letrec` is the body of its fixpoint
+            let definition = match arg.as_ref() {
+                Expr::Fix { e, .. } if spans.expr_span(arg) == Some(span) => e,
+                _ => arg,
+            };
+            let end = spans
+                .expr_span(definition)
+                .map_or(span.end, |span| span.end);
+            if let Some(i) = self
+                .binders
+                .iter()
+                .position(|b| b.name == *x && contains(span, b.span.start))
+            {
+                lets.push((
+                    i,
+                    Span {
+                        start: span.start,
+                        end,
+                    },
+                ));
+            }
+            e = body;
+        }
+        lets
+    }
+}
+
+struct Server<W: Write> {
+    output: W,
+    format: Format,
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["jsonrpc"] = json!("2.0");
+        write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, (i64, String)>) -> io::Result<()> {
+        let mut response = json!({ "id": request["id"] });
+        match result {
+            Ok(result) => response["result"] = result,
+            Err((code, message)) => response["error"] = json!({"code": code, "message": message}),
+        }
+        self.send(response)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(json!({"method": method, "params": params}))
+    }
+
+    fn format(&self, tau: &Type) -> String {
+        format_type(tau, self.format, None)
+    }
+
+    /// Analyze a new version of a document and publish its diagnostics
+    fn update(&mut self, uri: &str, text: String) -> io::Result<()> {
+        let document = Document::new(text);
+        let diagnostics: Vec<Value> = document
+            .error
+            .iter()
+            .map(|(message, span)| {
+                json!({
+                    "range": range(&document.text, *span),
+                    "severity": ERROR,
+                    "source": "interpreter",
+                    "message": message,
+                })
+            })
+            .collect();
+        self.documents.insert(uri.to_string(), document);
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        )
+    }
+
+    fn hover(&self, document: &Document, offset: usize) -> Value {
+        let (span, contents) = match document.binder_at(offset) {
+            Some((i, span)) => {
+                let binder = &document.binders[i];
+                match &binder.tau {
+                    Some(tau) => (span, format!("{} : {}", binder.name.0, self.format(tau))),
+                    None => return Value::Null,
+                }
+            }
+            None => match document.typed_at(offset) {
+                Some((span, tau)) => (span, self.format(tau)),
+                None => return Value::Null,
+            },
+        };
+        json!({
+            "contents": {"kind": "plaintext", "value": contents},
+            "range": range(&document.text, span),
+        })
+    }
+
+    /// Handle a message. Returns `false` once the client asks the server to exit.
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let params = &message["params"];
+        let method = message["method"].as_str().unwrap_or_default();
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let location = |document: &Document, span: Span| json!({"uri": uri, "range": range(&document.text, span)});
+        match method {
+            "initialize" => {
+                let capabilities = json!({
+                    "capabilities": {
+                        // full text on every change
+                        "textDocumentSync": 1,
+                        "hoverProvider": true,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "documentSymbolProvider": true,
+                    },
+                    "serverInfo": {"name": "interpreter"},
+                });
+                self.respond(message, Ok(capabilities))?
+            }
+            "shutdown" => self.respond(message, Ok(Value::Null))?,
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.update(uri, text.to_string())?
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.update(uri, text.to_string())?
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.notify(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                )?
+            }
+            "textDocument/hover"
+            | "textDocument/definition"
+            | "textDocument/references"
+            | "textDocument/documentSymbol" => {
+                let result = match self.documents.get(uri) {
+                    None => Value::Null,
+                    Some(document) => {
+                        let offset = offset(&document.text, &params["position"]);
+                        match method {
+                            "textDocument/hover" => self.hover(document, offset),
+                            "textDocument/definition" => match document.binder_at(offset) {
+                                Some((i, _)) => location(document, document.binders[i].span),
+                                None => Value::Null,
+                            },
+                            "textDocument/references" => match document.binder_at(offset) {
+                                Some((i, _)) => {
+                                    let declaration = params["context"]["includeDeclaration"]
+                                        .as_bool()
+                                        .unwrap_or(false)
+                                        .then_some(document.binders[i].span);
+                                    let uses = document.uses.iter().filter(|&&(_, j)| i == j);
+                                    let references: Vec<Value> = declaration
+                                        .into_iter()
+                                        .chain(uses.map(|&(span, _)| span))
+                                        .map(|span| location(document, span))
+                                        .collect();
+                                    json!(references)
+                                }
+                                None => Value::Null,
+                            },
+                            _ => {
+                                let symbols: Vec<Value> = document
+                                    .top_level()
+                                    .into_iter()
+                                    .map(|(i, span)| {
+                                        let binder = &document.binders[i];
+                                        json!({
+                                            "name": binder.name.0,
+                                            "detail": binder.tau.as_ref().map(|tau| self.format(tau)),
+                                            "kind": VARIABLE,
+                                            "range": range(&document.text, span),
+                                            "selectionRange": range(&document.text, binder.span),
+                                        })
+                                    })
+                                    .collect();
+                                json!(symbols)
+                            }
+                        }
+                    }
+                };
+                self.respond(message, Ok(result))?
+            }
+            // notifications have no id and get no response
+            _ if message.get("id").is_none() => (),
+            _ => self.respond(
+                message,
+                Err((METHOD_NOT_FOUND, format!("Unsupported method `{method}`"))),
+            )?,
+        }
+        Ok(true)
+    }
+}
+
+/// Serve until the client exits or closes the input
+pub fn serve(mut input: impl BufRead, output: impl Write, format: Format) -> io::Result<()> {
+    let mut server = Server {
+        output,
+        // types are shown on one line
+        format: match format.output {
+            OutputMode::Graphviz => Format {
+                output: OutputMode::Simplified,
+                ..format
+            },
+            _ => format,
+        },
+        documents: HashMap::new(),
+    };
+    while let Some(message) = read_message(&mut input)? {
+        if !server.handle(&message)? {
+            break;
+        }
+    }
+    Ok(())
+}
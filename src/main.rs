@@ -1,29 +1,47 @@
-mod ast;
-mod ast_util;
-mod dotgen;
-mod evaluate;
-mod flags;
-mod monad;
-mod parser;
-mod typecheck;
+// `Ok(println!(..))` keeps printing steps inside `do_!` blocks
+#![allow(clippy::unit_arg)]
 
 use clap::Parser;
-use evaluate::eval;
-use flags::{format_ast, format_type, Mode, OutputMode};
-use monad::Monad;
-use parser::parse;
+use interpreter::{
+    arena::Arena,
+    ast::{Blame, Expr},
+    dap,
+    debugger::Debugger,
+    derivation::LatexStyle,
+    do_,
+    evaluate::{force, try_eval_arena, try_eval_frames, try_eval_traced},
+    flags::{
+        format_ast, format_derivation, format_shared, format_type, format_value, Format,
+        InputFormat, Mode, OutputMode, Strategy, Trace,
+    },
+    golden,
+    gradual::elaborate,
+    lsp,
+    monad::Monad,
+    nbe::{equiv, Equivalence},
+    normalize::{self, normalize},
+    parser::parse_with_spans,
+    pretty::{pretty_width, WIDTH},
+    serialize::{expr_from_json, expr_from_sexp},
+    span::SourceMap,
+    stepdiff::{html, write_frames, Frame},
+    typecheck::{type_check, type_check_derivation, type_check_substructural, Substructural},
+};
 use std::{
     fmt,
-    fs::read_to_string,
+    fs::{self, read_to_string},
     io::{self, Read},
+    path::Path,
     process::exit,
 };
-use typecheck::type_check;
 
 #[derive(Debug)]
 enum Error {
     Parse(String),
     TypeCheck(String),
+    Blame(Blame),
+    OutOfSteps(usize),
+    Normalize(String),
     Io(io::Error),
 }
 
@@ -32,6 +50,9 @@ impl fmt::Display for Error {
         match self {
             Self::Parse(s) => write!(f, "Parse error: {s}"),
             Self::TypeCheck(s) => write!(f, "Type error: {s}"),
+            Self::Blame(blame) => write!(f, "Cast failed: {blame}"),
+            Self::OutOfSteps(n) => write!(f, "No normal form within {n} steps"),
+            Self::Normalize(s) => write!(f, "Normalization failed: {s}"),
             Self::Io(err) => write!(f, "I/O error: {err}"),
         }
     }
@@ -53,12 +74,120 @@ struct Cli {
     #[arg(value_enum)]
     output: OutputMode,
 
-    /// Input file. Read input from stdin if not specified.
+    /// Input file. Read input from stdin if not specified, except in `debug` mode. The file or
+    /// directory of programs in `test` mode.
+    #[arg(required_if_eq_any([("mode", "debug"), ("mode", "test")]))]
     input_path: Option<String>,
+
+    /// The program to compare against in `equiv` mode
+    #[arg(required_if_eq("mode", "equiv"))]
+    other_path: Option<String>,
+
+    /// Track uses of linear variables (arguments of `-o` functions) while type checking
+    #[arg(long, value_enum)]
+    substructural: Option<Substructural>,
+
+    /// Evaluation strategy
+    #[arg(long, value_enum, default_value_t)]
+    strategy: Strategy,
+
+    /// How `very-verbose` prints each step
+    #[arg(long, value_enum, default_value_t)]
+    trace: Trace,
+
+    /// Where `--trace html` writes the page (default `steps.html`) and `--trace frames` the
+    /// pictures (default `steps`)
+    #[arg(long)]
+    trace_out: Option<String>,
+
+    /// Evaluate on a DAG in which equal subterms are shared, and report its size in verbose modes.
+    /// Uses call-by-name.
+    #[arg(long, conflicts_with = "strategy")]
+    share: bool,
+
+    /// Evaluate on an arena-allocated AST, which steps faster and reclaims the nodes the term no
+    /// longer reaches in batches. Uses call-by-name.
+    #[arg(long, conflicts_with_all = ["strategy", "share"])]
+    arena: bool,
+
+    /// Reduce to normal form with normal-order reduction, including under binders
+    #[arg(long, conflicts_with_all = ["strategy", "share", "arena"])]
+    normalize: bool,
+
+    /// Step budget of `--normalize`
+    #[arg(long, default_value_t = 10000)]
+    max_steps: usize,
+
+    /// Format of the input programs
+    #[arg(long, value_enum, default_value_t)]
+    input_format: InputFormat,
+
+    /// Proof tree package of `derivation` mode with `latex` output
+    #[arg(long, value_enum, default_value_t)]
+    latex_style: LatexStyle,
+
+    /// Line width of `fmt` and of `simplified` output
+    #[arg(long, default_value_t = WIDTH)]
+    width: usize,
+
+    /// Replace the expectations of failing programs in `test` mode with their actual results
+    #[arg(long)]
+    bless: bool,
+}
+
+/// Write the pictures of the steps of `--trace html` or `--trace frames`
+fn write_trace(frames: &[Frame], trace: Trace, out: Option<String>) -> io::Result<()> {
+    match trace {
+        Trace::Html => fs::write(out.as_deref().unwrap_or("steps.html"), html(frames)),
+        _ => write_frames(frames, Path::new(out.as_deref().unwrap_or("steps"))),
+    }
+}
+
+/// Parse a program, or load a serialized one. Loaded programs have no source locations.
+fn read_program(input: &str, format: InputFormat) -> Result<(Box<Expr>, SourceMap), String> {
+    match format {
+        InputFormat::Source => parse_with_spans(input),
+        InputFormat::Json => expr_from_json(input).map(|e| (e, SourceMap::default())),
+        InputFormat::Sexp => expr_from_sexp(input).map(|e| (e, SourceMap::default())),
+    }
+}
+
+/// The result to print. Simplified output shows the contents of lazy data evaluated.
+fn printable(result: Expr, output: OutputMode) -> Result<Expr, Error> {
+    match output {
+        OutputMode::Simplified => force(result).map_err(Error::Blame),
+        _ => Ok(result),
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
+    let format = Format {
+        output: cli.output,
+        width: cli.width,
+    };
+    if let Mode::Dap | Mode::Lsp = cli.mode {
+        let serve = match cli.mode {
+            Mode::Dap => dap::serve,
+            _ => lsp::serve,
+        };
+        if let Err(err) = serve(io::stdin().lock(), io::stdout(), format) {
+            eprintln!("{}", Error::Io(err));
+            exit(-1);
+        }
+        return;
+    }
+    if cli.mode == Mode::Test {
+        let path = cli.input_path.unwrap();
+        match golden::test(Path::new(&path), cli.bless, &mut io::stdout()) {
+            Ok(summary) if summary.failed == 0 => return,
+            Ok(_) => exit(1),
+            Err(err) => {
+                eprintln!("{}", Error::Io(err));
+                exit(-1);
+            }
+        }
+    }
     if let Err(err) = do_!(
         // read program
         match cli.input_path {
@@ -66,25 +195,103 @@ fn main() {
             Some(path) => read_to_string(path).map_err(Error::Io),
         } => input,
         // parse program
-        parse(&input).map_err(Error::Parse) => ast,
+        read_program(&input, cli.input_format).map_err(Error::Parse) => (ast, spans),
         match cli.mode {
-            Mode::Parse => Ok(println!("{}", format_ast(&ast, cli.output, None))),
-            _ => do_!(
-                // type check
+            Mode::Parse => Ok(println!("{}", format_ast(&ast, format, None))),
+            Mode::Fmt => Ok(println!("{}", pretty_width(&ast, cli.width))),
+            Mode::Derivation => do_!(
+                type_check_derivation(&ast, cli.substructural).map_err(Error::TypeCheck) => derivation,
+                Ok(println!("{}", format_derivation(&derivation, cli.output, cli.latex_style)))
+            ),
+            Mode::Debug => do_!(
+                match cli.substructural {
+                    None => type_check(&ast),
+                    Some(mode) => type_check_substructural(&ast, mode),
+                }.map_err(Error::TypeCheck) => _t,
+                Debugger::new(elaborate(&ast, &spans), format)
+                    .run(io::stdin().lock(), io::stdout())
+                    .map_err(Error::Io)
+            ),
+            Mode::Equiv => do_!(
+                read_to_string(cli.other_path.unwrap()).map_err(Error::Io) => other,
+                read_program(&other, cli.input_format).map_err(Error::Parse) => (other, _),
                 type_check(&ast).map_err(Error::TypeCheck) => t,
-                // print type
-                match cli.mode {
-                    Mode::Verbose | Mode::VeryVerbose => Ok(println!("{}", format_type(&t, cli.output))),
-                    _ => Ok(()),
+                type_check(&other).map_err(Error::TypeCheck) => t_other,
+                match t == t_other {
+                    true => Ok(()),
+                    false => Err(Error::TypeCheck(format!("The programs have types {t} and {t_other}"))),
                 },
+                equiv(&ast, &other, &t).map_err(Error::Normalize) => result,
+                Ok(match (result, cli.output) {
+                    (Equivalence::Equivalent(_), OutputMode::Graphviz) => println!("// Equivalent"),
+                    (Equivalence::Equivalent(_), _) => println!("Equivalent"),
+                    (Equivalence::Different(n1, n2), OutputMode::Graphviz) => println!(
+                        "// Not equivalent\ndigraph Program {{\n{}\n{}\n}}",
+                        format_ast(&n1, format, Some(String::from("first"))),
+                        format_ast(&n2, format, Some(String::from("second")))
+                    ),
+                    (Equivalence::Different(n1, n2), _) => println!(
+                        "Not equivalent:\n{}\n{}",
+                        format_ast(&n1, format, None),
+                        format_ast(&n2, format, None)
+                    ),
+                })
+            ),
+            _ => do_!(
+                // type check
+                match cli.substructural {
+                    None => type_check(&ast),
+                    Some(mode) => type_check_substructural(&ast, mode),
+                }.map_err(Error::TypeCheck) => t,
                 match cli.output {
                     OutputMode::Graphviz => Ok(println!("digraph Program {{")),
                     _ => Ok(()),
                 },
+                // print type
+                match cli.mode {
+                    Mode::Verbose | Mode::VeryVerbose => {
+                        Ok(println!("{}", format_type(&t, format, Some(String::from("type")))))
+                    }
+                    _ => Ok(()),
+                },
+                // insert runtime casts for `?` annotations
+                Ok(elaborate(&ast, &spans)) => ast,
                 // evaluate
-                Ok(eval(&ast, cli.mode, cli.output)) => result,
+                match (cli.normalize, cli.share || cli.arena) {
+                    (true, _) => normalize(&ast, cli.max_steps, cli.mode, format)
+                        .map_err(|err| match err {
+                            normalize::Error::Blame(blame) => Error::Blame(blame),
+                            normalize::Error::OutOfSteps(n) => Error::OutOfSteps(n),
+                        })
+                        .map(|result| format_value(&result, &t, format, Some(String::from("last")))),
+                    (false, false) => match (cli.mode, cli.trace) {
+                        (Mode::VeryVerbose, Trace::Html | Trace::Frames) => do_!(
+                            Ok(try_eval_frames(&ast, cli.strategy)) => (frames, result),
+                            write_trace(&frames, cli.trace, cli.trace_out).map_err(Error::Io),
+                            result.map_err(Error::Blame)
+                        ),
+                        _ => try_eval_traced(&ast, cli.mode, format, cli.strategy, cli.trace)
+                            .map_err(Error::Blame),
+                    }
+                    .and_then(|result| printable(result, cli.output))
+                    .map(|result| format_value(&result, &t, format, Some(String::from("last")))),
+                    (false, true) => try_eval_arena(
+                        &ast,
+                        if cli.share { Arena::hash_consed() } else { Arena::new() },
+                        cli.mode,
+                        format,
+                    )
+                        .map_err(Error::Blame)
+                        .and_then(|(arena, result)| match cli.output {
+                            OutputMode::Graphviz => {
+                                Ok(format_shared(&arena, result, format, Some(String::from("last"))))
+                            }
+                            _ => printable(arena.to_expr(result), cli.output)
+                                .map(|result| format_value(&result, &t, format, None)),
+                        }),
+                } => result,
                 // print result
-                Ok(println!("{}", format_ast(&result, cli.output, Some(String::from("last"))))),
+                Ok(println!("{result}")),
                 match cli.output {
                     OutputMode::Graphviz => Ok(println!("}}")),
                     _ => Ok(()),
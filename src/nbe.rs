@@ -0,0 +1,627 @@
+//! Normalization by evaluation: a program is evaluated into a semantic domain in which free
+//! variables are *neutral* values, then read back ("reified") at its type.
+//!
+//! Reification is directed by the type, which gives eta for functions, products and `forall`:
+//! a value of function type is always read back as `fun`, however it was written, and a pair
+//! type as a pair. A neutral value records the types it needs to be read back. An `if`,
+//! `case` or `import` stuck on a neutral value becomes a `Split`, and eliminating a split
+//! eliminates each of its branches instead (commuting conversions), so the only neutral
+//! values that are eliminated are the ones with a known type.
+//!
+//! `fix` is unrolled whenever it is used, which need not terminate under binders, so the
+//! number of unrollings is limited.
+
+use std::{cell::Cell, collections::HashMap, thread};
+
+use crate::{ast::*, ast_util::Symbol};
+
+/// Unrollings of `fix` before giving up
+const FUEL: usize = 1000;
+
+/// Evaluation recurses through every unrolling it is under
+const STACK_SIZE: usize = 256 << 20;
+
+#[derive(Clone, Default)]
+struct Env<'a> {
+    vars: HashMap<Variable, Value<'a>>,
+    tys: HashMap<Variable, Type>,
+}
+
+impl<'a> Env<'a> {
+    fn with_var(&self, x: &Variable, v: Value<'a>) -> Self {
+        let mut env = self.clone();
+        env.vars.insert(x.clone(), v);
+        env
+    }
+
+    fn with_ty(&self, a: &Variable, tau: Type) -> Self {
+        let mut env = self.clone();
+        env.tys.insert(a.clone(), tau);
+        env
+    }
+
+    /// An annotation with the type variables bound so far replaced
+    fn resolve(&self, tau: &Type) -> Type {
+        tau.clone().substitute_map(self.tys.clone())
+    }
+}
+
+#[derive(Clone)]
+enum Value<'a> {
+    Num(i32),
+    True,
+    False,
+    Unit,
+    /// A `fun` or a linear function
+    Lam(Env<'a>, &'a Variable, &'a Expr),
+    Pair(Box<Value<'a>>, Box<Value<'a>>),
+    Inject(Direction, Box<Value<'a>>),
+    TyLam(Env<'a>, &'a Variable, &'a Expr),
+    Fold(Box<Value<'a>>),
+    Export(Box<Value<'a>>, Type),
+    /// A `fix` expression, unrolled when it is used
+    Fix(Env<'a>, &'a Expr),
+    Neutral(Neutral<'a>, Type),
+    Split(Box<Split<'a>>),
+}
+
+/// A variable, or an elimination or arithmetic that is stuck on one
+#[derive(Clone)]
+enum Neutral<'a> {
+    Var(Variable),
+    /// With the type of the argument
+    App(Box<Neutral<'a>>, Box<Value<'a>>, Type),
+    Project(Box<Neutral<'a>>, Direction),
+    TyApp(Box<Neutral<'a>>, Type),
+    Unfold(Box<Neutral<'a>>),
+    Addop(AddOp, Box<Value<'a>>, Box<Value<'a>>),
+    Mulop(MulOp, Box<Value<'a>>, Box<Value<'a>>),
+    Relop(RelOp, Box<Value<'a>>, Box<Value<'a>>),
+    And(Box<Value<'a>>, Box<Value<'a>>),
+    Or(Box<Value<'a>>, Box<Value<'a>>),
+}
+
+/// A branch on a neutral value, with both branches evaluated
+#[derive(Clone)]
+enum Split<'a> {
+    If {
+        cond: Value<'a>,
+        then_: Value<'a>,
+        else_: Value<'a>,
+    },
+    Case {
+        e: Value<'a>,
+        sum: Type,
+        xleft: Variable,
+        eleft: Value<'a>,
+        xright: Variable,
+        eright: Value<'a>,
+    },
+    Import {
+        e_mod: Value<'a>,
+        exists: Type,
+        x: Variable,
+        a: Variable,
+        e_body: Value<'a>,
+    },
+}
+
+impl<'a> Split<'a> {
+    /// Apply an elimination to every branch
+    fn map(self, f: &impl Fn(Value<'a>) -> Result<Value<'a>, String>) -> Result<Value<'a>, String> {
+        let split = match self {
+            Split::If { cond, then_, else_ } => Split::If {
+                cond,
+                then_: f(then_)?,
+                else_: f(else_)?,
+            },
+            Split::Case {
+                e,
+                sum,
+                xleft,
+                eleft,
+                xright,
+                eright,
+            } => Split::Case {
+                e,
+                sum,
+                xleft,
+                eleft: f(eleft)?,
+                xright,
+                eright: f(eright)?,
+            },
+            Split::Import {
+                e_mod,
+                exists,
+                x,
+                a,
+                e_body,
+            } => Split::Import {
+                e_mod,
+                exists,
+                x,
+                a,
+                e_body: f(e_body)?,
+            },
+        };
+        Ok(Value::Split(Box::new(split)))
+    }
+}
+
+struct Nbe {
+    fuel: Cell<usize>,
+    names: Cell<usize>,
+}
+
+impl Nbe {
+    fn new() -> Self {
+        Nbe {
+            fuel: Cell::new(FUEL),
+            names: Cell::new(0),
+        }
+    }
+
+    /// A name for a variable bound in the normal form. Every name is used once, and the
+    /// programs are closed, so there is nothing to capture.
+    fn fresh(&self, x: &Variable) -> Variable {
+        let base = x.0.trim_end_matches(|c: char| c.is_ascii_digit());
+        let base = if base.is_empty() { "x" } else { base };
+        self.names.set(self.names.get() + 1);
+        Variable::from(format!("{base}{}", self.names.get()))
+    }
+
+    fn eval<'a>(&self, e: &'a Expr, env: &Env<'a>) -> Result<Value<'a>, String> {
+        let value = match e {
+            Expr::Num(n, _) => Value::Num(*n),
+            Expr::True(_) => Value::True,
+            Expr::False(_) => Value::False,
+            Expr::Unit(_) => Value::Unit,
+            Expr::Var(x, _) => env
+                .vars
+                .get(x)
+                .cloned()
+                .ok_or_else(|| format!("Free variable {}", x.0))?,
+            Expr::Addop {
+                binop, left, right, ..
+            } => match (self.eval_num(left, env)?, self.eval_num(right, env)?) {
+                (Value::Num(l), Value::Num(r)) => Value::Num(match binop {
+                    AddOp::Add => l + r,
+                    AddOp::Sub => l - r,
+                }),
+                (l, r) => Value::Neutral(
+                    Neutral::Addop(binop.clone(), Box::new(l), Box::new(r)),
+                    Type::Num,
+                ),
+            },
+            Expr::Mulop {
+                binop, left, right, ..
+            } => match (self.eval_num(left, env)?, self.eval_num(right, env)?, binop) {
+                (Value::Num(l), Value::Num(r), MulOp::Mul) => Value::Num(l * r),
+                (Value::Num(l), Value::Num(r), MulOp::Div) if r != 0 => Value::Num(l / r),
+                (l, r, _) => Value::Neutral(
+                    Neutral::Mulop(binop.clone(), Box::new(l), Box::new(r)),
+                    Type::Num,
+                ),
+            },
+            Expr::Relop {
+                relop, left, right, ..
+            } => match (self.eval_num(left, env)?, self.eval_num(right, env)?) {
+                (Value::Num(l), Value::Num(r)) => match relop {
+                    RelOp::Lt if l < r => Value::True,
+                    RelOp::Gt if l > r => Value::True,
+                    RelOp::Eq if l == r => Value::True,
+                    _ => Value::False,
+                },
+                (l, r) => Value::Neutral(
+                    Neutral::Relop(relop.clone(), Box::new(l), Box::new(r)),
+                    Type::Bool,
+                ),
+            },
+            Expr::And { left, right, .. } => {
+                match (self.eval_num(left, env)?, self.eval_num(right, env)?) {
+                    (Value::True, Value::True) => Value::True,
+                    (Value::True | Value::False, Value::True | Value::False) => Value::False,
+                    (l, r) => Value::Neutral(Neutral::And(Box::new(l), Box::new(r)), Type::Bool),
+                }
+            }
+            Expr::Or { left, right, .. } => {
+                match (self.eval_num(left, env)?, self.eval_num(right, env)?) {
+                    (Value::False, Value::False) => Value::False,
+                    (Value::True | Value::False, Value::True | Value::False) => Value::True,
+                    (l, r) => Value::Neutral(Neutral::Or(Box::new(l), Box::new(r)), Type::Bool),
+                }
+            }
+            Expr::If {
+                cond, then_, else_, ..
+            } => match self.force(self.eval(cond, env)?)? {
+                Value::True => self.eval(then_, env)?,
+                Value::False => self.eval(else_, env)?,
+                cond => Value::Split(Box::new(Split::If {
+                    cond,
+                    then_: self.eval(then_, env)?,
+                    else_: self.eval(else_, env)?,
+                })),
+            },
+            Expr::Lam { x, e, .. } | Expr::LinLam { x, e, .. } => Value::Lam(env.clone(), x, e),
+            Expr::App { lam, arg, .. } => {
+                let arg = self.eval(arg, env)?;
+                self.apply(self.eval(lam, env)?, arg)?
+            }
+            Expr::Pair { left, right, .. } => Value::Pair(
+                Box::new(self.eval(left, env)?),
+                Box::new(self.eval(right, env)?),
+            ),
+            Expr::Project { e, d, .. } => self.project(self.eval(e, env)?, d)?,
+            // by eta for products, a neutral pair is taken apart with projections
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                ..
+            } => {
+                let pair = self.eval(e_pair, env)?;
+                let left = self.project(pair.clone(), &Direction::Left)?;
+                let right = self.project(pair, &Direction::Right)?;
+                self.eval(e_body, &env.with_var(x, left).with_var(y, right))?
+            }
+            Expr::Inject { e, d, .. } => Value::Inject(d.clone(), Box::new(self.eval(e, env)?)),
+            Expr::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+                ..
+            } => self.case(self.eval(e, env)?, (xleft, eleft), (xright, eright), env)?,
+            Expr::Fix { .. } => Value::Fix(env.clone(), e),
+            Expr::TyLam { a, e, .. } => Value::TyLam(env.clone(), a, e),
+            Expr::TyApp { e, tau, .. } => self.ty_apply(self.eval(e, env)?, env.resolve(tau))?,
+            Expr::Fold { e, .. } => Value::Fold(Box::new(self.eval(e, env)?)),
+            Expr::Unfold(e, _) => self.unfold(self.eval(e, env)?)?,
+            Expr::Export { e, tau_adt, .. } => {
+                Value::Export(Box::new(self.eval(e, env)?), env.resolve(tau_adt))
+            }
+            Expr::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+                ..
+            } => self.import(self.eval(e_mod, env)?, x, a, e_body, env)?,
+            Expr::Cast { .. } => return Err(String::from("Programs with `?` are not supported")),
+        };
+        Ok(value)
+    }
+
+    /// Evaluate an operand of an arithmetic or boolean operator
+    fn eval_num<'a>(&self, e: &'a Expr, env: &Env<'a>) -> Result<Value<'a>, String> {
+        self.force(self.eval(e, env)?)
+    }
+
+    /// Unroll a `fix` that is about to be used
+    fn force<'a>(&self, v: Value<'a>) -> Result<Value<'a>, String> {
+        let mut v = v;
+        while let Value::Fix(env, fix @ Expr::Fix { x, e, .. }) = v {
+            if self.fuel.get() == 0 {
+                return Err(format!("Gave up after unrolling `fix` {FUEL} times"));
+            }
+            self.fuel.set(self.fuel.get() - 1);
+            v = self.eval(e, &env.with_var(x, Value::Fix(env.clone(), fix)))?;
+        }
+        Ok(v)
+    }
+
+    fn apply<'a>(&self, f: Value<'a>, arg: Value<'a>) -> Result<Value<'a>, String> {
+        match self.force(f)? {
+            Value::Lam(env, x, e) => self.eval(e, &env.with_var(x, arg)),
+            Value::Neutral(ne, Type::Fn { arg: tau, ret } | Type::LinFn { arg: tau, ret }) => Ok(
+                Value::Neutral(Neutral::App(Box::new(ne), Box::new(arg), *tau), *ret),
+            ),
+            Value::Split(split) => split.map(&|f| self.apply(f, arg.clone())),
+            _ => unreachable!(),
+        }
+    }
+
+    fn project<'a>(&self, v: Value<'a>, d: &Direction) -> Result<Value<'a>, String> {
+        match (self.force(v)?, d) {
+            (Value::Pair(left, _), Direction::Left) => Ok(*left),
+            (Value::Pair(_, right), Direction::Right) => Ok(*right),
+            (Value::Neutral(ne, Type::Product { left, right }), d) => {
+                let tau = match d {
+                    Direction::Left => left,
+                    Direction::Right => right,
+                };
+                Ok(Value::Neutral(
+                    Neutral::Project(Box::new(ne), d.clone()),
+                    *tau,
+                ))
+            }
+            (Value::Split(split), d) => split.map(&|v| self.project(v, d)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn ty_apply<'a>(&self, v: Value<'a>, tau: Type) -> Result<Value<'a>, String> {
+        match self.force(v)? {
+            Value::TyLam(env, a, e) => self.eval(e, &env.with_ty(a, tau)),
+            Value::Neutral(ne, Type::Forall { tau: body, .. }) => Ok(Value::Neutral(
+                Neutral::TyApp(Box::new(ne), tau.clone()),
+                body.open(&tau),
+            )),
+            Value::Split(split) => split.map(&|v| self.ty_apply(v, tau.clone())),
+            _ => unreachable!(),
+        }
+    }
+
+    fn unfold<'a>(&self, v: Value<'a>) -> Result<Value<'a>, String> {
+        match self.force(v)? {
+            Value::Fold(v) => Ok(*v),
+            Value::Neutral(ne, rec @ Type::Rec { .. }) => {
+                let Type::Rec { tau, .. } = &rec else {
+                    unreachable!()
+                };
+                let tau = tau.open(&rec);
+                Ok(Value::Neutral(Neutral::Unfold(Box::new(ne)), tau))
+            }
+            Value::Split(split) => split.map(&|v| self.unfold(v)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn case<'a>(
+        &self,
+        v: Value<'a>,
+        (xleft, eleft): (&'a Variable, &'a Expr),
+        (xright, eright): (&'a Variable, &'a Expr),
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, String> {
+        match self.force(v)? {
+            Value::Inject(Direction::Left, v) => self.eval(eleft, &env.with_var(xleft, *v)),
+            Value::Inject(Direction::Right, v) => self.eval(eright, &env.with_var(xright, *v)),
+            Value::Neutral(ne, sum @ Type::Sum { .. }) => {
+                let Type::Sum { left, right } = &sum else {
+                    unreachable!()
+                };
+                let (l, r) = (self.fresh(xleft), self.fresh(xright));
+                let left = Value::Neutral(Neutral::Var(l.clone()), *left.clone());
+                let right = Value::Neutral(Neutral::Var(r.clone()), *right.clone());
+                Ok(Value::Split(Box::new(Split::Case {
+                    eleft: self.eval(eleft, &env.with_var(xleft, left))?,
+                    eright: self.eval(eright, &env.with_var(xright, right))?,
+                    e: Value::Neutral(ne, sum.clone()),
+                    sum,
+                    xleft: l,
+                    xright: r,
+                })))
+            }
+            Value::Split(split) => {
+                split.map(&|v| self.case(v, (xleft, eleft), (xright, eright), env))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn import<'a>(
+        &self,
+        v: Value<'a>,
+        x: &'a Variable,
+        a: &'a Variable,
+        e_body: &'a Expr,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, String> {
+        match self.force(v)? {
+            Value::Export(v, adt) => self.eval(e_body, &env.with_var(x, *v).with_ty(a, adt)),
+            Value::Neutral(ne, exists @ Type::Exists { .. }) => {
+                let Type::Exists { tau, .. } = &exists else {
+                    unreachable!()
+                };
+                let (new_x, new_a) = (self.fresh(x), self.fresh(a));
+                let module = Value::Neutral(
+                    Neutral::Var(new_x.clone()),
+                    tau.open(&Type::Var(new_a.clone())),
+                );
+                let env = env.with_var(x, module).with_ty(a, Type::Var(new_a.clone()));
+                Ok(Value::Split(Box::new(Split::Import {
+                    e_body: self.eval(e_body, &env)?,
+                    e_mod: Value::Neutral(ne, exists.clone()),
+                    exists,
+                    x: new_x,
+                    a: new_a,
+                })))
+            }
+            Value::Split(split) => split.map(&|v| self.import(v, x, a, e_body, env)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Read a value back as an expression in normal form
+    fn reify(&self, v: Value, tau: &Type) -> Result<Expr, String> {
+        let expr = |v, tau| self.reify(v, tau).map(Box::new);
+        let expr = match tau {
+            Type::Fn { arg, ret } | Type::LinFn { arg, ret } => {
+                let x = self.fresh(&Variable::from("x"));
+                let var = Value::Neutral(Neutral::Var(x.clone()), *arg.clone());
+                let e = expr(self.apply(v, var)?, ret)?;
+                match tau {
+                    Type::Fn { .. } => Expr::Lam {
+                        x,
+                        tau: arg.clone(),
+                        e,
+                        id: NodeId::default(),
+                    },
+                    _ => Expr::LinLam {
+                        x,
+                        tau: arg.clone(),
+                        e,
+                        id: NodeId::default(),
+                    },
+                }
+            }
+            Type::Product { left, right } => Expr::Pair {
+                left: expr(self.project(v.clone(), &Direction::Left)?, left)?,
+                right: expr(self.project(v, &Direction::Right)?, right)?,
+                id: NodeId::default(),
+            },
+            Type::Forall { a, tau: body } => {
+                let a = self.fresh(a);
+                let e = self.ty_apply(v, Type::Var(a.clone()))?;
+                Expr::TyLam {
+                    e: expr(e, &body.open(&Type::Var(a.clone())))?,
+                    a,
+                    id: NodeId::default(),
+                }
+            }
+            _ => match (self.force(v)?, tau) {
+                (Value::Num(n), _) => Expr::Num(n, NodeId::default()),
+                (Value::True, _) => Expr::True(NodeId::default()),
+                (Value::False, _) => Expr::False(NodeId::default()),
+                (Value::Unit, _) => Expr::Unit(NodeId::default()),
+                (Value::Inject(d, v), Type::Sum { left, right }) => Expr::Inject {
+                    e: match d {
+                        Direction::Left => expr(*v, left)?,
+                        Direction::Right => expr(*v, right)?,
+                    },
+                    d,
+                    tau: Box::new(tau.clone()),
+                    id: NodeId::default(),
+                },
+                (Value::Fold(v), Type::Rec { tau: body, .. }) => Expr::Fold {
+                    e: expr(*v, &body.open(tau))?,
+                    tau: Box::new(tau.clone()),
+                    id: NodeId::default(),
+                },
+                (Value::Export(v, adt), Type::Exists { tau: body, .. }) => Expr::Export {
+                    e: expr(*v, &body.open(&adt))?,
+                    tau_adt: Box::new(adt),
+                    tau_mod: Box::new(tau.clone()),
+                    id: NodeId::default(),
+                },
+                (Value::Neutral(ne, _), _) => self.reify_neutral(ne)?,
+                (Value::Split(split), _) => match *split {
+                    Split::If { cond, then_, else_ } => Expr::If {
+                        cond: expr(cond, &Type::Bool)?,
+                        then_: expr(then_, tau)?,
+                        else_: expr(else_, tau)?,
+                        id: NodeId::default(),
+                    },
+                    Split::Case {
+                        e,
+                        sum,
+                        xleft,
+                        eleft,
+                        xright,
+                        eright,
+                    } => Expr::Case {
+                        e: expr(e, &sum)?,
+                        xleft,
+                        eleft: expr(eleft, tau)?,
+                        xright,
+                        eright: expr(eright, tau)?,
+                        id: NodeId::default(),
+                    },
+                    Split::Import {
+                        e_mod,
+                        exists,
+                        x,
+                        a,
+                        e_body,
+                    } => Expr::Import {
+                        x,
+                        a,
+                        e_mod: expr(e_mod, &exists)?,
+                        e_body: expr(e_body, tau)?,
+                        id: NodeId::default(),
+                    },
+                },
+                _ => unreachable!("A value doesn't match its type {tau}"),
+            },
+        };
+        Ok(expr)
+    }
+
+    fn reify_neutral(&self, ne: Neutral) -> Result<Expr, String> {
+        let neutral = |ne| self.reify_neutral(ne).map(Box::new);
+        let expr = |v, tau| self.reify(v, tau).map(Box::new);
+        let expr = match ne {
+            Neutral::Var(x) => Expr::Var(x, NodeId::default()),
+            Neutral::App(ne, arg, tau) => Expr::App {
+                lam: neutral(*ne)?,
+                arg: expr(*arg, &tau)?,
+                id: NodeId::default(),
+            },
+            Neutral::Project(ne, d) => Expr::Project {
+                e: neutral(*ne)?,
+                d,
+                id: NodeId::default(),
+            },
+            Neutral::TyApp(ne, tau) => Expr::TyApp {
+                e: neutral(*ne)?,
+                tau: Box::new(tau),
+                id: NodeId::default(),
+            },
+            Neutral::Unfold(ne) => Expr::Unfold(neutral(*ne)?, NodeId::default()),
+            Neutral::Addop(binop, left, right) => Expr::Addop {
+                binop,
+                left: expr(*left, &Type::Num)?,
+                right: expr(*right, &Type::Num)?,
+                id: NodeId::default(),
+            },
+            Neutral::Mulop(binop, left, right) => Expr::Mulop {
+                binop,
+                left: expr(*left, &Type::Num)?,
+                right: expr(*right, &Type::Num)?,
+                id: NodeId::default(),
+            },
+            Neutral::Relop(relop, left, right) => Expr::Relop {
+                relop,
+                left: expr(*left, &Type::Num)?,
+                right: expr(*right, &Type::Num)?,
+                id: NodeId::default(),
+            },
+            Neutral::And(left, right) => Expr::And {
+                left: expr(*left, &Type::Bool)?,
+                right: expr(*right, &Type::Bool)?,
+                id: NodeId::default(),
+            },
+            Neutral::Or(left, right) => Expr::Or {
+                left: expr(*left, &Type::Bool)?,
+                right: expr(*right, &Type::Bool)?,
+                id: NodeId::default(),
+            },
+        };
+        Ok(expr)
+    }
+}
+
+/// The beta-eta normal form of a closed, well-typed expression of type `tau`
+pub fn normal_form(e: &Expr, tau: &Type) -> Result<Expr, String> {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let nbe = Nbe::new();
+                let v = nbe.eval(e, &Env::default())?;
+                nbe.reify(v, tau)
+            })
+            .map_err(|err| err.to_string())?
+            .join()
+            .map_err(|_| String::from("Normalization panicked"))?
+    })
+}
+
+pub enum Equivalence {
+    /// With the common normal form
+    Equivalent(Expr),
+    /// With the two normal forms
+    Different(Expr, Expr),
+}
+
+/// Compare two closed expressions of type `tau` up to beta-eta equivalence
+pub fn equiv(e1: &Expr, e2: &Expr, tau: &Type) -> Result<Equivalence, String> {
+    let (n1, n2) = (normal_form(e1, tau)?, normal_form(e2, tau)?);
+    Ok(match Expr::alpha_equiv(n1.clone(), n2.clone()) {
+        true => Equivalence::Equivalent(n1),
+        false => Equivalence::Different(n1, n2),
+    })
+}
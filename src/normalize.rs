@@ -0,0 +1,266 @@
+//! Reduction to normal form with normal-order (leftmost-outermost) reduction.
+//!
+//! Unlike `evaluate`, this reduces inside functions, pairs, injections and the other values,
+//! and leaves an expression stuck on a variable (such as `x + 1` under `fun x`) in place.
+//! A `fix` is unrolled only when something eliminates it or an operator needs its value, since
+//! its body always contains another copy of it.
+
+use std::collections::HashMap;
+
+use crate::{
+    ast::*,
+    ast_util::Symbol,
+    evaluate::{cast_value, Outcome},
+    flags::{format_ast, Format, Mode},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A runtime cast failed
+    Blame(Blame),
+    /// No normal form was reached within the step budget
+    OutOfSteps(usize),
+}
+
+/// Values in the sense of `evaluate`, which casts can be applied to
+fn is_value(e: &Expr) -> bool {
+    match e {
+        Expr::Lam { .. }
+        | Expr::LinLam { .. }
+        | Expr::Num(..)
+        | Expr::True(_)
+        | Expr::False(_)
+        | Expr::Unit(_)
+        | Expr::Pair { .. }
+        | Expr::Inject { .. }
+        | Expr::TyLam { .. }
+        | Expr::Export { .. }
+        | Expr::Fold { .. } => true,
+        Expr::Cast {
+            e, from, to, blame, ..
+        } => is_value(e) && matches!(cast_value(e, from, to, blame), Outcome::Value),
+        _ => false,
+    }
+}
+
+/// The subexpressions an elimination form or operator inspects, left to right
+fn heads_mut(e: &mut Expr) -> Vec<&mut Expr> {
+    match e {
+        Expr::App { lam: head, .. }
+        | Expr::Project { e: head, .. }
+        | Expr::LetPair { e_pair: head, .. }
+        | Expr::Case { e: head, .. }
+        | Expr::TyApp { e: head, .. }
+        | Expr::Unfold(head, _)
+        | Expr::Import { e_mod: head, .. }
+        | Expr::If { cond: head, .. } => vec![head],
+        Expr::Addop { left, right, .. }
+        | Expr::Mulop { left, right, .. }
+        | Expr::Relop { left, right, .. }
+        | Expr::And { left, right, .. }
+        | Expr::Or { left, right, .. } => vec![left, right],
+        _ => vec![],
+    }
+}
+
+/// The result of contracting `e` if it is a redex
+fn contract(e: &Expr) -> Result<Option<Expr>, Blame> {
+    let result = match e {
+        Expr::Addop {
+            binop, left, right, ..
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Num(l, _), Expr::Num(r, _)) => Expr::Num(
+                match binop {
+                    AddOp::Add => l + r,
+                    AddOp::Sub => l - r,
+                },
+                NodeId::default(),
+            ),
+            _ => return Ok(None),
+        },
+        Expr::Mulop {
+            binop, left, right, ..
+        } => match (left.as_ref(), right.as_ref(), binop) {
+            (Expr::Num(l, _), Expr::Num(r, _), MulOp::Mul) => Expr::Num(l * r, NodeId::default()),
+            // dividing by zero is stuck: the division may be in a branch that's never taken
+            (Expr::Num(l, _), Expr::Num(r, _), MulOp::Div) if *r != 0 => {
+                Expr::Num(l / r, NodeId::default())
+            }
+            _ => return Ok(None),
+        },
+        Expr::Relop {
+            relop, left, right, ..
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Num(l, _), Expr::Num(r, _)) => match relop {
+                RelOp::Lt if l < r => Expr::True(NodeId::default()),
+                RelOp::Gt if l > r => Expr::True(NodeId::default()),
+                RelOp::Eq if l == r => Expr::True(NodeId::default()),
+                _ => Expr::False(NodeId::default()),
+            },
+            _ => return Ok(None),
+        },
+        Expr::If {
+            cond, then_, else_, ..
+        } => match cond.as_ref() {
+            Expr::True(_) => *then_.clone(),
+            Expr::False(_) => *else_.clone(),
+            _ => return Ok(None),
+        },
+        Expr::And { left, right, .. } => match (left.as_ref(), right.as_ref()) {
+            (Expr::True(_), Expr::True(_)) => Expr::True(NodeId::default()),
+            (Expr::False(_) | Expr::True(_), Expr::False(_) | Expr::True(_)) => {
+                Expr::False(NodeId::default())
+            }
+            _ => return Ok(None),
+        },
+        Expr::Or { left, right, .. } => match (left.as_ref(), right.as_ref()) {
+            (Expr::False(_), Expr::False(_)) => Expr::False(NodeId::default()),
+            (Expr::False(_) | Expr::True(_), Expr::False(_) | Expr::True(_)) => {
+                Expr::True(NodeId::default())
+            }
+            _ => return Ok(None),
+        },
+        Expr::App { lam, arg, .. } => match lam.as_ref() {
+            Expr::Lam { x, e, .. } | Expr::LinLam { x, e, .. } => {
+                e.clone().substitute(x.clone(), *arg.clone())
+            }
+            Expr::Cast {
+                e: f,
+                from,
+                to,
+                blame,
+                ..
+            } if is_value(lam) => match (from.as_ref(), to.as_ref()) {
+                (Type::Fn { arg: a1, ret: r1 }, Type::Fn { arg: a2, ret: r2 })
+                | (Type::LinFn { arg: a1, ret: r1 }, Type::LinFn { arg: a2, ret: r2 }) => {
+                    Expr::Cast {
+                        e: Box::new(Expr::App {
+                            lam: f.clone(),
+                            arg: Box::new(Expr::Cast {
+                                e: arg.clone(),
+                                from: a2.clone(),
+                                to: a1.clone(),
+                                blame: blame.flip(),
+                                id: NodeId::default(),
+                            }),
+                            id: NodeId::default(),
+                        }),
+                        from: r1.clone(),
+                        to: r2.clone(),
+                        blame: blame.clone(),
+                        id: NodeId::default(),
+                    }
+                }
+                _ => unreachable!(),
+            },
+            _ => return Ok(None),
+        },
+        Expr::Project { e, d, .. } => match (e.as_ref(), d) {
+            (Expr::Pair { left, .. }, Direction::Left) => *left.clone(),
+            (Expr::Pair { right, .. }, Direction::Right) => *right.clone(),
+            _ => return Ok(None),
+        },
+        // at once, so neither component is substituted into the other
+        Expr::LetPair {
+            x,
+            y,
+            e_pair,
+            e_body,
+            ..
+        } => match e_pair.as_ref() {
+            Expr::Pair { left, right, .. } => e_body.clone().substitute_map(HashMap::from([
+                (x.clone(), *left.clone()),
+                (y.clone(), *right.clone()),
+            ])),
+            _ => return Ok(None),
+        },
+        Expr::Case {
+            e,
+            xleft,
+            eleft,
+            xright,
+            eright,
+            ..
+        } => match e.as_ref() {
+            Expr::Inject {
+                e,
+                d: Direction::Left,
+                ..
+            } => eleft.clone().substitute(xleft.clone(), *e.clone()),
+            Expr::Inject {
+                e,
+                d: Direction::Right,
+                ..
+            } => eright.clone().substitute(xright.clone(), *e.clone()),
+            _ => return Ok(None),
+        },
+        Expr::TyApp { e, .. } => match e.as_ref() {
+            Expr::TyLam { e, .. } => *e.clone(),
+            _ => return Ok(None),
+        },
+        Expr::Unfold(e, _) => match e.as_ref() {
+            Expr::Fold { e, .. } => *e.clone(),
+            _ => return Ok(None),
+        },
+        Expr::Import {
+            x, e_mod, e_body, ..
+        } => match e_mod.as_ref() {
+            Expr::Export { e, .. } => e_body.clone().substitute(x.clone(), *e.clone()),
+            _ => return Ok(None),
+        },
+        Expr::Cast {
+            e, from, to, blame, ..
+        } if is_value(e) => match cast_value(e, from, to, blame) {
+            Outcome::Step(e) => e,
+            Outcome::Value => return Ok(None),
+            Outcome::Blame(blame) => return Err(blame),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(result))
+}
+
+/// Contract the leftmost-outermost redex in place. Returns `false` on a normal form.
+pub fn step(e: &mut Expr) -> Result<bool, Blame> {
+    if let Some(result) = contract(e)? {
+        *e = result;
+        return Ok(true);
+    }
+    for head in heads_mut(e) {
+        if let Expr::Fix { x, e: body, .. } = &*head {
+            *head = body.clone().substitute(x.clone(), head.clone());
+            return Ok(true);
+        }
+    }
+    for child in e.children_mut() {
+        if step(child)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Reduce to normal form in at most `budget` steps
+pub fn normalize(
+    e: &Expr,
+    budget: usize,
+    mode: Mode,
+    output: impl Into<Format>,
+) -> Result<Expr, Error> {
+    let format = output.into();
+    let mut e = e.clone();
+    for n in 1..=budget {
+        let before = (mode == Mode::VeryVerbose).then(|| e.clone());
+        if !step(&mut e).map_err(Error::Blame)? {
+            return Ok(e);
+        }
+        if let Some(before) = before {
+            println!("{}", format_ast(&before, format, Some(format!("step{n}"))))
+        }
+    }
+    // the last step may have reached the normal form
+    match step(&mut e.clone()).map_err(Error::Blame)? {
+        true => Err(Error::OutOfSteps(budget)),
+        false => Ok(e),
+    }
+}
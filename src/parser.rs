@@ -1,18 +1,53 @@
-use crate::ast::{Expr, Type};
+use crate::{
+    ast::{Expr, Type},
+    span::{SourceMap, Span},
+};
 
-use lalrpop_util::lalrpop_mod;
+use lalrpop_util::{lalrpop_mod, ParseError};
 
 lalrpop_mod!(grammar);
 
 pub fn parse(input: &str) -> Result<Box<Expr>, String> {
+    parse_with_spans(input).map(|(e, _)| e)
+}
+
+/// Parse the expression and record the source location of every node
+pub fn parse_with_spans(input: &str) -> Result<(Box<Expr>, SourceMap), String> {
+    parse_located(input).map_err(|(err, _)| err)
+}
+
+/// Like `parse_with_spans`, but a syntax error also says where it is
+pub fn parse_located(input: &str) -> Result<(Box<Expr>, SourceMap), (String, Span)> {
+    let mut spans = SourceMap::new(input);
     grammar::ExprParser::new()
-        .parse(input)
-        .map_err(|e| e.to_string())
+        .parse(&mut spans, input)
+        .map(|e| (e, spans))
+        .map_err(|e| {
+            let span = match &e {
+                ParseError::InvalidToken { location }
+                | ParseError::UnrecognizedEof { location, .. } => Span {
+                    start: *location,
+                    end: *location,
+                },
+                ParseError::UnrecognizedToken {
+                    token: (start, _, end),
+                    ..
+                }
+                | ParseError::ExtraToken {
+                    token: (start, _, end),
+                } => Span {
+                    start: *start,
+                    end: *end,
+                },
+                ParseError::User { .. } => Span::default(),
+            };
+            (e.to_string(), span)
+        })
 }
 
 #[allow(unused)]
 pub fn parse_type(input: &str) -> Result<Box<Type>, String> {
     grammar::TypeParser::new()
-        .parse(input)
+        .parse(&mut SourceMap::new(input), input)
         .map_err(|e| e.to_string())
 }
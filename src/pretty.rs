@@ -0,0 +1,711 @@
+//! Printers that fit programs into a line width, for two notations.
+//!
+//! The source notation parses back to the same program. Parentheses are only added where the
+//! grammar needs them: every construct has the level of the nonterminal that produces it, and a
+//! subterm is parenthesized when its level is below the one its position in the grammar accepts.
+//! Applications of functions print as `let`s and `letrec`s, which parse to the same terms.
+//! Casts and negative numbers have no concrete syntax.
+//!
+//! The simplified notation of `OutputMode::Simplified` and `Display` leaves out most type
+//! annotations and parenthesizes every operation, but not types.
+
+use crate::{
+    ast::*,
+    ast_util::Symbol,
+    doc::{align, alone, group, line, nest, text, Doc},
+};
+
+/// The line width `pretty` fits programs into
+pub const WIDTH: usize = 80;
+
+/// The nonterminals of the expression grammar, loosest first
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    LetRec,
+    Let,
+    TyLam,
+    Func,
+    Fix,
+    App,
+    TyApp,
+    Import,
+    Export,
+    Unfold,
+    Fold,
+    If,
+    Case,
+    Inject,
+    Project,
+    Or,
+    And,
+    Rel,
+    Add,
+    Mul,
+    Primary,
+}
+
+/// The nonterminals of the type grammar, loosest first
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TypeLevel {
+    Quantified,
+    Func,
+    Product,
+    Sum,
+    Primary,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Notation {
+    Source,
+    Simplified,
+    /// LaTeX math mode, for types only
+    Latex,
+}
+
+impl Notation {
+    /// How the notation spells a keyword or symbol of the source notation
+    fn spell(self, source: &'static str) -> &'static str {
+        match (self, source) {
+            (Notation::Simplified, "unit") => "()",
+            (Notation::Simplified, "->") => "→",
+            (Notation::Simplified, "-o") => "⊸",
+            (Notation::Simplified, "rec") => "μ",
+            (Notation::Simplified, "forall") => "∀",
+            (Notation::Simplified, "exists") => "∃",
+            (Notation::Latex, "num") => "\\mathsf{num}",
+            (Notation::Latex, "bool") => "\\mathsf{bool}",
+            (Notation::Latex, "unit") => "\\mathsf{unit}",
+            (Notation::Latex, "->") => "\\to",
+            (Notation::Latex, "-o") => "\\multimap",
+            (Notation::Latex, "*") => "\\times",
+            (Notation::Latex, "rec") => "\\mu",
+            (Notation::Latex, "forall") => "\\forall",
+            (Notation::Latex, "exists") => "\\exists",
+            _ => source,
+        }
+    }
+
+    fn name(self, name: &str) -> Doc {
+        match self {
+            Notation::Latex => text(format!("\\mathit{{{}}}", name.replace('_', "\\_"))),
+            _ => text(name),
+        }
+    }
+}
+
+/// A `let` or `letrec`: its variable, annotation, definition and body
+fn as_let(e: &Expr) -> Option<(bool, &Variable, &Type, &Expr, &Expr)> {
+    let Expr::App { lam, arg, .. } = e else {
+        return None;
+    };
+    let Expr::Lam {
+        x, tau, e: body, ..
+    } = lam.as_ref()
+    else {
+        return None;
+    };
+    match arg.as_ref() {
+        Expr::Fix {
+            x: y,
+            tau: sigma,
+            e: definition,
+            ..
+        } if x == y && tau == sigma => Some((true, x, tau, definition, body)),
+        _ => Some((false, x, tau, arg, body)),
+    }
+}
+
+fn level(e: &Expr) -> Level {
+    match e {
+        Expr::App { .. } => match as_let(e) {
+            Some((true, ..)) => Level::LetRec,
+            Some((false, ..)) => Level::Let,
+            None => Level::App,
+        },
+        Expr::LetPair { .. } => Level::Let,
+        Expr::TyLam { .. } => Level::TyLam,
+        Expr::Lam { .. } | Expr::LinLam { .. } => Level::Func,
+        Expr::Fix { .. } => Level::Fix,
+        Expr::TyApp { .. } => Level::TyApp,
+        Expr::Import { .. } => Level::Import,
+        Expr::Export { .. } => Level::Export,
+        Expr::Unfold(..) => Level::Unfold,
+        Expr::Fold { .. } => Level::Fold,
+        Expr::If { .. } => Level::If,
+        Expr::Case { .. } => Level::Case,
+        Expr::Inject { .. } => Level::Inject,
+        Expr::Project { .. } => Level::Project,
+        Expr::Or { .. } => Level::Or,
+        Expr::And { .. } => Level::And,
+        Expr::Relop { .. } => Level::Rel,
+        Expr::Addop { .. } => Level::Add,
+        Expr::Mulop { .. } => Level::Mul,
+        Expr::Cast { .. } => Level::App,
+        Expr::Num(..)
+        | Expr::True(_)
+        | Expr::False(_)
+        | Expr::Unit(_)
+        | Expr::Var(..)
+        | Expr::Pair { .. } => Level::Primary,
+    }
+}
+
+fn type_level(tau: &Type) -> TypeLevel {
+    match tau {
+        Type::Rec { .. } | Type::Forall { .. } | Type::Exists { .. } => TypeLevel::Quantified,
+        Type::Fn { .. } | Type::LinFn { .. } => TypeLevel::Func,
+        Type::Product { .. } => TypeLevel::Product,
+        Type::Sum { .. } => TypeLevel::Sum,
+        _ => TypeLevel::Primary,
+    }
+}
+
+fn direction(d: &Direction) -> &'static str {
+    match d {
+        Direction::Left => "L",
+        Direction::Right => "R",
+    }
+}
+
+fn parenthesize(doc: Doc) -> Doc {
+    text("(") + nest(1, doc) + ")"
+}
+
+/// Print a type in source notation on one line. Bound variables are renamed where their names
+/// would shadow an enclosing binder or capture a free variable.
+pub fn pretty_type(tau: &Type) -> String {
+    type_doc(tau, Notation::Source).flat()
+}
+
+/// Print a type in simplified notation, breaking it up to fit into `width` columns
+pub fn simplified_type(tau: &Type, width: usize) -> String {
+    type_doc(tau, Notation::Simplified).render(width)
+}
+
+/// Print an expression for LaTeX, in source notation and a typewriter font
+pub fn latex_expr(e: &Expr) -> String {
+    let mut latex = String::from("\\texttt{");
+    for c in source(e).flat().chars() {
+        match c {
+            '\\' => latex += "\\textbackslash{}",
+            '~' => latex += "\\textasciitilde{}",
+            '^' => latex += "\\textasciicircum{}",
+            '{' | '}' | '_' | '#' | '&' | '$' | '%' => {
+                latex.push('\\');
+                latex.push(c)
+            }
+            c => latex.push(c),
+        }
+    }
+    latex + "}"
+}
+
+/// Print a type for LaTeX math mode
+pub fn latex_type(tau: &Type) -> String {
+    type_doc(tau, Notation::Latex).flat()
+}
+
+pub(crate) fn simplified_type_doc(tau: &Type) -> Doc {
+    type_doc(tau, Notation::Simplified)
+}
+
+fn type_doc(tau: &Type, notation: Notation) -> Doc {
+    align(ty(tau, TypeLevel::Quantified, notation, &mut Vec::new()))
+}
+
+/// `names` holds the names chosen for the enclosing binders, innermost last
+fn ty(tau: &Type, at: TypeLevel, notation: Notation, names: &mut Vec<String>) -> Doc {
+    let mut infix = |left: &Type, op, right: &Type, at: TypeLevel, right_at: TypeLevel| {
+        let left = ty(left, at, notation, names);
+        let op = notation.spell(op);
+        group(left + " " + op + line() + ty(right, right_at, notation, names))
+    };
+    let doc = match tau {
+        Type::Num => text(notation.spell("num")),
+        Type::Bool => text(notation.spell("bool")),
+        Type::Unit => text(notation.spell("unit")),
+        Type::Dyn => text("?"),
+        Type::Var(a) => notation.name(&a.0),
+        Type::Bound(i) => match names.len().checked_sub(*i as usize + 1) {
+            Some(level) => notation.name(&names[level]),
+            None => text(format!("#{i}")),
+        },
+        Type::Fn { arg, ret } => infix(arg, "->", ret, TypeLevel::Product, TypeLevel::Func),
+        Type::LinFn { arg, ret } => infix(arg, "-o", ret, TypeLevel::Product, TypeLevel::Func),
+        Type::Product { left, right } => {
+            infix(left, "*", right, TypeLevel::Sum, TypeLevel::Product)
+        }
+        Type::Sum { left, right } => infix(left, "+", right, TypeLevel::Primary, TypeLevel::Sum),
+        Type::Rec { a, tau: body }
+        | Type::Forall { a, tau: body }
+        | Type::Exists { a, tau: body } => {
+            let quantifier = notation.spell(match tau {
+                Type::Rec { .. } => "rec",
+                Type::Forall { .. } => "forall",
+                _ => "exists",
+            });
+            // primes aren't allowed in identifiers
+            let free = body.free_vars();
+            let mut name = a.0.clone();
+            let mut n = 0;
+            while names.contains(&name) || free.contains(&Variable::from(&name)) {
+                n += 1;
+                name = match notation {
+                    Notation::Source => format!("{}{n}", a.0),
+                    _ => format!("{}{}", a.0, "'".repeat(n)),
+                };
+            }
+            names.push(name);
+            let body = ty(body, TypeLevel::Quantified, notation, names);
+            let name = notation.name(&names.pop().unwrap());
+            group(text(format!("{quantifier} ")) + name + " ." + nest(2, line() + body))
+        }
+    };
+    match type_level(tau) < at {
+        true => parenthesize(doc),
+        false => doc,
+    }
+}
+
+pub fn pretty(e: &Expr) -> String {
+    pretty_width(e, WIDTH)
+}
+
+/// Print a program in source notation, breaking the constructs that don't fit into `width`
+/// columns over several lines
+pub fn pretty_width(e: &Expr, width: usize) -> String {
+    source(e).render(width)
+}
+
+/// `e` in source notation
+fn source(e: &Expr) -> Doc {
+    let tau = |tau: &Type| type_doc(tau, Notation::Source);
+    let child = |e: &Expr, at: Level| match level(e) < at {
+        true => parenthesize(source(e)),
+        false => source(e),
+    };
+    let binary = |left: &Expr, op: &str, right: &Expr, at: Level, right_at: Level| {
+        let right = nest(op.len() + 1, child(right, right_at));
+        group(child(left, at) + line() + op + " " + right)
+    };
+    if let Some((rec, x, sigma, definition, body)) = as_let(e) {
+        let (keyword, at) = match rec {
+            true => ("letrec", Level::LetRec),
+            false => ("let", Level::Let),
+        };
+        // the definition stays on the line of the `let` if it fits there
+        let header = text(format!("{keyword} {} : ", x.0)) + tau(sigma) + " =";
+        let definition = group(header + nest(2, line() + child(definition, at)) + " in");
+        return group(definition + line() + child(body, at));
+    }
+    match e {
+        Expr::Num(n, _) => text(n.to_string()),
+        Expr::True(_) => text("true"),
+        Expr::False(_) => text("false"),
+        Expr::Unit(_) => text("()"),
+        Expr::Var(x, _) => text(&x.0),
+        Expr::Addop {
+            binop, left, right, ..
+        } => binary(left, &binop.to_string(), right, Level::Add, Level::Mul),
+        Expr::Mulop {
+            binop, left, right, ..
+        } => binary(left, &binop.to_string(), right, Level::Mul, Level::Primary),
+        Expr::Relop {
+            relop, left, right, ..
+        } => {
+            let op = match relop {
+                RelOp::Eq => "==",
+                RelOp::Lt => "<",
+                RelOp::Gt => ">",
+            };
+            binary(left, op, right, Level::Rel, Level::Add)
+        }
+        Expr::And { left, right, .. } => binary(left, "&&", right, Level::And, Level::Rel),
+        Expr::Or { left, right, .. } => binary(left, "||", right, Level::Or, Level::And),
+        Expr::If {
+            cond, then_, else_, ..
+        } => group(
+            text("if ")
+                + nest(3, child(cond, Level::Case))
+                + line()
+                + "then "
+                + nest(5, child(then_, Level::Case))
+                + line()
+                + "else "
+                + nest(5, child(else_, Level::Case)),
+        ),
+        Expr::Lam {
+            x,
+            tau: sigma,
+            e: body,
+            ..
+        }
+        | Expr::LinLam {
+            x,
+            tau: sigma,
+            e: body,
+            ..
+        } => {
+            let arrow = if let Expr::Lam { .. } = e { "->" } else { "-o" };
+            let header = text(format!("fun ({} : ", x.0)) + tau(sigma) + ") " + arrow;
+            group(header + nest(2, line() + child(body, Level::Func)))
+        }
+        Expr::Fix {
+            x,
+            tau: sigma,
+            e: body,
+            ..
+        } => {
+            let header = text(format!("fix ({} : ", x.0)) + tau(sigma) + ") ->";
+            group(header + nest(2, line() + child(body, Level::Fix)))
+        }
+        Expr::TyLam { a, e: body, .. } => {
+            group(text(format!("tyfun {} ->", a.0)) + nest(2, line() + child(body, Level::TyLam)))
+        }
+        Expr::App { .. } => {
+            // the arguments of `f a b` go on lines of their own
+            let mut args = Vec::new();
+            let mut head = e;
+            while let (Expr::App { lam, arg, .. }, None) = (head, as_let(head)) {
+                args.push(arg);
+                head = lam;
+            }
+            let args = args
+                .into_iter()
+                .rev()
+                .fold(text(""), |doc, arg| doc + line() + child(arg, Level::TyApp));
+            group(child(head, Level::App) + nest(2, args))
+        }
+        Expr::TyApp { e, tau: sigma, .. } => child(e, Level::TyApp) + " [" + tau(sigma) + "]",
+        Expr::Pair { left, right, .. } => group(parenthesize(
+            child(left, Level::LetRec) + "," + line() + child(right, Level::LetRec),
+        )),
+        Expr::Project { e, d, .. } => child(e, Level::Or) + "." + direction(d),
+        Expr::LetPair {
+            x,
+            y,
+            e_pair,
+            e_body,
+            ..
+        } => {
+            let header = text(format!("let ({}, {}) =", x.0, y.0));
+            let definition = group(header + nest(2, line() + child(e_pair, Level::Let)) + " in");
+            group(definition + line() + child(e_body, Level::Let))
+        }
+        Expr::Inject {
+            e, d, tau: sigma, ..
+        } => {
+            let annotation = text(format!(" = {} as ", direction(d))) + tau(sigma);
+            text("inj ") + nest(4, child(e, Level::Project)) + annotation
+        }
+        Expr::Case {
+            e,
+            xleft,
+            eleft,
+            xright,
+            eright,
+            ..
+        } => {
+            let left = text(format!("L ({}) -> ", xleft.0))
+                + nest(8 + xleft.0.len(), child(eleft, Level::Case));
+            let right = text(format!("| R ({}) -> ", xright.0))
+                + nest(10 + xright.0.len(), child(eright, Level::Case));
+            // the scrutinee is not broken up for the sake of the brace
+            let scrutinee = text("case ") + nest(5, alone(child(e, Level::Case))) + " {";
+            group(scrutinee + nest(2, line() + left) + line() + right + line() + "}")
+        }
+        Expr::Fold { e, tau: sigma, .. } => {
+            text("fold ") + nest(5, child(e, Level::If)) + " as " + tau(sigma)
+        }
+        Expr::Unfold(e, _) => text("unfold ") + nest(7, child(e, Level::Fold)),
+        Expr::Export {
+            e,
+            tau_adt,
+            tau_mod,
+            ..
+        } => {
+            let types = line() + "without " + tau(tau_adt) + line() + "as " + tau(tau_mod);
+            group(text("export ") + nest(7, child(e, Level::Unfold)) + nest(2, types))
+        }
+        Expr::Import {
+            x,
+            a,
+            e_mod,
+            e_body,
+            ..
+        } => {
+            let header = text(format!("import ({}, {}) =", x.0, a.0));
+            let module = group(header + nest(2, line() + child(e_mod, Level::Import)) + " in");
+            group(module + line() + child(e_body, Level::Export))
+        }
+        Expr::Cast { e, from, to, .. } => {
+            text("⟨") + tau(to) + " ⇐ " + tau(from) + "⟩ " + child(e, Level::Primary)
+        }
+    }
+}
+
+/// Print in simplified notation, breaking constructs that don't fit into `width` columns over
+/// several lines
+pub fn simplified(e: &Expr, width: usize) -> String {
+    simplified_doc(e).render(width)
+}
+
+/// Whether `e` continues a chain of pairs nested to the right, like a list or a tuple
+fn continues_chain(e: &Expr) -> bool {
+    match e {
+        Expr::Pair { .. } => true,
+        Expr::Fold { e, .. } | Expr::Inject { e, .. } => continues_chain(e),
+        _ => false,
+    }
+}
+
+/// Parentheses whose contents line up after the opening one
+fn enclose(doc: Doc) -> Doc {
+    text("(") + align(doc) + ")"
+}
+
+/// A chain of pairs nested to the right with its elements lined up, rather than each moving
+/// further right
+fn chain(e: &Expr) -> Doc {
+    match e {
+        Expr::Pair { left, right, .. } => {
+            let right = match continues_chain(right) {
+                true => chain(right),
+                false => align(simplified_doc(right)),
+            };
+            group(text("(") + align(simplified_doc(left)) + " ," + line() + right + ")")
+        }
+        Expr::Fold { e, .. } => text("fold ") + chain(e) + " as ...",
+        Expr::Inject { e, .. } => chain(e),
+        _ => simplified_doc(e),
+    }
+}
+
+pub(crate) fn simplified_doc(e: &Expr) -> Doc {
+    let tau = simplified_type_doc;
+    let binary = |left: &Expr, op: &str, right: &Expr| {
+        let body = simplified_doc(left) + line() + op + " " + simplified_doc(right);
+        group(enclose(body))
+    };
+    let binder = |header: Doc, body: &Expr| group(header + nest(2, line() + simplified_doc(body)));
+    match e {
+        Expr::Var(x, _) => text(&x.0),
+        Expr::Num(n, _) => text(n.to_string()),
+        Expr::True(_) => text("true"),
+        Expr::False(_) => text("false"),
+        Expr::Unit(_) => text("()"),
+        Expr::Addop {
+            binop, left, right, ..
+        } => binary(left, &binop.to_string(), right),
+        Expr::Mulop {
+            binop, left, right, ..
+        } => binary(left, &binop.to_string(), right),
+        Expr::Relop {
+            relop, left, right, ..
+        } => binary(left, &relop.to_string(), right),
+        Expr::And { left, right, .. } => binary(left, "&&", right),
+        Expr::Or { left, right, .. } => binary(left, "||", right),
+        Expr::If {
+            cond, then_, else_, ..
+        } => group(
+            text("if ")
+                + nest(3, simplified_doc(cond))
+                + line()
+                + "then "
+                + nest(5, simplified_doc(then_))
+                + line()
+                + "else "
+                + nest(5, simplified_doc(else_)),
+        ),
+        Expr::Pair { .. } => align(chain(e)),
+        Expr::Project { e: pair, d, .. } => match (pair.as_ref(), d) {
+            (Expr::Pair { left, .. }, Direction::Left) => simplified_doc(left),
+            (Expr::Pair { right, .. }, Direction::Right) => simplified_doc(right),
+            _ => text(format!("{:?}", e)),
+        },
+        Expr::LetPair {
+            x,
+            y,
+            e_pair,
+            e_body,
+            ..
+        } => {
+            let header = text(format!("let ({}, {}) =", x.0, y.0));
+            let definition = group(header + nest(2, line() + simplified_doc(e_pair)) + " in");
+            group(definition + line() + simplified_doc(e_body))
+        }
+        Expr::Inject { e, .. } => simplified_doc(e),
+        Expr::Case {
+            e,
+            xleft,
+            eleft,
+            xright,
+            eright,
+            ..
+        } => {
+            let left = text(format!("L({}) -> ", xleft.0)) + align(simplified_doc(eleft));
+            let right = text(format!("| R({}) -> ", xright.0)) + align(simplified_doc(eright));
+            let scrutinee = text("case ") + nest(5, alone(simplified_doc(e))) + " of";
+            group(scrutinee + nest(2, line() + left + line() + right))
+        }
+        Expr::App { lam, arg, .. } => {
+            group(enclose(simplified_doc(lam) + line() + simplified_doc(arg)))
+        }
+        Expr::Lam {
+            x, tau: sigma, e, ..
+        } => binder(text(format!("λ ({} : ", x.0)) + tau(sigma) + ") ->", e),
+        Expr::LinLam {
+            x, tau: sigma, e, ..
+        } => binder(text(format!("λ ({} : ", x.0)) + tau(sigma) + ") -o", e),
+        Expr::TyLam { a, e, .. } => binder(text(format!("Λ {} ->", a.0)), e),
+        Expr::TyApp { e, tau: sigma, .. } => {
+            group(enclose(simplified_doc(e) + line() + tau(sigma)))
+        }
+        Expr::Fix {
+            x, tau: sigma, e, ..
+        } => binder(text(format!("fix ({} : ", x.0)) + tau(sigma) + ") ->", e),
+        Expr::Fold { e: body, .. } if continues_chain(body) => align(chain(e)),
+        Expr::Fold { e, .. } => text("fold ") + align(simplified_doc(e)) + " as ...",
+        Expr::Unfold(e, _) => text("unfold ") + nest(7, simplified_doc(e)),
+        Expr::Export {
+            e,
+            tau_adt,
+            tau_mod,
+            ..
+        } => {
+            let types = line() + "without " + tau(tau_adt) + line() + "as " + tau(tau_mod);
+            group(text("export ") + nest(7, simplified_doc(e)) + nest(2, types))
+        }
+        Expr::Import {
+            x,
+            a,
+            e_mod,
+            e_body,
+            ..
+        } => {
+            let header = text(format!("import ({}, {}) =", x.0, a.0));
+            let module = group(header + nest(2, line() + simplified_doc(e_mod)) + " in");
+            group(module + line() + simplified_doc(e_body))
+        }
+        Expr::Cast { e, from, to, .. } => {
+            text("⟨") + tau(to) + " ⇐ " + tau(from) + "⟩ " + simplified_doc(e)
+        }
+    }
+}
+
+/// Print a value of type `tau` the way other languages write the common encodings: lists of type
+/// `rec a . unit + (T * a)` as `[1, 2, 3]`, options of type `unit + T` as `None` and `Some 4`,
+/// and pairs nested to the right as tuples `(1, true, ())`. Anything else, including the parts
+/// of lazily evaluated values that are still unevaluated, prints in simplified notation.
+pub fn value(e: &Expr, tau: &Type, width: usize) -> String {
+    value_doc(e, tau).render(width)
+}
+
+/// The element type of a list type `rec a . unit + (T * a)`
+fn list_element(tau: &Type) -> Option<&Type> {
+    let Type::Rec { tau: body, .. } = tau else {
+        return None;
+    };
+    let Type::Sum { left, right } = body.as_ref() else {
+        return None;
+    };
+    let Type::Product {
+        left: element,
+        right: tail,
+    } = right.as_ref()
+    else {
+        return None;
+    };
+    // the elements can't refer to the list type
+    match (left.as_ref(), tail.as_ref()) {
+        (Type::Unit, Type::Bound(0)) if element.open(&Type::Dyn) == **element => Some(element),
+        _ => None,
+    }
+}
+
+/// The elements of a list, unless its spine is not evaluated all the way
+fn list_elements(mut e: &Expr) -> Option<Vec<&Expr>> {
+    let mut elements = Vec::new();
+    loop {
+        let Expr::Fold { e: cell, .. } = e else {
+            return None;
+        };
+        match cell.as_ref() {
+            Expr::Inject {
+                d: Direction::Left, ..
+            } => return Some(elements),
+            Expr::Inject {
+                e: cons,
+                d: Direction::Right,
+                ..
+            } => match cons.as_ref() {
+                Expr::Pair { left, right, .. } => {
+                    elements.push(left);
+                    e = right;
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+}
+
+fn is_option(tau: &Type) -> bool {
+    matches!(tau, Type::Sum { left, .. } if **left == Type::Unit)
+}
+
+/// `elements` separated by commas, all on one line or each on a line of its own
+fn sequence(open: &str, elements: Vec<Doc>, close: &str) -> Doc {
+    let mut elements = elements.into_iter().map(align);
+    let first = elements.next().unwrap_or_else(|| text(""));
+    let elements = elements.fold(first, |doc, element| doc + "," + line() + element);
+    group(text(open) + align(elements) + close)
+}
+
+fn value_doc(e: &Expr, tau: &Type) -> Doc {
+    if let (Some(element), Some(elements)) = (list_element(tau), list_elements(e)) {
+        let elements = elements.into_iter().map(|e| value_doc(e, element));
+        return sequence("[", elements.collect(), "]");
+    }
+    match (e, tau) {
+        (
+            Expr::Inject {
+                d: Direction::Left, ..
+            },
+            _,
+        ) if is_option(tau) => text("None"),
+        (
+            Expr::Inject {
+                e,
+                d: Direction::Right,
+                ..
+            },
+            Type::Sum { right, .. },
+        ) if is_option(tau) => match (e.as_ref(), is_option(right)) {
+            (Expr::Inject { .. }, true) => text("Some ") + enclose(value_doc(e, right)),
+            _ => text("Some ") + align(value_doc(e, right)),
+        },
+        (Expr::Inject { e, d, .. }, Type::Sum { left, right }) => match d {
+            Direction::Left => value_doc(e, left),
+            Direction::Right => value_doc(e, right),
+        },
+        (Expr::Pair { .. }, Type::Product { .. }) => {
+            let (mut e, mut tau) = (e, tau);
+            let mut elements = Vec::new();
+            while let (
+                Expr::Pair { left, right, .. },
+                Type::Product {
+                    left: sigma,
+                    right: rest,
+                },
+            ) = (e, tau)
+            {
+                elements.push(value_doc(left, sigma));
+                (e, tau) = (right, rest);
+            }
+            elements.push(value_doc(e, tau));
+            sequence("(", elements, ")")
+        }
+        _ => simplified_doc(e),
+    }
+}
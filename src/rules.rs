@@ -0,0 +1,949 @@
+//! The small-step rules, written once for the two representations of terms: the tree of
+//! `ast::Expr` that `evaluate::Machine` steps, and the `arena::Arena`.
+//!
+//! A representation is a `Store`: it shows a term one node deep as a `Shape`, whose subterms,
+//! types and variables are whatever the store uses to refer to them, and it builds new nodes
+//! from shapes. `step` finds the redex and `contract` applies the rule for it.
+
+use crate::{
+    ast::*,
+    gradual::{consistent, ground},
+};
+
+pub enum Outcome<T = Expr> {
+    Step(T),
+    Value,
+    /// A runtime cast failed
+    Blame(Blame),
+}
+
+/// One node of a term with subterms `E`, types `T` and variables `V`, otherwise like `Expr`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Shape<E, T, V> {
+    Num(i32),
+    Addop {
+        binop: AddOp,
+        left: E,
+        right: E,
+    },
+    Mulop {
+        binop: MulOp,
+        left: E,
+        right: E,
+    },
+    True,
+    False,
+    If {
+        cond: E,
+        then_: E,
+        else_: E,
+    },
+    Relop {
+        relop: RelOp,
+        left: E,
+        right: E,
+    },
+    And {
+        left: E,
+        right: E,
+    },
+    Or {
+        left: E,
+        right: E,
+    },
+    Var(V),
+    Lam {
+        x: V,
+        tau: T,
+        e: E,
+    },
+    LinLam {
+        x: V,
+        tau: T,
+        e: E,
+    },
+    App {
+        lam: E,
+        arg: E,
+    },
+    Unit,
+    Pair {
+        left: E,
+        right: E,
+    },
+    Project {
+        e: E,
+        d: Direction,
+    },
+    LetPair {
+        x: V,
+        y: V,
+        e_pair: E,
+        e_body: E,
+    },
+    Inject {
+        e: E,
+        d: Direction,
+        tau: T,
+    },
+    Case {
+        e: E,
+        xleft: V,
+        eleft: E,
+        xright: V,
+        eright: E,
+    },
+    Fix {
+        x: V,
+        tau: T,
+        e: E,
+    },
+    TyLam {
+        a: V,
+        e: E,
+    },
+    TyApp {
+        e: E,
+        tau: T,
+    },
+    Fold {
+        e: E,
+        tau: T,
+    },
+    Unfold(E),
+    Export {
+        e: E,
+        tau_adt: T,
+        tau_mod: T,
+    },
+    Import {
+        x: V,
+        a: V,
+        e_mod: E,
+        e_body: E,
+    },
+    Cast {
+        e: E,
+        from: T,
+        to: T,
+        blame: Blame,
+    },
+}
+
+impl<'a> Shape<&'a Expr, &'a Type, &'a Variable> {
+    /// The top node of `e`, borrowing its parts
+    pub fn of(e: &'a Expr) -> Self {
+        match e {
+            Expr::Num(n, _) => Shape::Num(*n),
+            Expr::Addop {
+                binop, left, right, ..
+            } => Shape::Addop {
+                binop: binop.clone(),
+                left,
+                right,
+            },
+            Expr::Mulop {
+                binop, left, right, ..
+            } => Shape::Mulop {
+                binop: binop.clone(),
+                left,
+                right,
+            },
+            Expr::True(_) => Shape::True,
+            Expr::False(_) => Shape::False,
+            Expr::If {
+                cond, then_, else_, ..
+            } => Shape::If { cond, then_, else_ },
+            Expr::Relop {
+                relop, left, right, ..
+            } => Shape::Relop {
+                relop: relop.clone(),
+                left,
+                right,
+            },
+            Expr::And { left, right, .. } => Shape::And { left, right },
+            Expr::Or { left, right, .. } => Shape::Or { left, right },
+            Expr::Var(x, _) => Shape::Var(x),
+            Expr::Lam { x, tau, e, .. } => Shape::Lam { x, tau, e },
+            Expr::LinLam { x, tau, e, .. } => Shape::LinLam { x, tau, e },
+            Expr::App { lam, arg, .. } => Shape::App { lam, arg },
+            Expr::Unit(_) => Shape::Unit,
+            Expr::Pair { left, right, .. } => Shape::Pair { left, right },
+            Expr::Project { e, d, .. } => Shape::Project { e, d: d.clone() },
+            Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                ..
+            } => Shape::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+            },
+            Expr::Inject { e, d, tau, .. } => Shape::Inject {
+                e,
+                d: d.clone(),
+                tau,
+            },
+            Expr::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+                ..
+            } => Shape::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+            },
+            Expr::Fix { x, tau, e, .. } => Shape::Fix { x, tau, e },
+            Expr::TyLam { a, e, .. } => Shape::TyLam { a, e },
+            Expr::TyApp { e, tau, .. } => Shape::TyApp { e, tau },
+            Expr::Fold { e, tau, .. } => Shape::Fold { e, tau },
+            Expr::Unfold(e, _) => Shape::Unfold(e),
+            Expr::Export {
+                e,
+                tau_adt,
+                tau_mod,
+                ..
+            } => Shape::Export {
+                e,
+                tau_adt,
+                tau_mod,
+            },
+            Expr::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+                ..
+            } => Shape::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+            },
+            Expr::Cast {
+                e, from, to, blame, ..
+            } => Shape::Cast {
+                e,
+                from,
+                to,
+                blame: blame.clone(),
+            },
+        }
+    }
+}
+
+impl From<Shape<Expr, Type, Variable>> for Expr {
+    fn from(shape: Shape<Expr, Type, Variable>) -> Self {
+        shape
+            .map(
+                &mut (),
+                |_, e| Box::new(e),
+                |_, tau| Box::new(tau),
+                |_, x| x,
+            )
+            .into()
+    }
+}
+
+impl From<Shape<Box<Expr>, Box<Type>, Variable>> for Expr {
+    fn from(shape: Shape<Box<Expr>, Box<Type>, Variable>) -> Self {
+        shape.with_id(NodeId::default())
+    }
+}
+
+impl Shape<Box<Expr>, Box<Type>, Variable> {
+    /// The node of this shape with id `id`
+    pub fn with_id(self, id: NodeId) -> Expr {
+        match self {
+            Shape::Num(n) => Expr::Num(n, id),
+            Shape::Addop { binop, left, right } => Expr::Addop {
+                binop,
+                left,
+                right,
+                id,
+            },
+            Shape::Mulop { binop, left, right } => Expr::Mulop {
+                binop,
+                left,
+                right,
+                id,
+            },
+            Shape::True => Expr::True(id),
+            Shape::False => Expr::False(id),
+            Shape::If { cond, then_, else_ } => Expr::If {
+                cond,
+                then_,
+                else_,
+                id,
+            },
+            Shape::Relop { relop, left, right } => Expr::Relop {
+                relop,
+                left,
+                right,
+                id,
+            },
+            Shape::And { left, right } => Expr::And { left, right, id },
+            Shape::Or { left, right } => Expr::Or { left, right, id },
+            Shape::Var(x) => Expr::Var(x, id),
+            Shape::Lam { x, tau, e } => Expr::Lam { x, tau, e, id },
+            Shape::LinLam { x, tau, e } => Expr::LinLam { x, tau, e, id },
+            Shape::App { lam, arg } => Expr::App { lam, arg, id },
+            Shape::Unit => Expr::Unit(id),
+            Shape::Pair { left, right } => Expr::Pair { left, right, id },
+            Shape::Project { e, d } => Expr::Project { e, d, id },
+            Shape::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+            } => Expr::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+                id,
+            },
+            Shape::Inject { e, d, tau } => Expr::Inject { e, d, tau, id },
+            Shape::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+            } => Expr::Case {
+                e,
+                xleft,
+                eleft,
+                xright,
+                eright,
+                id,
+            },
+            Shape::Fix { x, tau, e } => Expr::Fix { x, tau, e, id },
+            Shape::TyLam { a, e } => Expr::TyLam { a, e, id },
+            Shape::TyApp { e, tau } => Expr::TyApp { e, tau, id },
+            Shape::Fold { e, tau } => Expr::Fold { e, tau, id },
+            Shape::Unfold(e) => Expr::Unfold(e, id),
+            Shape::Export {
+                e,
+                tau_adt,
+                tau_mod,
+            } => Expr::Export {
+                e,
+                tau_adt,
+                tau_mod,
+                id,
+            },
+            Shape::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+            } => Expr::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+                id,
+            },
+            Shape::Cast { e, from, to, blame } => Expr::Cast {
+                e,
+                from,
+                to,
+                blame,
+                id,
+            },
+        }
+    }
+}
+
+impl<E, T, V> Shape<E, T, V> {
+    /// Replace the subterms, types and variables, in the order of the fields. The functions
+    /// share the context `cx`.
+    pub fn map<C, E2, T2, V2>(
+        self,
+        cx: &mut C,
+        mut e: impl FnMut(&mut C, E) -> E2,
+        mut t: impl FnMut(&mut C, T) -> T2,
+        mut v: impl FnMut(&mut C, V) -> V2,
+    ) -> Shape<E2, T2, V2> {
+        match self {
+            Shape::Num(n) => Shape::Num(n),
+            Shape::Addop { binop, left, right } => Shape::Addop {
+                binop,
+                left: e(cx, left),
+                right: e(cx, right),
+            },
+            Shape::Mulop { binop, left, right } => Shape::Mulop {
+                binop,
+                left: e(cx, left),
+                right: e(cx, right),
+            },
+            Shape::True => Shape::True,
+            Shape::False => Shape::False,
+            Shape::If { cond, then_, else_ } => Shape::If {
+                cond: e(cx, cond),
+                then_: e(cx, then_),
+                else_: e(cx, else_),
+            },
+            Shape::Relop { relop, left, right } => Shape::Relop {
+                relop,
+                left: e(cx, left),
+                right: e(cx, right),
+            },
+            Shape::And { left, right } => Shape::And {
+                left: e(cx, left),
+                right: e(cx, right),
+            },
+            Shape::Or { left, right } => Shape::Or {
+                left: e(cx, left),
+                right: e(cx, right),
+            },
+            Shape::Var(x) => Shape::Var(v(cx, x)),
+            Shape::Lam { x, tau, e: body } => Shape::Lam {
+                x: v(cx, x),
+                tau: t(cx, tau),
+                e: e(cx, body),
+            },
+            Shape::LinLam { x, tau, e: body } => Shape::LinLam {
+                x: v(cx, x),
+                tau: t(cx, tau),
+                e: e(cx, body),
+            },
+            Shape::App { lam, arg } => Shape::App {
+                lam: e(cx, lam),
+                arg: e(cx, arg),
+            },
+            Shape::Unit => Shape::Unit,
+            Shape::Pair { left, right } => Shape::Pair {
+                left: e(cx, left),
+                right: e(cx, right),
+            },
+            Shape::Project { e: pair, d } => Shape::Project { e: e(cx, pair), d },
+            Shape::LetPair {
+                x,
+                y,
+                e_pair,
+                e_body,
+            } => Shape::LetPair {
+                x: v(cx, x),
+                y: v(cx, y),
+                e_pair: e(cx, e_pair),
+                e_body: e(cx, e_body),
+            },
+            Shape::Inject { e: inner, d, tau } => Shape::Inject {
+                e: e(cx, inner),
+                d,
+                tau: t(cx, tau),
+            },
+            Shape::Case {
+                e: sum,
+                xleft,
+                eleft,
+                xright,
+                eright,
+            } => Shape::Case {
+                e: e(cx, sum),
+                xleft: v(cx, xleft),
+                eleft: e(cx, eleft),
+                xright: v(cx, xright),
+                eright: e(cx, eright),
+            },
+            Shape::Fix { x, tau, e: body } => Shape::Fix {
+                x: v(cx, x),
+                tau: t(cx, tau),
+                e: e(cx, body),
+            },
+            Shape::TyLam { a, e: body } => Shape::TyLam {
+                a: v(cx, a),
+                e: e(cx, body),
+            },
+            Shape::TyApp { e: inner, tau } => Shape::TyApp {
+                e: e(cx, inner),
+                tau: t(cx, tau),
+            },
+            Shape::Fold { e: inner, tau } => Shape::Fold {
+                e: e(cx, inner),
+                tau: t(cx, tau),
+            },
+            Shape::Unfold(inner) => Shape::Unfold(e(cx, inner)),
+            Shape::Export {
+                e: inner,
+                tau_adt,
+                tau_mod,
+            } => Shape::Export {
+                e: e(cx, inner),
+                tau_adt: t(cx, tau_adt),
+                tau_mod: t(cx, tau_mod),
+            },
+            Shape::Import {
+                x,
+                a,
+                e_mod,
+                e_body,
+            } => Shape::Import {
+                x: v(cx, x),
+                a: v(cx, a),
+                e_mod: e(cx, e_mod),
+                e_body: e(cx, e_body),
+            },
+            Shape::Cast {
+                e: inner,
+                from,
+                to,
+                blame,
+            } => Shape::Cast {
+                e: e(cx, inner),
+                from: t(cx, from),
+                to: t(cx, to),
+                blame,
+            },
+        }
+    }
+}
+
+/// The subterms of a node, in the order of `Expr::children`
+fn subterms<E: Copy, T: Clone, V: Clone>(shape: &Shape<E, T, V>) -> Vec<E> {
+    let mut subterms = Vec::new();
+    shape.clone().map(
+        &mut subterms,
+        |subterms, e| subterms.push(e),
+        |_, _| (),
+        |_, _| (),
+    );
+    subterms
+}
+
+/// A representation of terms, as the rules see it
+pub(crate) trait Store {
+    /// A subterm, type or variable being looked at: borrowed from the tree, or an index into
+    /// the arena
+    type E<'a>: Copy;
+    type T<'a>: Copy;
+    type V<'a>: Copy;
+    /// Owned terms, types and variables, which new nodes are built from
+    type Term;
+    type Ty;
+    type Var;
+
+    fn view<'a>(&self, e: Self::E<'a>) -> Shape<Self::E<'a>, Self::T<'a>, Self::V<'a>>;
+    fn ty<'b, 'a: 'b>(&'b self, tau: Self::T<'a>) -> &'b Type;
+    fn name(&self, x: Self::V<'_>) -> Variable;
+
+    fn term(&self, e: Self::E<'_>) -> Self::Term;
+    fn own_type(&self, tau: Self::T<'_>) -> Self::Ty;
+    fn own_var(&self, x: Self::V<'_>) -> Self::Var;
+    fn alloc(&mut self, shape: Shape<Self::Term, Self::Ty, Self::Var>) -> Self::Term;
+    fn alloc_type(&mut self, tau: Type) -> Self::Ty;
+    /// Build the node that `e` becomes when one of its subterms steps
+    fn rebuild(
+        &mut self,
+        _e: Self::E<'_>,
+        shape: Shape<Self::Term, Self::Ty, Self::Var>,
+    ) -> Self::Term {
+        self.alloc(shape)
+    }
+    fn substitute(&mut self, e: Self::Term, x: Self::Var, v: Self::Term) -> Self::Term;
+
+    /// Whether pairs, injections, packages and folds are values whatever they contain
+    fn lazy(&self) -> bool {
+        true
+    }
+
+    /// What a bound variable is replaced with
+    fn pass(&mut self, arg: Self::E<'_>) -> Self::Term {
+        self.term(arg)
+    }
+
+    /// Step a variable, which only thunks can be at runtime
+    fn lookup(&mut self, x: Self::V<'_>) -> Outcome<Self::Term> {
+        unreachable!(
+            "Free variable {:?} should be found in type checking",
+            self.name(x)
+        )
+    }
+
+    /// Step a subterm
+    fn step_in(&mut self, e: Self::E<'_>) -> Outcome<Self::Term>
+    where
+        Self: Sized,
+    {
+        step(self, e)
+    }
+
+    /// Called when the `i`th subterm of a node stepped, from the redex outwards
+    fn descended(&mut self, _i: usize) {}
+}
+
+/// One step of `e`: the subterms a rule needs as values are stepped first, left to right, then
+/// the rule contracts `e`
+pub(crate) fn step<S: Store>(s: &mut S, e: S::E<'_>) -> Outcome<S::Term> {
+    let shape = s.view(e);
+    let lazy = s.lazy();
+    // how many of the leading subterms are evaluated, and whether a rule applies after that
+    let (strict, redex) = match &shape {
+        Shape::Lam { .. }
+        | Shape::LinLam { .. }
+        | Shape::Num(_)
+        | Shape::True
+        | Shape::False
+        | Shape::Unit
+        | Shape::TyLam { .. } => return Outcome::Value,
+        // lazy data constructors don't evaluate their contents
+        Shape::Pair { .. } | Shape::Inject { .. } | Shape::Export { .. } | Shape::Fold { .. }
+            if lazy =>
+        {
+            return Outcome::Value
+        }
+        Shape::Pair { .. } => (2, false),
+        Shape::Inject { .. } | Shape::Export { .. } | Shape::Fold { .. } => (1, false),
+        Shape::Var(x) => return s.lookup(*x),
+        Shape::Fix { .. } => (0, true),
+        Shape::App { .. } if !lazy => (2, true),
+        Shape::Addop { .. }
+        | Shape::Mulop { .. }
+        | Shape::Relop { .. }
+        | Shape::And { .. }
+        | Shape::Or { .. } => (2, true),
+        Shape::If { .. }
+        | Shape::App { .. }
+        | Shape::Project { .. }
+        | Shape::LetPair { .. }
+        | Shape::Case { .. }
+        | Shape::TyApp { .. }
+        | Shape::Unfold(_)
+        | Shape::Import { .. }
+        | Shape::Cast { .. } => (1, true),
+    };
+    let subterms = subterms(&shape);
+    for (i, &child) in subterms.iter().enumerate().take(strict) {
+        // a function cast takes its argument as it is
+        if let (1, Shape::App { lam, .. }) = (i, &shape) {
+            if matches!(s.view(*lam), Shape::Cast { .. }) {
+                break;
+            }
+        }
+        match s.step_in(child) {
+            Outcome::Value => {}
+            Outcome::Step(next) => {
+                s.descended(i);
+                // rebuild the node around the stepped subterm
+                let mut next = Some(next);
+                let mut j = 0;
+                let shape = shape.map(
+                    s,
+                    |s, e| {
+                        j += 1;
+                        match j - 1 == i {
+                            true => next.take().unwrap(),
+                            false => s.term(e),
+                        }
+                    },
+                    |s, tau| s.own_type(tau),
+                    |s, x| s.own_var(x),
+                );
+                return Outcome::Step(s.rebuild(e, shape));
+            }
+            Outcome::Blame(blame) => return Outcome::Blame(blame),
+        }
+    }
+    match redex {
+        true => contract(s, e, shape),
+        false => Outcome::Value,
+    }
+}
+
+/// Wrap `e` in a cast
+fn cast<S: Store>(s: &mut S, e: S::Term, from: Type, to: Type, blame: &Blame) -> S::Term {
+    let from = s.alloc_type(from);
+    let to = s.alloc_type(to);
+    s.alloc(Shape::Cast {
+        e,
+        from,
+        to,
+        blame: blame.clone(),
+    })
+}
+
+/// Apply the rule for a redex whose subterms are values where they need to be
+fn contract<'a, S: Store>(
+    s: &mut S,
+    redex: S::E<'a>,
+    shape: Shape<S::E<'a>, S::T<'a>, S::V<'a>>,
+) -> Outcome<S::Term> {
+    let num = |s: &S, e| match s.view(e) {
+        Shape::Num(n) => n,
+        _ => unreachable!(),
+    };
+    let truth = |s: &S, e| match s.view(e) {
+        Shape::True => true,
+        Shape::False => false,
+        _ => unreachable!(),
+    };
+    let boolean = |s: &mut S, b| s.alloc(if b { Shape::True } else { Shape::False });
+    let next = match shape {
+        // 1. arithmetic
+        Shape::Addop { binop, left, right } => {
+            let (l, r) = (num(s, left), num(s, right));
+            s.alloc(Shape::Num(match binop {
+                AddOp::Add => l + r,
+                AddOp::Sub => l - r,
+            }))
+        }
+        Shape::Mulop { binop, left, right } => {
+            let (l, r) = (num(s, left), num(s, right));
+            s.alloc(Shape::Num(match binop {
+                MulOp::Mul => l * r,
+                MulOp::Div => l / r,
+            }))
+        }
+        // 2. conditionals
+        Shape::If { cond, then_, else_ } => match truth(s, cond) {
+            true => s.term(then_),
+            false => s.term(else_),
+        },
+        Shape::Relop { relop, left, right } => {
+            let (l, r) = (num(s, left), num(s, right));
+            boolean(
+                s,
+                match relop {
+                    RelOp::Lt => l < r,
+                    RelOp::Gt => l > r,
+                    RelOp::Eq => l == r,
+                },
+            )
+        }
+        Shape::And { left, right } => {
+            let b = truth(s, left) && truth(s, right);
+            boolean(s, b)
+        }
+        Shape::Or { left, right } => {
+            let b = truth(s, left) || truth(s, right);
+            boolean(s, b)
+        }
+        // 3. functions
+        Shape::App { lam, arg } => match s.view(lam) {
+            Shape::Lam { x, e, .. } | Shape::LinLam { x, e, .. } => {
+                let (e, x, v) = (s.term(e), s.own_var(x), s.pass(arg));
+                s.substitute(e, x, v)
+            }
+            Shape::Cast {
+                e: f,
+                from,
+                to,
+                blame,
+            } => match (s.ty(from).clone(), s.ty(to).clone()) {
+                (Type::Fn { arg: a1, ret: r1 }, Type::Fn { arg: a2, ret: r2 })
+                | (Type::LinFn { arg: a1, ret: r1 }, Type::LinFn { arg: a2, ret: r2 }) => {
+                    // the caller supplies the argument, so it takes the blame if that fails
+                    let arg = s.term(arg);
+                    let arg = cast(s, arg, *a2, *a1, &blame.flip());
+                    let lam = s.term(f);
+                    let app = s.alloc(Shape::App { lam, arg });
+                    cast(s, app, *r1, *r2, &blame)
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        },
+        // 4. product types
+        Shape::Project { e, d } => match (s.view(e), d) {
+            (Shape::Pair { left, .. }, Direction::Left) => s.term(left),
+            (Shape::Pair { right, .. }, Direction::Right) => s.term(right),
+            _ => unreachable!(),
+        },
+        // `y` first, so that in `let (x, x) = ...` the second binding shadows the first
+        Shape::LetPair {
+            x,
+            y,
+            e_pair,
+            e_body,
+        } => match s.view(e_pair) {
+            Shape::Pair { left, right } => {
+                let (body, y, v) = (s.term(e_body), s.own_var(y), s.pass(right));
+                let body = s.substitute(body, y, v);
+                let (x, v) = (s.own_var(x), s.pass(left));
+                s.substitute(body, x, v)
+            }
+            _ => unreachable!(),
+        },
+        // 5. sum types
+        Shape::Case {
+            e,
+            xleft,
+            eleft,
+            xright,
+            eright,
+        } => match s.view(e) {
+            Shape::Inject { e, d, .. } => {
+                let (x, body) = match d {
+                    Direction::Left => (xleft, eleft),
+                    Direction::Right => (xright, eright),
+                };
+                let (body, x, v) = (s.term(body), s.own_var(x), s.pass(e));
+                s.substitute(body, x, v)
+            }
+            _ => unreachable!(),
+        },
+        // 6. fixpoints
+        Shape::Fix { x, e, .. } => {
+            let (e, x, fix) = (s.term(e), s.own_var(x), s.term(redex));
+            s.substitute(e, x, fix)
+        }
+        // 7. polymorphism
+        Shape::TyApp { e, .. } => match s.view(e) {
+            Shape::TyLam { e, .. } => s.term(e),
+            _ => unreachable!(),
+        },
+        // 8. recursive types
+        Shape::Unfold(e) => match s.view(e) {
+            Shape::Fold { e, .. } => s.term(e),
+            _ => unreachable!(),
+        },
+        // 9. existential types
+        Shape::Import {
+            x, e_mod, e_body, ..
+        } => match s.view(e_mod) {
+            Shape::Export { e, .. } => {
+                let (body, x, v) = (s.term(e_body), s.own_var(x), s.pass(e));
+                s.substitute(body, x, v)
+            }
+            _ => unreachable!(),
+        },
+        // 10. gradual typing
+        Shape::Cast {
+            e, from, to, blame, ..
+        } => {
+            let (from, to) = (s.ty(from).clone(), s.ty(to).clone());
+            return cast_value(s, e, &from, &to, &blame);
+        }
+        _ => unreachable!("a value is not a redex"),
+    };
+    Outcome::Step(next)
+}
+
+/// The type a value tagged with ground type `tag` had before it was injected into `?`
+fn injected<S: Store>(s: &S, e: S::E<'_>, tag: &Type) -> Type {
+    match s.view(e) {
+        Shape::Cast { from, to, .. } if s.ty(to) == tag => s.ty(from).clone(),
+        _ => tag.clone(),
+    }
+}
+
+/// Apply a cast to a value
+pub(crate) fn cast_value<S: Store>(
+    s: &mut S,
+    v: S::E<'_>,
+    from: &Type,
+    to: &Type,
+    blame: &Blame,
+) -> Outcome<S::Term> {
+    // `==` is alpha-equivalence
+    if from == to {
+        return Outcome::Step(s.term(v));
+    }
+    let next = match (from, to, s.view(v)) {
+        // injecting into `?` goes through the ground type
+        (_, Type::Dyn, _) if ground(from) == *from => return Outcome::Value,
+        (_, Type::Dyn, _) => {
+            let v = s.term(v);
+            let inner = cast(s, v, from.clone(), ground(from), blame);
+            cast(s, inner, ground(from), Type::Dyn, blame)
+        }
+        // projecting out of `?` fails unless the value was injected from a type consistent with
+        // `to`, so that a function is blamed where it is cast to the wrong type rather than
+        // where it is called
+        (Type::Dyn, _, Shape::Cast { e, from: tag, .. })
+            if !consistent(&injected(s, e, s.ty(tag)), to) =>
+        {
+            return Outcome::Blame(blame.clone())
+        }
+        (Type::Dyn, _, Shape::Cast { e, from: tag, .. }) if ground(to) == *to => {
+            match s.ty(tag) == to {
+                true => s.term(e),
+                false => return Outcome::Blame(blame.clone()),
+            }
+        }
+        (Type::Dyn, _, _) => {
+            let v = s.term(v);
+            let inner = cast(s, v, Type::Dyn, ground(to), blame);
+            cast(s, inner, ground(to), to.clone(), blame)
+        }
+        // function casts wait for an argument
+        (Type::Fn { .. }, Type::Fn { .. }, _) | (Type::LinFn { .. }, Type::LinFn { .. }, _) => {
+            return Outcome::Value
+        }
+        // other casts are pushed into the value
+        (
+            Type::Product {
+                left: l1,
+                right: r1,
+            },
+            Type::Product {
+                left: l2,
+                right: r2,
+            },
+            Shape::Pair { left, right },
+        ) => {
+            let (left, right) = (s.term(left), s.term(right));
+            let left = cast(s, left, *l1.clone(), *l2.clone(), blame);
+            let right = cast(s, right, *r1.clone(), *r2.clone(), blame);
+            s.alloc(Shape::Pair { left, right })
+        }
+        (
+            Type::Sum {
+                left: l1,
+                right: r1,
+            },
+            Type::Sum {
+                left: l2,
+                right: r2,
+            },
+            Shape::Inject { e, d, .. },
+        ) => {
+            let e = s.term(e);
+            let e = match d {
+                Direction::Left => cast(s, e, *l1.clone(), *l2.clone(), blame),
+                Direction::Right => cast(s, e, *r1.clone(), *r2.clone(), blame),
+            };
+            let tau = s.alloc_type(to.clone());
+            s.alloc(Shape::Inject { e, d, tau })
+        }
+        (Type::Rec { tau: t1, .. }, Type::Rec { tau: t2, .. }, Shape::Fold { e, .. }) => {
+            let e = s.term(e);
+            let e = cast(s, e, t1.open(from), t2.open(to), blame);
+            let tau = s.alloc_type(to.clone());
+            s.alloc(Shape::Fold { e, tau })
+        }
+        (Type::Forall { tau: t1, .. }, Type::Forall { tau: t2, .. }, Shape::TyLam { a, e }) => {
+            let x = Type::Var(s.name(a));
+            let (a, e) = (s.own_var(a), s.term(e));
+            let e = cast(s, e, t1.open(&x), t2.open(&x), blame);
+            s.alloc(Shape::TyLam { a, e })
+        }
+        (
+            Type::Exists { tau: t1, .. },
+            Type::Exists { tau: t2, .. },
+            Shape::Export { e, tau_adt, .. },
+        ) => {
+            let adt = s.ty(tau_adt).clone();
+            let (e, tau_adt) = (s.term(e), s.own_type(tau_adt));
+            let e = cast(s, e, t1.open(&adt), t2.open(&adt), blame);
+            let tau_mod = s.alloc_type(to.clone());
+            s.alloc(Shape::Export {
+                e,
+                tau_adt,
+                tau_mod,
+            })
+        }
+        _ => unreachable!("Cast from {from:?} to {to:?} should be rejected in type checking"),
+    };
+    Outcome::Step(next)
+}
@@ -0,0 +1,758 @@
+//! JSON and S-expression forms of expressions and types, for tools written in other languages.
+//!
+//! Every node has a kind and named fields. In JSON a node is an object with a `"kind"` and one
+//! entry per field; in S-expressions it is a list of the kind followed by the fields in the
+//! order below, or just the kind if it has no fields. Names, directions (`L` or `R`) and
+//! operators are strings, which S-expressions write as symbols unless they need quotes.
+//!
+//! Expressions:
+//!
+//! | kind      | fields                                   |
+//! |-----------|------------------------------------------|
+//! | `num`     | `value`                                  |
+//! | `true`, `false`, `unit` |                            |
+//! | `var`     | `name`                                   |
+//! | `addop`   | `op` (`add`, `sub`), `left`, `right`     |
+//! | `mulop`   | `op` (`mul`, `div`), `left`, `right`     |
+//! | `relop`   | `op` (`lt`, `gt`, `eq`), `left`, `right` |
+//! | `and`, `or`, `pair` | `left`, `right`                |
+//! | `if`      | `cond`, `then`, `else`                   |
+//! | `lam`, `lin_lam`, `fix` | `x`, `tau`, `e`            |
+//! | `app`     | `lam`, `arg`                             |
+//! | `project` | `e`, `d`                                 |
+//! | `let_pair` | `x`, `y`, `e_pair`, `e_body`            |
+//! | `inject`  | `e`, `d`, `tau`                          |
+//! | `case`    | `e`, `xleft`, `eleft`, `xright`, `eright` |
+//! | `ty_lam`  | `a`, `e`                                 |
+//! | `ty_app`, `fold` | `e`, `tau`                        |
+//! | `unfold`  | `e`                                      |
+//! | `export`  | `e`, `tau_adt`, `tau_mod`                |
+//! | `import`  | `x`, `a`, `e_mod`, `e_body`              |
+//! | `cast`    | `e`, `from`, `to`, `blame`               |
+//!
+//! The `blame` of a cast is a `blame` node with fields `label`, `polarity` (`positive`,
+//! `negative`) and, when the label names a place in the source, its byte offsets `start` and
+//! `end`.
+//!
+//! Types:
+//!
+//! | kind      | fields                                   |
+//! |-----------|------------------------------------------|
+//! | `num`, `bool`, `unit`, `dyn` |                       |
+//! | `var`     | `name`                                   |
+//! | `bound`   | `index`                                  |
+//! | `fn`, `lin_fn` | `arg`, `ret`                        |
+//! | `product`, `sum` | `left`, `right`                   |
+//! | `rec`, `forall`, `exists` | `a`, `tau`               |
+//!
+//! Types are locally nameless: a variable bound by a `rec`, `forall` or `exists` is a `bound`
+//! node whose index counts the binders between it and its own, and `a` is only a name to print.
+//! `var` is a free type variable. For example `forall a . a -> b` is
+//! `(forall a (fn (bound 0) (var b)))`.
+//!
+//! Steps:
+//!
+//! `very-verbose` with `--trace json` prints one object per step, with the fields
+//!
+//! | field    | value                                                            |
+//! |----------|------------------------------------------------------------------|
+//! | `step`   | its number, from 1                                               |
+//! | `rule`   | the name of the rule that fired                                  |
+//! | `path`   | the child indices from the root of the term or thunk to the redex |
+//! | `thunk`  | the index `i` of the thunk `ℓi` the redex is in, or `null`       |
+//! | `term`, `redex`, `result` | the expressions before the step, and the redex and what it became |
+//!
+//! The expressions are nodes as above in the `json` output mode, and strings in the others.
+
+use std::{fmt, iter::Peekable, str::Chars};
+
+use serde_json::{json, Map, Value};
+
+use crate::{ast::*, span::Span};
+
+/// A node with its fields, in the order S-expressions list them
+enum Tree {
+    Int(i64),
+    Str(String),
+    Node(&'static str, Vec<(&'static str, Tree)>),
+}
+
+fn leaf(kind: &'static str) -> Tree {
+    Tree::Node(kind, Vec::new())
+}
+
+fn name(x: &Variable) -> Tree {
+    Tree::Str(x.0.clone())
+}
+
+fn direction(d: &Direction) -> Tree {
+    Tree::Str(String::from(match d {
+        Direction::Left => "L",
+        Direction::Right => "R",
+    }))
+}
+
+fn op(op: &str) -> Tree {
+    Tree::Str(String::from(op))
+}
+
+fn blame_tree(blame: &Blame) -> Tree {
+    let polarity = match blame.polarity {
+        Polarity::Positive => "positive",
+        Polarity::Negative => "negative",
+    };
+    let mut fields = vec![
+        ("label", Tree::Str(blame.label.clone())),
+        ("polarity", Tree::Str(String::from(polarity))),
+    ];
+    if let Some(span) = blame.span {
+        fields.push(("start", Tree::Int(span.start as i64)));
+        fields.push(("end", Tree::Int(span.end as i64)));
+    }
+    Tree::Node("blame", fields)
+}
+
+fn type_tree(tau: &Type) -> Tree {
+    let binary = |kind, (l, left): (_, &Type), (r, right): (_, &Type)| {
+        Tree::Node(kind, vec![(l, type_tree(left)), (r, type_tree(right))])
+    };
+    let binder =
+        |kind, a, tau: &Type| Tree::Node(kind, vec![("a", name(a)), ("tau", type_tree(tau))]);
+    match tau {
+        Type::Num => leaf("num"),
+        Type::Bool => leaf("bool"),
+        Type::Unit => leaf("unit"),
+        Type::Dyn => leaf("dyn"),
+        Type::Var(a) => Tree::Node("var", vec![("name", name(a))]),
+        Type::Bound(i) => Tree::Node("bound", vec![("index", Tree::Int(*i as i64))]),
+        Type::Fn { arg, ret } => binary("fn", ("arg", arg), ("ret", ret)),
+        Type::LinFn { arg, ret } => binary("lin_fn", ("arg", arg), ("ret", ret)),
+        Type::Product { left, right } => binary("product", ("left", left), ("right", right)),
+        Type::Sum { left, right } => binary("sum", ("left", left), ("right", right)),
+        Type::Rec { a, tau } => binder("rec", a, tau),
+        Type::Forall { a, tau } => binder("forall", a, tau),
+        Type::Exists { a, tau } => binder("exists", a, tau),
+    }
+}
+
+fn expr_tree(e: &Expr) -> Tree {
+    let binary = |kind, left: &Expr, right: &Expr| {
+        Tree::Node(
+            kind,
+            vec![("left", expr_tree(left)), ("right", expr_tree(right))],
+        )
+    };
+    let operation = |kind, o: &str, left: &Expr, right: &Expr| {
+        let fields = vec![
+            ("op", op(o)),
+            ("left", expr_tree(left)),
+            ("right", expr_tree(right)),
+        ];
+        Tree::Node(kind, fields)
+    };
+    let function = |kind, x, tau: &Type, e: &Expr| {
+        Tree::Node(
+            kind,
+            vec![("x", name(x)), ("tau", type_tree(tau)), ("e", expr_tree(e))],
+        )
+    };
+    let annotated = |kind, e: &Expr, tau: &Type| {
+        Tree::Node(kind, vec![("e", expr_tree(e)), ("tau", type_tree(tau))])
+    };
+    match e {
+        Expr::Num(n, _) => Tree::Node("num", vec![("value", Tree::Int(*n as i64))]),
+        Expr::True(_) => leaf("true"),
+        Expr::False(_) => leaf("false"),
+        Expr::Unit(_) => leaf("unit"),
+        Expr::Var(x, _) => Tree::Node("var", vec![("name", name(x))]),
+        Expr::Addop {
+            binop, left, right, ..
+        } => {
+            let o = match binop {
+                AddOp::Add => "add",
+                AddOp::Sub => "sub",
+            };
+            operation("addop", o, left, right)
+        }
+        Expr::Mulop {
+            binop, left, right, ..
+        } => {
+            let o = match binop {
+                MulOp::Mul => "mul",
+                MulOp::Div => "div",
+            };
+            operation("mulop", o, left, right)
+        }
+        Expr::Relop {
+            relop, left, right, ..
+        } => {
+            let o = match relop {
+                RelOp::Lt => "lt",
+                RelOp::Gt => "gt",
+                RelOp::Eq => "eq",
+            };
+            operation("relop", o, left, right)
+        }
+        Expr::And { left, right, .. } => binary("and", left, right),
+        Expr::Or { left, right, .. } => binary("or", left, right),
+        Expr::Pair { left, right, .. } => binary("pair", left, right),
+        Expr::If {
+            cond, then_, else_, ..
+        } => Tree::Node(
+            "if",
+            vec![
+                ("cond", expr_tree(cond)),
+                ("then", expr_tree(then_)),
+                ("else", expr_tree(else_)),
+            ],
+        ),
+        Expr::Lam { x, tau, e, .. } => function("lam", x, tau, e),
+        Expr::LinLam { x, tau, e, .. } => function("lin_lam", x, tau, e),
+        Expr::Fix { x, tau, e, .. } => function("fix", x, tau, e),
+        Expr::App { lam, arg, .. } => Tree::Node(
+            "app",
+            vec![("lam", expr_tree(lam)), ("arg", expr_tree(arg))],
+        ),
+        Expr::Project { e, d, .. } => {
+            Tree::Node("project", vec![("e", expr_tree(e)), ("d", direction(d))])
+        }
+        Expr::LetPair {
+            x,
+            y,
+            e_pair,
+            e_body,
+            ..
+        } => Tree::Node(
+            "let_pair",
+            vec![
+                ("x", name(x)),
+                ("y", name(y)),
+                ("e_pair", expr_tree(e_pair)),
+                ("e_body", expr_tree(e_body)),
+            ],
+        ),
+        Expr::Inject { e, d, tau, .. } => Tree::Node(
+            "inject",
+            vec![
+                ("e", expr_tree(e)),
+                ("d", direction(d)),
+                ("tau", type_tree(tau)),
+            ],
+        ),
+        Expr::Case {
+            e,
+            xleft,
+            eleft,
+            xright,
+            eright,
+            ..
+        } => Tree::Node(
+            "case",
+            vec![
+                ("e", expr_tree(e)),
+                ("xleft", name(xleft)),
+                ("eleft", expr_tree(eleft)),
+                ("xright", name(xright)),
+                ("eright", expr_tree(eright)),
+            ],
+        ),
+        Expr::TyLam { a, e, .. } => Tree::Node("ty_lam", vec![("a", name(a)), ("e", expr_tree(e))]),
+        Expr::TyApp { e, tau, .. } => annotated("ty_app", e, tau),
+        Expr::Fold { e, tau, .. } => annotated("fold", e, tau),
+        Expr::Unfold(e, _) => Tree::Node("unfold", vec![("e", expr_tree(e))]),
+        Expr::Export {
+            e,
+            tau_adt,
+            tau_mod,
+            ..
+        } => Tree::Node(
+            "export",
+            vec![
+                ("e", expr_tree(e)),
+                ("tau_adt", type_tree(tau_adt)),
+                ("tau_mod", type_tree(tau_mod)),
+            ],
+        ),
+        Expr::Import {
+            x,
+            a,
+            e_mod,
+            e_body,
+            ..
+        } => Tree::Node(
+            "import",
+            vec![
+                ("x", name(x)),
+                ("a", name(a)),
+                ("e_mod", expr_tree(e_mod)),
+                ("e_body", expr_tree(e_body)),
+            ],
+        ),
+        Expr::Cast {
+            e, from, to, blame, ..
+        } => Tree::Node(
+            "cast",
+            vec![
+                ("e", expr_tree(e)),
+                ("from", type_tree(from)),
+                ("to", type_tree(to)),
+                ("blame", blame_tree(blame)),
+            ],
+        ),
+    }
+}
+
+impl Tree {
+    fn json(&self) -> Value {
+        match self {
+            Tree::Int(n) => json!(n),
+            Tree::Str(s) => json!(s),
+            Tree::Node(kind, fields) => {
+                let mut object = Map::new();
+                object.insert(String::from("kind"), json!(kind));
+                for (name, field) in fields {
+                    object.insert(String::from(*name), field.json());
+                }
+                Value::Object(object)
+            }
+        }
+    }
+
+    fn sexp(&self, out: &mut String) {
+        match self {
+            Tree::Int(n) => *out += &n.to_string(),
+            Tree::Str(s) if is_symbol(s) => *out += s,
+            Tree::Str(s) => *out += &Value::from(s.as_str()).to_string(),
+            Tree::Node(kind, fields) if fields.is_empty() => *out += kind,
+            Tree::Node(kind, fields) => {
+                *out += "(";
+                *out += kind;
+                for (_, field) in fields {
+                    out.push(' ');
+                    field.sexp(out);
+                }
+                out.push(')')
+            }
+        }
+    }
+}
+
+/// Whether a string can be written without quotes
+fn is_symbol(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '\'')
+}
+
+pub fn expr_to_json(e: &Expr) -> Value {
+    expr_tree(e).json()
+}
+
+pub fn type_to_json(tau: &Type) -> Value {
+    type_tree(tau).json()
+}
+
+pub fn expr_to_sexp(e: &Expr) -> String {
+    let mut out = String::new();
+    expr_tree(e).sexp(&mut out);
+    out
+}
+
+pub fn type_to_sexp(tau: &Type) -> String {
+    let mut out = String::new();
+    type_tree(tau).sexp(&mut out);
+    out
+}
+
+pub enum Sexp {
+    /// A symbol, number or string
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sexp::Atom(s) if is_symbol(s) || s.parse::<i64>().is_ok() => write!(f, "{s}"),
+            Sexp::Atom(s) => write!(f, "{}", Value::from(s.as_str())),
+            Sexp::List(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    match i {
+                        0 => write!(f, "{item}")?,
+                        _ => write!(f, " {item}")?,
+                    }
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Read one S-expression, with strings in JSON syntax
+pub fn parse_sexp(input: &str) -> Result<Sexp, String> {
+    let mut chars = input.chars().peekable();
+    let sexp = read_sexp(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(sexp),
+        Some(c) => Err(format!("Unexpected {c:?} after the S-expression")),
+    }
+}
+
+fn read_sexp(chars: &mut Peekable<Chars>) -> Result<Sexp, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        None => Err(String::from("Unexpected end of the S-expression")),
+        Some(')') => Err(String::from("Unexpected ')'")),
+        Some('(') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                if chars.next_if_eq(&')').is_some() {
+                    return Ok(Sexp::List(items));
+                }
+                items.push(read_sexp(chars)?);
+            }
+        }
+        Some('"') => {
+            let mut string = String::from('"');
+            chars.next();
+            loop {
+                match chars.next() {
+                    None => return Err(String::from("Unterminated string")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        string.push('\\');
+                        string.extend(chars.next());
+                    }
+                    Some(c) => string.push(c),
+                }
+            }
+            string.push('"');
+            serde_json::from_str(&string)
+                .map(Sexp::Atom)
+                .map_err(|err| format!("Invalid string {string}: {err}"))
+        }
+        Some(_) => {
+            let mut atom = String::new();
+            while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '(' && c != ')') {
+                atom.push(c)
+            }
+            Ok(Sexp::Atom(atom))
+        }
+    }
+}
+
+/// A node to deserialize, in either format
+#[derive(Clone, Copy)]
+enum Input<'a> {
+    Json(&'a Value),
+    Sexp(&'a Sexp),
+}
+
+impl fmt::Display for Input<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Json(value) => write!(f, "{value}"),
+            Input::Sexp(sexp) => write!(f, "{sexp}"),
+        }
+    }
+}
+
+impl<'a> Input<'a> {
+    fn kind(self) -> Result<&'a str, String> {
+        let kind = match self {
+            Input::Json(value) => value.get("kind").and_then(Value::as_str),
+            Input::Sexp(Sexp::Atom(kind)) => Some(kind.as_str()),
+            Input::Sexp(Sexp::List(items)) => match items.first() {
+                Some(Sexp::Atom(kind)) => Some(kind.as_str()),
+                _ => None,
+            },
+        };
+        kind.ok_or_else(|| format!("Expected a node, found {self}"))
+    }
+
+    /// The field called `name`, the `i`th after the kind in S-expressions
+    fn field(self, i: usize, name: &str) -> Result<Input<'a>, String> {
+        let field = match self {
+            Input::Json(value) => value.get(name).map(Input::Json),
+            Input::Sexp(Sexp::List(items)) => items.get(i + 1).map(Input::Sexp),
+            Input::Sexp(Sexp::Atom(_)) => None,
+        };
+        field.ok_or_else(|| format!("Missing field {name} in {self}"))
+    }
+
+    fn string(self) -> Result<&'a str, String> {
+        let string = match self {
+            Input::Json(value) => value.as_str(),
+            Input::Sexp(Sexp::Atom(s)) => Some(s.as_str()),
+            Input::Sexp(Sexp::List(_)) => None,
+        };
+        string.ok_or_else(|| format!("Expected a string, found {self}"))
+    }
+
+    fn int<T: TryFrom<i64>>(self) -> Result<T, String> {
+        let n = match self {
+            Input::Json(value) => value.as_i64(),
+            Input::Sexp(Sexp::Atom(s)) => s.parse().ok(),
+            Input::Sexp(Sexp::List(_)) => None,
+        };
+        n.and_then(|n| T::try_from(n).ok())
+            .ok_or_else(|| format!("Expected a number in range, found {self}"))
+    }
+
+    fn name(self, i: usize, name: &str) -> Result<Variable, String> {
+        self.field(i, name)?.string().map(Variable::from)
+    }
+
+    fn blame(self) -> Result<Blame, String> {
+        if self.kind()? != "blame" {
+            return Err(format!("Expected a blame node, found {self}"));
+        }
+        let polarity = match self.field(1, "polarity")?.string()? {
+            "positive" => Polarity::Positive,
+            "negative" => Polarity::Negative,
+            polarity => return Err(format!("Unknown polarity: {polarity}")),
+        };
+        let span = match self.field(2, "start") {
+            Ok(start) => Some(Span {
+                start: start.int()?,
+                end: self.field(3, "end")?.int()?,
+            }),
+            Err(_) => None,
+        };
+        Ok(Blame {
+            label: String::from(self.field(0, "label")?.string()?),
+            span,
+            polarity,
+        })
+    }
+
+    fn direction(self, i: usize) -> Result<Direction, String> {
+        match self.field(i, "d")?.string()? {
+            "L" => Ok(Direction::Left),
+            "R" => Ok(Direction::Right),
+            d => Err(format!("Unknown direction: {d}")),
+        }
+    }
+
+    fn ty(self) -> Result<Box<Type>, String> {
+        let field = |i, name| self.field(i, name).and_then(Input::ty);
+        let tau = match self.kind()? {
+            "num" => Type::Num,
+            "bool" => Type::Bool,
+            "unit" => Type::Unit,
+            "dyn" => Type::Dyn,
+            "var" => Type::Var(self.name(0, "name")?),
+            "bound" => Type::Bound(self.field(0, "index")?.int()?),
+            "fn" => Type::Fn {
+                arg: field(0, "arg")?,
+                ret: field(1, "ret")?,
+            },
+            "lin_fn" => Type::LinFn {
+                arg: field(0, "arg")?,
+                ret: field(1, "ret")?,
+            },
+            "product" => Type::Product {
+                left: field(0, "left")?,
+                right: field(1, "right")?,
+            },
+            "sum" => Type::Sum {
+                left: field(0, "left")?,
+                right: field(1, "right")?,
+            },
+            "rec" => Type::Rec {
+                a: self.name(0, "a")?,
+                tau: field(1, "tau")?,
+            },
+            "forall" => Type::Forall {
+                a: self.name(0, "a")?,
+                tau: field(1, "tau")?,
+            },
+            "exists" => Type::Exists {
+                a: self.name(0, "a")?,
+                tau: field(1, "tau")?,
+            },
+            kind => return Err(format!("Unknown type kind: {kind}")),
+        };
+        Ok(Box::new(tau))
+    }
+
+    fn expr(self) -> Result<Box<Expr>, String> {
+        let field = |i, name| self.field(i, name).and_then(Input::expr);
+        let ty = |i, name| self.field(i, name).and_then(Input::ty);
+        let op = || self.field(0, "op").and_then(Input::string);
+        let e = match self.kind()? {
+            "num" => Expr::Num(self.field(0, "value")?.int()?, NodeId::default()),
+            "true" => Expr::True(NodeId::default()),
+            "false" => Expr::False(NodeId::default()),
+            "unit" => Expr::Unit(NodeId::default()),
+            "var" => Expr::Var(self.name(0, "name")?, NodeId::default()),
+            "addop" => Expr::Addop {
+                binop: match op()? {
+                    "add" => AddOp::Add,
+                    "sub" => AddOp::Sub,
+                    op => return Err(format!("Unknown addop: {op}")),
+                },
+                left: field(1, "left")?,
+                right: field(2, "right")?,
+                id: NodeId::default(),
+            },
+            "mulop" => Expr::Mulop {
+                binop: match op()? {
+                    "mul" => MulOp::Mul,
+                    "div" => MulOp::Div,
+                    op => return Err(format!("Unknown mulop: {op}")),
+                },
+                left: field(1, "left")?,
+                right: field(2, "right")?,
+                id: NodeId::default(),
+            },
+            "relop" => Expr::Relop {
+                relop: match op()? {
+                    "lt" => RelOp::Lt,
+                    "gt" => RelOp::Gt,
+                    "eq" => RelOp::Eq,
+                    op => return Err(format!("Unknown relop: {op}")),
+                },
+                left: field(1, "left")?,
+                right: field(2, "right")?,
+                id: NodeId::default(),
+            },
+            "and" => Expr::And {
+                left: field(0, "left")?,
+                right: field(1, "right")?,
+                id: NodeId::default(),
+            },
+            "or" => Expr::Or {
+                left: field(0, "left")?,
+                right: field(1, "right")?,
+                id: NodeId::default(),
+            },
+            "pair" => Expr::Pair {
+                left: field(0, "left")?,
+                right: field(1, "right")?,
+                id: NodeId::default(),
+            },
+            "if" => Expr::If {
+                cond: field(0, "cond")?,
+                then_: field(1, "then")?,
+                else_: field(2, "else")?,
+                id: NodeId::default(),
+            },
+            "lam" => Expr::Lam {
+                x: self.name(0, "x")?,
+                tau: ty(1, "tau")?,
+                e: field(2, "e")?,
+                id: NodeId::default(),
+            },
+            "lin_lam" => Expr::LinLam {
+                x: self.name(0, "x")?,
+                tau: ty(1, "tau")?,
+                e: field(2, "e")?,
+                id: NodeId::default(),
+            },
+            "fix" => Expr::Fix {
+                x: self.name(0, "x")?,
+                tau: ty(1, "tau")?,
+                e: field(2, "e")?,
+                id: NodeId::default(),
+            },
+            "app" => Expr::App {
+                lam: field(0, "lam")?,
+                arg: field(1, "arg")?,
+                id: NodeId::default(),
+            },
+            "project" => Expr::Project {
+                e: field(0, "e")?,
+                d: self.direction(1)?,
+                id: NodeId::default(),
+            },
+            "let_pair" => Expr::LetPair {
+                x: self.name(0, "x")?,
+                y: self.name(1, "y")?,
+                e_pair: field(2, "e_pair")?,
+                e_body: field(3, "e_body")?,
+                id: NodeId::default(),
+            },
+            "inject" => Expr::Inject {
+                e: field(0, "e")?,
+                d: self.direction(1)?,
+                tau: ty(2, "tau")?,
+                id: NodeId::default(),
+            },
+            "case" => Expr::Case {
+                e: field(0, "e")?,
+                xleft: self.name(1, "xleft")?,
+                eleft: field(2, "eleft")?,
+                xright: self.name(3, "xright")?,
+                eright: field(4, "eright")?,
+                id: NodeId::default(),
+            },
+            "ty_lam" => Expr::TyLam {
+                a: self.name(0, "a")?,
+                e: field(1, "e")?,
+                id: NodeId::default(),
+            },
+            "ty_app" => Expr::TyApp {
+                e: field(0, "e")?,
+                tau: ty(1, "tau")?,
+                id: NodeId::default(),
+            },
+            "fold" => Expr::Fold {
+                e: field(0, "e")?,
+                tau: ty(1, "tau")?,
+                id: NodeId::default(),
+            },
+            "unfold" => Expr::Unfold(field(0, "e")?, NodeId::default()),
+            "export" => Expr::Export {
+                e: field(0, "e")?,
+                tau_adt: ty(1, "tau_adt")?,
+                tau_mod: ty(2, "tau_mod")?,
+                id: NodeId::default(),
+            },
+            "import" => Expr::Import {
+                x: self.name(0, "x")?,
+                a: self.name(1, "a")?,
+                e_mod: field(2, "e_mod")?,
+                e_body: field(3, "e_body")?,
+                id: NodeId::default(),
+            },
+            "cast" => Expr::Cast {
+                e: field(0, "e")?,
+                from: ty(1, "from")?,
+                to: ty(2, "to")?,
+                blame: self.field(3, "blame")?.blame()?,
+                id: NodeId::default(),
+            },
+            kind => return Err(format!("Unknown expression kind: {kind}")),
+        };
+        Ok(Box::new(e))
+    }
+}
+
+fn from_json(input: &str) -> Result<Value, String> {
+    serde_json::from_str(input).map_err(|err| format!("Invalid JSON: {err}"))
+}
+
+pub fn expr_from_json(input: &str) -> Result<Box<Expr>, String> {
+    Input::Json(&from_json(input)?).expr()
+}
+
+pub fn type_from_json(input: &str) -> Result<Box<Type>, String> {
+    Input::Json(&from_json(input)?).ty()
+}
+
+pub fn expr_from_sexp(input: &str) -> Result<Box<Expr>, String> {
+    Input::Sexp(&parse_sexp(input)?).expr()
+}
+
+pub fn type_from_sexp(input: &str) -> Result<Box<Type>, String> {
+    Input::Sexp(&parse_sexp(input)?).ty()
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, NodeId};
+
+/// Byte range `[start, end)` of a node in the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Source locations of the nodes of a parsed program, by `NodeId`, so they can be found from
+/// clones and substituted copies of the nodes too
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    exprs: HashMap<NodeId, Span>,
+    /// Spans of the types written in a node, in the order of its fields
+    types: HashMap<NodeId, Vec<Span>>,
+    /// Spans of the variables bound in a node, in the order of its fields
+    binders: HashMap<NodeId, Vec<Span>>,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(input: &str) -> Self {
+        SourceMap {
+            exprs: HashMap::new(),
+            types: HashMap::new(),
+            binders: HashMap::new(),
+            line_starts: std::iter::once(0)
+                .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+        }
+    }
+
+    /// Allocate an expression node with a fresh id and remember where it came from
+    pub fn expr(
+        &mut self,
+        start: usize,
+        end: usize,
+        node: impl FnOnce(NodeId) -> Expr,
+    ) -> Box<Expr> {
+        let id = NodeId::fresh();
+        self.exprs.insert(id, Span { start, end });
+        Box::new(node(id))
+    }
+
+    /// Like `expr`, for a node with types written in it
+    pub fn annotated(
+        &mut self,
+        start: usize,
+        end: usize,
+        types: Vec<Span>,
+        node: impl FnOnce(NodeId) -> Expr,
+    ) -> Box<Expr> {
+        let e = self.expr(start, end, node);
+        self.types.insert(e.id(), types);
+        e
+    }
+
+    /// Remember where the variables bound in `e` are written
+    pub fn binding(&mut self, e: Box<Expr>, binders: Vec<Span>) -> Box<Expr> {
+        self.binders.insert(e.id(), binders);
+        e
+    }
+
+    pub fn expr_span(&self, e: &Expr) -> Option<Span> {
+        self.exprs.get(&e.id()).copied()
+    }
+
+    /// The span of the `i`th type written in `e`
+    pub fn type_span(&self, e: &Expr, i: usize) -> Option<Span> {
+        self.types.get(&e.id())?.get(i).copied()
+    }
+
+    /// The span of the `i`th variable bound in `e`
+    pub fn binder_span(&self, e: &Expr, i: usize) -> Option<Span> {
+        self.binders.get(&e.id())?.get(i).copied()
+    }
+
+    /// 1-based line and column of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = line.checked_sub(1).map_or(0, |i| self.line_starts[i]);
+        (line.max(1), offset - start + 1)
+    }
+}
@@ -0,0 +1,203 @@
+//! Pictures of evaluation steps: each step is drawn as the term before it, with the redex
+//! marked, next to the term after it, with the nodes the contraction created marked. The frames
+//! go into one HTML page to step through, or into a directory with an SVG file per step.
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{
+    ast::{Expr, NodeId},
+    evaluate::StepRecord,
+    svggen::{escape, label, svg_header, Layout},
+};
+
+pub use crate::svggen::{Mark, Tree};
+
+/// The two sides of a step
+pub struct Frame {
+    /// e.g. `step 3: E-AppLam at [0, 1]`
+    pub caption: String,
+    pub before: Tree,
+    pub after: Tree,
+}
+
+/// `e` with every node marked `mark`. Types are left out.
+fn term(e: &Expr, mark: Mark) -> Tree {
+    Tree {
+        label: label(e),
+        mark,
+        children: e.children().into_iter().map(|c| term(c, mark)).collect(),
+    }
+}
+
+/// `e` with `at` drawing the subterm at `path`
+fn with_subterm(e: &Expr, path: &[usize], at: &dyn Fn(&Expr) -> Tree) -> Tree {
+    let Some((&i, rest)) = path.split_first() else {
+        return at(e);
+    };
+    let children = e.children().into_iter().enumerate();
+    Tree {
+        label: label(e),
+        mark: Mark::Plain,
+        children: children
+            .map(|(j, c)| match j == i {
+                true => with_subterm(c, rest, at),
+                false => term(c, Mark::Plain),
+            })
+            .collect(),
+    }
+}
+
+fn subterm<'a>(e: &'a Expr, path: &[usize]) -> Option<&'a Expr> {
+    path.iter()
+        .try_fold(e, |e, &i| e.children().get(i).copied())
+}
+
+/// The contractum, with nodes that are copies of subterms of the redex left plain. A copy keeps
+/// the id of the node it copies, and copies of its children, so a body with a variable
+/// substituted in it is new.
+fn contractum(result: &Expr, redex: &HashMap<NodeId, &Expr>) -> Tree {
+    let children: Vec<Tree> = result
+        .children()
+        .into_iter()
+        .map(|c| contractum(c, redex))
+        .collect();
+    let copied = redex.get(&result.id()).is_some_and(|original| {
+        let originals = original.children();
+        label(original) == label(result)
+            && originals.len() == children.len()
+            && originals
+                .iter()
+                .zip(result.children())
+                .zip(&children)
+                .all(|((o, c), tree)| o.id() == c.id() && tree.mark == Mark::Plain)
+    });
+    Tree {
+        label: label(result),
+        mark: match copied {
+            true => Mark::Plain,
+            false => Mark::Created,
+        },
+        children,
+    }
+}
+
+/// The subterms of `e` by id. Nodes without one are never copies.
+fn subterms<'a>(e: &'a Expr, all: &mut HashMap<NodeId, &'a Expr>) {
+    if e.id() != NodeId::default() {
+        all.insert(e.id(), e);
+    }
+    for child in e.children() {
+        subterms(child, all)
+    }
+}
+
+impl Frame {
+    /// Step `n` from `before` to `after`. Under call-by-need these are the thunk the redex is in,
+    /// as it is before and after the step.
+    pub fn new(n: u32, before: &Expr, record: &StepRecord, after: &Expr) -> Frame {
+        let caption = match record.thunk {
+            None => format!("step {n}: {} at {:?}", record.rule, record.path),
+            Some(i) => format!("step {n}: {} in ℓ{i} at {:?}", record.rule, record.path),
+        };
+        let mut redex = HashMap::new();
+        subterms(&record.redex, &mut redex);
+        let after = match subterm(after, &record.path) == Some(&record.result) {
+            true => with_subterm(after, &record.path, &|e| contractum(e, &redex)),
+            false => term(after, Mark::Plain),
+        };
+        Frame {
+            caption,
+            before: with_subterm(before, &record.path, &|e| term(e, Mark::Redex)),
+            after,
+        }
+    }
+
+    /// The frame as a standalone SVG image, the two trees side by side below the caption
+    pub fn svg(&self) -> String {
+        let before = Layout::new(&self.before);
+        let after = Layout::new(&self.after);
+        let offset = before.width + PANEL_GAP;
+        let width = offset + after.width;
+        let height = CAPTION + before.height.max(after.height);
+        let mut svg = svg_header(width, height);
+        svg += &format!(
+            "<text x=\"0\" y=\"16\" font-weight=\"bold\">{}</text>\n",
+            escape(&self.caption)
+        );
+        svg += &format!(
+            "<line x1=\"{0:.1}\" y1=\"{CAPTION}\" x2=\"{0:.1}\" y2=\"{height:.0}\" stroke=\"#ccc\"/>\n",
+            before.width + PANEL_GAP / 2.0
+        );
+        before.draw(0.0, CAPTION, &mut svg);
+        after.draw(offset, CAPTION, &mut svg);
+        svg + "</svg>"
+    }
+}
+
+const PANEL_GAP: f64 = 40.0;
+const CAPTION: f64 = 28.0;
+
+/// One page showing a frame at a time, with buttons, a slider and the arrow keys to move
+pub fn html(frames: &[Frame]) -> String {
+    let mut page = String::from(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Evaluation steps</title>
+<style>
+body { font-family: sans-serif; }
+nav { position: sticky; top: 0; background: #fff; padding: 4px 0; }
+.frame { display: none; overflow: auto; }
+.frame.current { display: block; }
+</style>
+</head>
+<body>
+",
+    );
+    page += &format!(
+        "<nav><button id=\"prev\">&#9664;</button> <input id=\"slider\" type=\"range\" min=\"1\" max=\"{0}\" value=\"1\"> <button id=\"next\">&#9654;</button> <span id=\"position\">{1} / {0}</span></nav>\n",
+        frames.len(),
+        frames.len().min(1)
+    );
+    if frames.is_empty() {
+        page += "<p>The program is a value: there are no steps.</p>\n";
+    }
+    for (i, frame) in frames.iter().enumerate() {
+        let current = if i == 0 { " current" } else { "" };
+        page += &format!("<div class=\"frame{current}\">\n{}\n</div>\n", frame.svg());
+    }
+    page + "<script>
+const frames = document.querySelectorAll('.frame');
+const slider = document.getElementById('slider');
+let current = 0;
+function show(i) {
+  if (i < 0 || i >= frames.length) return;
+  frames[current].classList.remove('current');
+  current = i;
+  frames[current].classList.add('current');
+  slider.value = current + 1;
+  document.getElementById('position').textContent = (current + 1) + ' / ' + frames.length;
+}
+document.getElementById('prev').onclick = () => show(current - 1);
+document.getElementById('next').onclick = () => show(current + 1);
+slider.oninput = () => show(slider.value - 1);
+document.onkeydown = (event) => {
+  if (event.key === 'ArrowLeft') show(current - 1);
+  if (event.key === 'ArrowRight') show(current + 1);
+};
+</script>
+</body>
+</html>"
+}
+
+/// Write each frame to `dir` as `stepN.svg`, numbered from 1 with the same number of digits
+pub fn write_frames(frames: &[Frame], dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let digits = frames.len().to_string().len();
+    for (i, frame) in frames.iter().enumerate() {
+        let name = format!("step{:0digits$}.svg", i + 1);
+        fs::write(dir.join(name), frame.svg())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,344 @@
+//! Pictures of trees as SVG, laid out without Graphviz. The layout is the tidy tree algorithm of
+//! Reingold and Tilford: the subtrees of a node are pushed together as far as their contours
+//! allow, and the node is centered over its first and last child.
+
+use crate::ast::{Direction, Expr, Type};
+
+/// What a node is drawn as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Plain,
+    /// A type annotation
+    Type,
+    /// The redex and its subterms, before a step
+    Redex,
+    /// A node of the contractum that isn't a copy of a subterm of the redex
+    Created,
+}
+
+/// A tree as it is drawn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    pub label: String,
+    pub mark: Mark,
+    pub children: Vec<Tree>,
+}
+
+const CHAR_WIDTH: f64 = 7.5;
+const NODE_HEIGHT: f64 = 20.0;
+const LEVEL: f64 = 44.0;
+const SIBLING_GAP: f64 = 8.0;
+const MARGIN: f64 = 8.0;
+
+/// The label of the node of an expression. Binders show the variables they bind.
+pub(crate) fn label(e: &Expr) -> String {
+    let direction = |d: &Direction| match d {
+        Direction::Left => "L",
+        Direction::Right => "R",
+    };
+    match e {
+        Expr::Num(..) | Expr::True(_) | Expr::False(_) | Expr::Unit(_) | Expr::Var(..) => {
+            e.to_string()
+        }
+        Expr::Addop { binop, .. } => binop.to_string(),
+        Expr::Mulop { binop, .. } => binop.to_string(),
+        Expr::Relop { relop, .. } => relop.to_string(),
+        Expr::If { .. } => String::from("if"),
+        Expr::And { .. } => String::from("&&"),
+        Expr::Or { .. } => String::from("||"),
+        Expr::Pair { .. } => String::from("pair"),
+        Expr::App { .. } => String::from("app"),
+        Expr::Lam { x, .. } => format!("λ {}", x.0),
+        Expr::LinLam { x, .. } => format!("λ⊸ {}", x.0),
+        Expr::Fix { x, .. } => format!("fix {}", x.0),
+        Expr::Project { d, .. } => format!(".{}", direction(d)),
+        Expr::LetPair { x, y, .. } => format!("let ({}, {})", x.0, y.0),
+        Expr::Inject { d, .. } => format!("inj {}", direction(d)),
+        Expr::Case { xleft, xright, .. } => format!("case {} | {}", xleft.0, xright.0),
+        Expr::TyApp { .. } => String::from("tyapp"),
+        Expr::TyLam { a, .. } => format!("Λ {}", a.0),
+        Expr::Fold { .. } => String::from("fold"),
+        Expr::Unfold(..) => String::from("unfold"),
+        Expr::Import { x, a, .. } => format!("import ({}, {})", x.0, a.0),
+        Expr::Export { .. } => String::from("export"),
+        Expr::Cast { .. } => String::from("cast"),
+    }
+}
+
+/// An expression with its type annotations, which are drawn in blue
+pub fn expr_tree(e: &Expr) -> Tree {
+    let types: Vec<&Type> = match e {
+        Expr::Lam { tau, .. } | Expr::LinLam { tau, .. } | Expr::Fix { tau, .. } => vec![tau],
+        Expr::Inject { tau, .. } | Expr::TyApp { tau, .. } | Expr::Fold { tau, .. } => vec![tau],
+        Expr::Export {
+            tau_adt, tau_mod, ..
+        } => vec![tau_adt, tau_mod],
+        Expr::Cast { from, to, .. } => vec![from, to],
+        _ => vec![],
+    };
+    let mut children: Vec<Tree> = e.children().into_iter().map(expr_tree).collect();
+    // annotations come before the body of a binder, and after the term otherwise
+    let types = types.into_iter().map(type_tree);
+    match e {
+        Expr::Lam { .. } | Expr::LinLam { .. } | Expr::Fix { .. } => {
+            children.splice(0..0, types);
+        }
+        _ => children.extend(types),
+    }
+    Tree {
+        label: label(e),
+        mark: Mark::Plain,
+        children,
+    }
+}
+
+/// A type, with bound variables labelled by the names of their binders
+pub fn type_tree(tau: &Type) -> Tree {
+    type_node(tau, &mut Vec::new())
+}
+
+/// `names` holds the names of the enclosing binders, innermost last
+fn type_node(tau: &Type, names: &mut Vec<String>) -> Tree {
+    let node = |label: String, children| Tree {
+        label,
+        mark: Mark::Type,
+        children,
+    };
+    match tau {
+        Type::Bound(i) => node(
+            match names.len().checked_sub(*i as usize + 1) {
+                Some(level) => names[level].clone(),
+                None => tau.to_string(),
+            },
+            vec![],
+        ),
+        Type::Num | Type::Bool | Type::Unit | Type::Var(_) | Type::Dyn => {
+            node(tau.to_string(), vec![])
+        }
+        Type::Product { left, right }
+        | Type::Sum { left, right }
+        | Type::Fn {
+            arg: left,
+            ret: right,
+        }
+        | Type::LinFn {
+            arg: left,
+            ret: right,
+        } => {
+            let op = match tau {
+                Type::Product { .. } => "*",
+                Type::Sum { .. } => "+",
+                Type::Fn { .. } => "→",
+                _ => "⊸",
+            };
+            let children = vec![type_node(left, names), type_node(right, names)];
+            node(String::from(op), children)
+        }
+        Type::Rec { a, tau: body }
+        | Type::Forall { a, tau: body }
+        | Type::Exists { a, tau: body } => {
+            let binder = match tau {
+                Type::Rec { .. } => "μ",
+                Type::Forall { .. } => "∀",
+                _ => "∃",
+            };
+            names.push(a.0.clone());
+            let body = type_node(body, names);
+            names.pop();
+            node(format!("{binder} {}", a.0), vec![body])
+        }
+    }
+}
+
+fn node_width(label: &str) -> f64 {
+    label.chars().count() as f64 * CHAR_WIDTH + 12.0
+}
+
+/// A node of the layout, numbered in preorder
+struct Node<'a> {
+    tree: &'a Tree,
+    x: f64,
+    depth: usize,
+    children: Vec<usize>,
+}
+
+/// A tree with the positions of its nodes
+pub struct Layout<'a> {
+    nodes: Vec<Node<'a>>,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(tree: &'a Tree) -> Layout<'a> {
+        // number the nodes in preorder, without recursion since the trees can be deep
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut stack: Vec<(&Tree, Option<usize>, usize)> = vec![(tree, None, 0)];
+        while let Some((tree, parent, depth)) = stack.pop() {
+            let id = nodes.len();
+            nodes.push(Node {
+                tree,
+                x: 0.0,
+                depth,
+                children: Vec::new(),
+            });
+            if let Some(parent) = parent {
+                nodes[parent].children.push(id);
+            }
+            stack.extend(tree.children.iter().rev().map(|c| (c, Some(id), depth + 1)));
+        }
+        // children before parents: place the subtrees of each node next to each other,
+        // relative to the node. A contour is the leftmost and rightmost extent of a subtree at
+        // each level, relative to its root.
+        let mut contours: Vec<Vec<(f64, f64)>> = vec![Vec::new(); nodes.len()];
+        let mut offsets = vec![0.0; nodes.len()];
+        for id in (0..nodes.len()).rev() {
+            let mut merged: Vec<(f64, f64)> = Vec::new();
+            let mut xs = Vec::new();
+            for &child in &nodes[id].children {
+                let contour = std::mem::take(&mut contours[child]);
+                let x = merged
+                    .iter()
+                    .zip(&contour)
+                    .map(|(&(_, right), &(left, _))| right - left + SIBLING_GAP)
+                    .reduce(f64::max)
+                    .unwrap_or(0.0);
+                for (level, &(left, right)) in contour.iter().enumerate() {
+                    match merged.get_mut(level) {
+                        Some(extent) => extent.1 = x + right,
+                        None => merged.push((x + left, x + right)),
+                    }
+                }
+                xs.push(x);
+            }
+            let middle = match (xs.first(), xs.last()) {
+                (Some(first), Some(last)) => (first + last) / 2.0,
+                _ => 0.0,
+            };
+            for (&child, x) in nodes[id].children.iter().zip(xs) {
+                offsets[child] = x - middle;
+            }
+            let half = node_width(&nodes[id].tree.label) / 2.0;
+            contours[id] = std::iter::once((-half, half))
+                .chain(merged.into_iter().map(|(l, r)| (l - middle, r - middle)))
+                .collect();
+        }
+        // parents before children: from offsets to positions
+        let left = contours[0]
+            .iter()
+            .map(|&(l, _)| l)
+            .reduce(f64::min)
+            .unwrap();
+        let right = contours[0]
+            .iter()
+            .map(|&(_, r)| r)
+            .reduce(f64::max)
+            .unwrap();
+        nodes[0].x = -left;
+        for id in 0..nodes.len() {
+            for i in 0..nodes[id].children.len() {
+                let child = nodes[id].children[i];
+                nodes[child].x = nodes[id].x + offsets[child];
+            }
+        }
+        let depth = nodes.iter().map(|node| node.depth).max().unwrap();
+        Layout {
+            nodes,
+            width: right - left,
+            height: depth as f64 * LEVEL + NODE_HEIGHT,
+        }
+    }
+
+    /// Draw the tree with its top left corner at `(dx, dy)`. Each subtree is a group, in which
+    /// the children of a node are in a group of class `children`.
+    pub fn draw(&self, dx: f64, dy: f64, svg: &mut String) {
+        self.draw_node(0, None, dx, dy, svg)
+    }
+
+    fn draw_node(&self, id: usize, parent: Option<usize>, dx: f64, dy: f64, svg: &mut String) {
+        let node = &self.nodes[id];
+        let (x, y) = (dx + node.x, dy + node.depth as f64 * LEVEL);
+        *svg += "<g class=\"node\">\n";
+        if let Some(parent) = parent {
+            let parent = &self.nodes[parent];
+            *svg += &format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{x:.1}\" y2=\"{y:.1}\" stroke=\"#888\"/>\n",
+                dx + parent.x,
+                y - LEVEL + NODE_HEIGHT
+            );
+        }
+        let (fill, stroke) = match node.tree.mark {
+            Mark::Plain => ("#fff", "#888"),
+            Mark::Type => ("#eef", "#00c"),
+            Mark::Redex => ("#fdd", "#c00"),
+            Mark::Created => ("#dfd", "#080"),
+        };
+        let width = node_width(&node.tree.label);
+        *svg += &format!(
+            "<rect x=\"{:.1}\" y=\"{y:.1}\" width=\"{width:.1}\" height=\"{NODE_HEIGHT}\" rx=\"4\" fill=\"{fill}\" stroke=\"{stroke}\"/>\n",
+            x - width / 2.0
+        );
+        *svg += &format!(
+            "<text x=\"{x:.1}\" y=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+            y + NODE_HEIGHT / 2.0,
+            escape(&node.tree.label)
+        );
+        if !node.children.is_empty() {
+            *svg += "<g class=\"children\">\n";
+            for &child in &node.children {
+                self.draw_node(child, Some(id), dx, dy, svg);
+            }
+            *svg += "</g>\n";
+        }
+        *svg += "</g>\n";
+    }
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The opening tag of an SVG image of `width` by `height`
+pub(crate) fn svg_header(width: f64, height: f64) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.0}\" height=\"{height:.0}\" font-family=\"monospace\" font-size=\"12\">\n"
+    )
+}
+
+/// A tree as an SVG image
+pub fn to_svg(tree: &Tree) -> String {
+    let layout = Layout::new(tree);
+    let mut svg = svg_header(layout.width + 2.0 * MARGIN, layout.height + 2.0 * MARGIN);
+    layout.draw(MARGIN, MARGIN, &mut svg);
+    svg + "</svg>"
+}
+
+/// A web page with a tree as an SVG image, in which clicking a node hides or shows its subtrees
+pub fn to_html(tree: &Tree) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Tree</title>
+<style>
+.node > rect, .node > text {{ cursor: pointer; }}
+.collapsed > .children {{ display: none; }}
+.collapsed > rect {{ stroke-dasharray: 3 2; stroke-width: 2; }}
+</style>
+</head>
+<body>
+{}
+<script>
+for (const label of document.querySelectorAll('.node > rect, .node > text')) {{
+  label.addEventListener('click', () => label.parentNode.classList.toggle('collapsed'));
+}}
+</script>
+</body>
+</html>",
+        to_svg(tree)
+    )
+}
@@ -1,9 +1,400 @@
-use std::collections::HashMap;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
-use crate::{ast::*, ast_util::Symbol, do_, monad::Monad};
+use clap::ValueEnum;
+
+use crate::{
+    ast::*,
+    ast_util::Symbol,
+    do_,
+    gradual::{consistent, consistent_with, meet},
+    intern::{Interner, Sym},
+    monad::Monad,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Substructural {
+    /// Linear variables must be used exactly once
+    Linear,
+
+    /// Linear variables may be used at most once
+    Affine,
+}
 
 pub fn type_check(ast: &Expr) -> Result<Type, String> {
-    type_check_expr(ast, HashMap::new())
+    type_check_expr(ast, Ctx::new(None))
+}
+
+/// Type check and additionally track how often variables are used. Arguments of `-o` functions
+/// and variables whose type contains a `-o` function are linear resources: they can't be
+/// duplicated, dropped (in linear mode), or captured by code that may run more than once.
+pub fn type_check_substructural(ast: &Expr, mode: Substructural) -> Result<Type, String> {
+    type_check_expr(ast, Ctx::new(Some(mode)))
+}
+
+/// The types of the subexpressions of a program, recorded while type checking it
+#[derive(Debug, Default)]
+pub struct Typing {
+    types: HashMap<NodeId, Type>,
+    /// The innermost expression that failed to type check
+    failed: Option<NodeId>,
+}
+
+impl Typing {
+    fn record(&mut self, e: &Expr, result: &Result<Type, String>) {
+        match result {
+            Ok(tau) => {
+                self.types.insert(e.id(), tau.clone());
+            }
+            Err(_) => {
+                self.failed.get_or_insert(e.id());
+            }
+        }
+    }
+
+    pub fn type_of(&self, e: &Expr) -> Option<&Type> {
+        self.types.get(&e.id())
+    }
+
+    /// Whether the error comes from checking `e` itself rather than a subexpression
+    pub fn failed_at(&self, e: &Expr) -> bool {
+        self.failed == Some(e.id())
+    }
+}
+
+/// Type check and record the type of every subexpression checked. Checking stops at the first
+/// error, so subexpressions after it have no type.
+pub fn type_check_recording(ast: &Expr) -> (Result<Type, String>, Typing) {
+    let ctx = Ctx::new(None);
+    ctx.env.borrow_mut().typing = Some(Typing::default());
+    let result = type_check_expr(ast, ctx.clone());
+    let typing = ctx.env.borrow_mut().typing.take().unwrap_or_default();
+    (result, typing)
+}
+
+/// Something a typing context assumes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Assumption {
+    Var(Variable, Type),
+    TyVar(Variable),
+}
+
+/// A typing derivation: `rule` concludes `context ⊢ e : tau` from the derivations of
+/// `premises`, one for each subexpression in the order they are checked
+#[derive(Debug, Clone)]
+pub struct Derivation {
+    pub rule: &'static str,
+    /// Outermost first
+    pub context: Vec<Assumption>,
+    pub e: Expr,
+    pub tau: Type,
+    pub premises: Vec<Derivation>,
+}
+
+/// Type check and build the derivation of the program's type
+pub fn type_check_derivation(
+    ast: &Expr,
+    mode: Option<Substructural>,
+) -> Result<Derivation, String> {
+    let ctx = Ctx::new(mode);
+    ctx.env.borrow_mut().derivations = Some(vec![Vec::new()]);
+    type_check_expr(ast, ctx.clone())?;
+    let derivations = ctx.env.borrow_mut().derivations.take();
+    Ok(derivations.unwrap().remove(0).remove(0))
+}
+
+/// The typing rule that concludes the type of `e`
+fn rule(e: &Expr) -> &'static str {
+    match e {
+        Expr::Num(..) => "T-Num",
+        Expr::Addop { .. } => "T-Add",
+        Expr::Mulop { .. } => "T-Mul",
+        Expr::True(_) => "T-True",
+        Expr::False(_) => "T-False",
+        Expr::Relop { .. } => "T-Rel",
+        Expr::If { .. } => "T-If",
+        Expr::And { .. } => "T-And",
+        Expr::Or { .. } => "T-Or",
+        Expr::Var(..) => "T-Var",
+        Expr::Lam { .. } => "T-Lam",
+        Expr::LinLam { .. } => "T-LinLam",
+        Expr::App { .. } => "T-App",
+        Expr::Unit(_) => "T-Unit",
+        Expr::Pair { .. } => "T-Pair",
+        Expr::LetPair { .. } => "T-LetPair",
+        Expr::Project { d, .. } => match d {
+            Direction::Left => "T-ProjL",
+            Direction::Right => "T-ProjR",
+        },
+        Expr::Inject { d, .. } => match d {
+            Direction::Left => "T-InjL",
+            Direction::Right => "T-InjR",
+        },
+        Expr::Case { .. } => "T-Case",
+        Expr::Fix { .. } => "T-Fix",
+        Expr::TyLam { .. } => "T-TyLam",
+        Expr::TyApp { .. } => "T-TyApp",
+        Expr::Fold { .. } => "T-Fold",
+        Expr::Unfold(..) => "T-Unfold",
+        Expr::Export { .. } => "T-Export",
+        Expr::Import { .. } => "T-Import",
+        Expr::Cast { .. } => "T-Cast",
+    }
+}
+
+/// Whether values of this type have to be tracked in substructural mode
+fn is_linear(tau: &Type) -> bool {
+    match tau {
+        Type::LinFn { .. } => true,
+        Type::Product { left, right } | Type::Sum { left, right } => {
+            is_linear(left) || is_linear(right)
+        }
+        Type::Rec { tau, .. } | Type::Exists { tau, .. } => is_linear(tau),
+        _ => false,
+    }
+}
+
+/// A variable in scope. Linear variables carry a use counter, so uses in sibling subterms add
+/// up.
+struct Binding {
+    tau: Type,
+    uses: Option<Rc<Cell<u32>>>,
+}
+
+/// The variables in scope, shared by every clone of a `Ctx`. Binders are strictly nested, so
+/// entering one pushes onto the stack of its variable and leaving it pops again: copying the
+/// context is free and lookups don't depend on how many variables are in scope.
+#[derive(Default)]
+struct Env {
+    names: Interner,
+    /// Bindings of each interned variable, innermost last
+    vars: Vec<Vec<Binding>>,
+    /// How many enclosing `tyfun`s and `import`s bind each interned type variable
+    tyvars: Vec<u32>,
+    /// Use counters of the linear variables in scope, outermost first
+    linear: Vec<(Variable, Rc<Cell<u32>>)>,
+    /// Where the types of subexpressions are recorded, if they are
+    typing: Option<Typing>,
+    /// If derivations are built, those of the premises found so far of each expression being
+    /// checked, outermost first
+    derivations: Option<Vec<Vec<Derivation>>>,
+    /// The assumptions in scope, outermost first, kept while building derivations
+    scope: Vec<Assumption>,
+}
+
+impl Env {
+    fn intern(&mut self, x: &Variable) -> Sym {
+        let sym = self.names.intern(x);
+        if sym.index() == self.vars.len() {
+            self.vars.push(Vec::new());
+            self.tyvars.push(0);
+        }
+        sym
+    }
+
+    /// Enter the scope of an assumption, if derivations need it
+    fn assume(&mut self, assumption: impl FnOnce() -> Assumption) {
+        if self.derivations.is_some() {
+            self.scope.push(assumption())
+        }
+    }
+
+    fn discharge(&mut self) {
+        if self.derivations.is_some() {
+            self.scope.pop();
+        }
+    }
+
+    fn tyvar_bound(&self, a: &Variable) -> bool {
+        self.names
+            .get(a)
+            .is_some_and(|sym| self.tyvars[sym.index()] > 0)
+    }
+}
+
+#[derive(Clone)]
+struct Ctx {
+    env: Rc<RefCell<Env>>,
+    mode: Option<Substructural>,
+}
+
+impl Ctx {
+    fn new(mode: Option<Substructural>) -> Self {
+        Ctx {
+            env: Rc::new(RefCell::new(Env::default())),
+            mode,
+        }
+    }
+
+    /// Type check with the type variable `a` in scope
+    fn with_tyvar(
+        &self,
+        a: &Variable,
+        check: impl FnOnce() -> Result<Type, String>,
+    ) -> Result<Type, String> {
+        let sym = {
+            let mut env = self.env.borrow_mut();
+            env.assume(|| Assumption::TyVar(a.clone()));
+            let sym = env.intern(a);
+            env.tyvars[sym.index()] += 1;
+            sym
+        };
+        let result = check();
+        let mut env = self.env.borrow_mut();
+        env.tyvars[sym.index()] -= 1;
+        env.discharge();
+        result
+    }
+
+    /// `consistent`, except that in substructural mode a linear value can't be cast to or from
+    /// `?`, which would stop tracking its uses
+    fn consistent(&self, t1: &Type, t2: &Type) -> bool {
+        match self.mode {
+            Some(_) => consistent_with(t1, t2, &|tau| !is_linear(tau)),
+            None => consistent(t1, t2),
+        }
+    }
+
+    /// Check that a type annotation only mentions type variables in scope
+    fn well_formed(&self, tau: &Type) -> Result<(), String> {
+        let env = self.env.borrow();
+        let mut unbound: Vec<_> = tau
+            .free_vars()
+            .into_iter()
+            .filter(|a| !env.tyvar_bound(a))
+            .map(|a| a.0)
+            .collect();
+        unbound.sort();
+        match unbound.first() {
+            None => Ok(()),
+            Some(a) => Err(format!(
+                "Unbound type variable `{}` in annotation {}",
+                a, tau
+            )),
+        }
+    }
+
+    fn get(&self, x: &Variable) -> Option<Type> {
+        let env = self.env.borrow();
+        let binding = env.vars[env.names.get(x)?.index()].last()?;
+        if let Some(uses) = &binding.uses {
+            uses.set(uses.get() + 1);
+        }
+        Some(binding.tau.clone())
+    }
+
+    /// Type check `e` with `x : tau` in scope, then check how often a linear `x` was used
+    fn with_var(&self, x: &Variable, tau: Type, linear: bool, e: &Expr) -> Result<Type, String> {
+        self.binding(x, tau, linear, || type_check_expr(e, self.clone()))
+    }
+
+    /// `with_var` for whatever `check` checks
+    fn binding(
+        &self,
+        x: &Variable,
+        tau: Type,
+        linear: bool,
+        check: impl FnOnce() -> Result<Type, String>,
+    ) -> Result<Type, String> {
+        let uses =
+            (self.mode.is_some() && (linear || is_linear(&tau))).then(|| Rc::new(Cell::new(0)));
+        let sym = {
+            let mut env = self.env.borrow_mut();
+            env.assume(|| Assumption::Var(x.clone(), tau.clone()));
+            let sym = env.intern(x);
+            env.vars[sym.index()].push(Binding {
+                tau,
+                uses: uses.clone(),
+            });
+            if let Some(uses) = &uses {
+                env.linear.push((x.clone(), uses.clone()));
+            }
+            sym
+        };
+        let result = check();
+        {
+            let mut env = self.env.borrow_mut();
+            env.discharge();
+            env.vars[sym.index()].pop();
+            if uses.is_some() {
+                env.linear.pop();
+            }
+        }
+        let tau_e = result?;
+        match uses.map(|uses| uses.get()) {
+            Some(0) if self.mode == Some(Substructural::Linear) => {
+                Err(format!("Linear variable `{}` is never used", x.0))
+            }
+            Some(n) if n > 1 => Err(format!("Linear variable `{}` is used {} times", x.0, n)),
+            _ => Ok(tau_e),
+        }
+    }
+
+    /// Use counts of the linear variables in scope, outermost first
+    fn usage(&self) -> Vec<u32> {
+        let env = self.env.borrow();
+        env.linear.iter().map(|(_, uses)| uses.get()).collect()
+    }
+
+    fn restore(&self, usage: &[u32]) {
+        let env = self.env.borrow();
+        for ((_, uses), n) in env.linear.iter().zip(usage) {
+            uses.set(*n);
+        }
+    }
+
+    fn linear_name(&self, i: usize) -> String {
+        self.env.borrow().linear[i].0 .0.clone()
+    }
+
+    /// Type check code that may run any number of times: it must not use linear variables
+    fn unrestricted(
+        &self,
+        what: impl FnOnce() -> String,
+        check: impl FnOnce() -> Result<Type, String>,
+    ) -> Result<Type, String> {
+        let before = self.usage();
+        let tau = check()?;
+        match self.usage().iter().zip(&before).position(|(n, m)| n != m) {
+            Some(i) => Err(format!(
+                "Linear variable `{}` is captured by {}",
+                self.linear_name(i),
+                what()
+            )),
+            None => Ok(tau),
+        }
+    }
+
+    /// Type check two branches of which exactly one runs
+    fn branches(
+        &self,
+        what: &str,
+        left: impl FnOnce() -> Result<Type, String>,
+        right: impl FnOnce() -> Result<Type, String>,
+    ) -> Result<(Type, Type), String> {
+        let before = self.usage();
+        let tau_left = left()?;
+        let after_left = self.usage();
+        self.restore(&before);
+        let tau_right = right()?;
+        let mut after_right = self.usage();
+        for (i, (n, m)) in after_left.iter().zip(after_right.iter_mut()).enumerate() {
+            if n != m {
+                if self.mode == Some(Substructural::Linear) {
+                    return Err(format!(
+                        "Linear variable `{}` is used {} time(s) in the first branch of {} but {} time(s) in the second",
+                        self.linear_name(i), n, what, m
+                    ));
+                }
+                *m = *n.max(m);
+            }
+        }
+        self.restore(&after_right);
+        Ok((tau_left, tau_right))
+    }
 }
 
 macro_rules! type_mismatch {
@@ -19,19 +410,44 @@ and
     };
 }
 
-fn type_check_expr(ast: &Expr, ctx: HashMap<Variable, Type>) -> Result<Type, String> {
+fn type_check_expr(ast: &Expr, ctx: Ctx) -> Result<Type, String> {
+    if let Some(derivations) = &mut ctx.env.borrow_mut().derivations {
+        derivations.push(Vec::new())
+    }
+    let result = type_check_node(ast, ctx.clone());
+    let env = &mut *ctx.env.borrow_mut();
+    if let Some(typing) = &mut env.typing {
+        typing.record(ast, &result)
+    }
+    if let (Some(derivations), Ok(tau)) = (&mut env.derivations, &result) {
+        let premises = derivations.pop().unwrap();
+        derivations.last_mut().unwrap().push(Derivation {
+            rule: rule(ast),
+            context: env.scope.clone(),
+            e: ast.clone(),
+            tau: tau.clone(),
+            premises,
+        })
+    }
+    result
+}
+
+fn type_check_node(ast: &Expr, ctx: Ctx) -> Result<Type, String> {
     match ast {
         // 1. arithmetic
-        Expr::Num(_) => Ok(Type::Num),
-        Expr::Addop { binop, left, right } => {
+        Expr::Num(..) => Ok(Type::Num),
+        Expr::Addop {
+            binop, left, right, ..
+        } => {
             let tau_left = type_check_expr(left, ctx.clone())?;
             let tau_right = type_check_expr(right, ctx.clone())?;
-            match (tau_left.clone(), tau_right.clone()) {
-                (Type::Num, Type::Num) => Ok(Type::Num),
-                _ => type_mismatch!(tau_left, tau_right, binop),
+            if ctx.consistent(&tau_left, &Type::Num) && ctx.consistent(&tau_right, &Type::Num) {
+                Ok(Type::Num)
+            } else {
+                type_mismatch!(tau_left, tau_right, binop)
             }
         }
-        
+
         // do_!(
         //     type_check_expr(left, ctx.clone()) => tau_left,
         //     type_check_expr(right, ctx) => tau_right,
@@ -40,91 +456,137 @@ fn type_check_expr(ast: &Expr, ctx: HashMap<Variable, Type>) -> Result<Type, Str
         //         _ => type_mismatch!(tau_left, tau_right, binop),
         //     }
         // ),
-        Expr::Mulop { binop, left, right } => do_!(
+        Expr::Mulop {
+            binop, left, right, ..
+        } => do_!(
             type_check_expr(left, ctx.clone()) => tau_left,
-            type_check_expr(right, ctx) => tau_right,
-            match (tau_left.clone(), tau_right.clone()) {
-                (Type::Num, Type::Num) => Ok(Type::Num),
-                _ => type_mismatch!(tau_left, tau_right, binop),
+            type_check_expr(right, ctx.clone()) => tau_right,
+            if ctx.consistent(&tau_left, &Type::Num) && ctx.consistent(&tau_right, &Type::Num) {
+                Ok(Type::Num)
+            } else {
+                type_mismatch!(tau_left, tau_right, binop)
             }
         ),
         // 2. conditionals
-        Expr::True | Expr::False => Ok(Type::Bool),
-        Expr::Relop { relop, left, right } => do_!(
+        Expr::True(_) | Expr::False(_) => Ok(Type::Bool),
+        Expr::Relop {
+            relop, left, right, ..
+        } => do_!(
             type_check_expr(left, ctx.clone()) => tau_left,
-            type_check_expr(right, ctx) => tau_right,
-            match (tau_left.clone(), tau_right.clone()) {
-                (Type::Num, Type::Num) => Ok(Type::Bool),
-                _ => type_mismatch!(tau_left, tau_right, relop),
+            type_check_expr(right, ctx.clone()) => tau_right,
+            if ctx.consistent(&tau_left, &Type::Num) && ctx.consistent(&tau_right, &Type::Num) {
+                Ok(Type::Bool)
+            } else {
+                type_mismatch!(tau_left, tau_right, relop)
             }
         ),
-        Expr::If { cond, then_, else_ } => do_!(
+        Expr::If {
+            cond, then_, else_, ..
+        } => do_!(
             type_check_expr(cond, ctx.clone()) => tau_cond,
-            type_check_expr(then_, ctx.clone()) => tau_then,
-            type_check_expr(else_, ctx) => tau_else,
-            match (tau_cond.clone(), tau_then.clone(), tau_else.clone()) {
-                (Type::Bool, tau_then, tau_else) if Type::alpha_equiv(tau_then.clone(), tau_else.clone()) => Ok(tau_then),
-                _ => Err(format!(r"If branches have incompatible types: if {:?} then {:?} else {:?}", tau_cond, tau_then, tau_else)),
+            ctx.branches(
+                "if",
+                || type_check_expr(then_, ctx.clone()),
+                || type_check_expr(else_, ctx.clone()),
+            ) => (tau_then, tau_else),
+            if ctx.consistent(&tau_cond, &Type::Bool) && ctx.consistent(&tau_then, &tau_else) {
+                Ok(meet(&tau_then, &tau_else))
+            } else {
+                Err(format!(r"If branches have incompatible types: if {:?} then {:?} else {:?}", tau_cond, tau_then, tau_else))
             }
         ),
-        Expr::And { left, right } => do_!(
+        Expr::And { left, right, .. } => do_!(
             type_check_expr(left, ctx.clone()) => tau_left,
-            type_check_expr(right, ctx) => tau_right,
-            match (tau_left.clone(), tau_right.clone()) {
-                (Type::Bool, Type::Bool) => Ok(Type::Bool),
-                _ => type_mismatch!(tau_left, tau_right, "&&"),
+            type_check_expr(right, ctx.clone()) => tau_right,
+            if ctx.consistent(&tau_left, &Type::Bool) && ctx.consistent(&tau_right, &Type::Bool) {
+                Ok(Type::Bool)
+            } else {
+                type_mismatch!(tau_left, tau_right, "&&")
             }
         ),
-        Expr::Or { left, right } => do_!(
+        Expr::Or { left, right, .. } => do_!(
             type_check_expr(left, ctx.clone()) => tau_left,
-            type_check_expr(right, ctx) => tau_right,
-            match (tau_left.clone(), tau_right.clone()) {
-                (Type::Bool, Type::Bool) => Ok(Type::Bool),
-                _ => type_mismatch!(tau_left, tau_right, "||"),
+            type_check_expr(right, ctx.clone()) => tau_right,
+            if ctx.consistent(&tau_left, &Type::Bool) && ctx.consistent(&tau_right, &Type::Bool) {
+                Ok(Type::Bool)
+            } else {
+                type_mismatch!(tau_left, tau_right, "||")
             }
         ),
         // 3. functions
-        Expr::Var(x) => match ctx.get(x) {
-            Some(tau) => Ok(tau.clone()),
+        Expr::Var(x, _) => match ctx.get(x) {
+            Some(tau) => Ok(tau),
             None => Err(format!("Free variable: {}", x.0)),
         },
-        Expr::Lam { x, tau, e } => do_!(
-            {
-                let mut ctx = ctx;
-                ctx.insert(x.clone(), *tau.clone());
-                type_check_expr(e, ctx.clone())
-            } => tau_e,
+        Expr::Lam { x, tau, e, .. } => do_!(
+            ctx.well_formed(tau),
+            ctx.unrestricted(
+                || format!("the unrestricted function `fun ({} : {}) -> ...`", x.0, tau),
+                || ctx.with_var(x, *tau.clone(), false, e),
+            ) => tau_e,
             Ok(Type::Fn { arg: tau.clone(), ret: Box::new(tau_e) })
         ),
-        Expr::App { lam, arg } => do_!(
+        Expr::LinLam { x, tau, e, .. } => do_!(
+            ctx.well_formed(tau),
+            ctx.with_var(x, *tau.clone(), true, e) => tau_e,
+            Ok(Type::LinFn { arg: tau.clone(), ret: Box::new(tau_e) })
+        ),
+        Expr::App { lam, arg, .. } => do_!(
             type_check_expr(lam, ctx.clone()) => tau_lam,
-            type_check_expr(arg, ctx) => tau_arg,
+            type_check_expr(arg, ctx.clone()) => tau_arg,
             match tau_lam.clone() {
-                Type::Fn { arg, ret } if Type::alpha_equiv(*arg.clone(), tau_arg.clone()) => Ok(*ret),
+                Type::Fn { arg, ret } | Type::LinFn { arg, ret } if ctx.consistent(&arg, &tau_arg) => Ok(*ret),
+                Type::Dyn if ctx.consistent(&Type::Dyn, &tau_arg) => Ok(Type::Dyn),
                 _ => type_mismatch!(tau_lam, tau_arg, "function application"),
             }
         ),
         // 4. product types
-        Expr::Pair { left, right } => do_!(
+        Expr::Pair { left, right, .. } => do_!(
             type_check_expr(left, ctx.clone()) => tau_left,
             type_check_expr(right, ctx) => tau_right,
             Ok(Type::Product { left: Box::new(tau_left), right: Box::new(tau_right) })
         ),
-        Expr::Project { e, d } => do_!(
-            type_check_expr(e, ctx) => tau_e,
+        Expr::Project { e, d, .. } => do_!(
+            type_check_expr(e, ctx.clone()) => tau_e,
             match (tau_e.clone(), d) {
+                (Type::Product { right: dropped, .. }, Direction::Left)
+                | (Type::Product { left: dropped, .. }, Direction::Right)
+                    if ctx.mode == Some(Substructural::Linear) && is_linear(&dropped) =>
+                    Err(format!(
+                        "Projection `.{}` drops a value of linear type {:?}",
+                        match d { Direction::Left => "L", Direction::Right => "R" },
+                        dropped
+                    )),
                 (Type::Product { left, .. }, Direction::Left) => Ok(*left),
                 (Type::Product { right, .. }, Direction::Right) => Ok(*right),
+                (Type::Dyn, _) => Ok(Type::Dyn),
                 _ => Err(format!("Projection has incompatible type: {:?}", tau_e)),
             }
         ),
-        Expr::Unit => Ok(Type::Unit),
+        // both components are bound, so a pair of linear values is used up
+        Expr::LetPair {
+            x,
+            y,
+            e_pair,
+            e_body,
+            ..
+        } => do_!(
+            type_check_expr(e_pair, ctx.clone()) => tau_e,
+            match tau_e {
+                Type::Product { left, right } => Ok((*left, *right)),
+                Type::Dyn => Ok((Type::Dyn, Type::Dyn)),
+                _ => Err(format!("Pair pattern has incompatible type: {:?}", tau_e)),
+            } => (tau_x, tau_y),
+            ctx.binding(x, tau_x, false, || ctx.with_var(y, tau_y, false, e_body))
+        ),
+        Expr::Unit(_) => Ok(Type::Unit),
         // 5. sum types
-        Expr::Inject { e, d, tau } => do_!(
-            type_check_expr(e, ctx) => tau_e,
+        Expr::Inject { e, d, tau, .. } => do_!(
+            ctx.well_formed(tau),
+            type_check_expr(e, ctx.clone()) => tau_e,
             match (d, tau.as_ref()) {
-                (Direction::Left, Type::Sum { left, .. }) if Type::alpha_equiv(tau_e.clone(), *left.clone()) => Ok(*tau.clone()),
-                (Direction::Right, Type::Sum { right, .. }) if Type::alpha_equiv(tau_e.clone(), *right.clone()) => Ok(*tau.clone()),
+                (Direction::Left, Type::Sum { left, .. }) if ctx.consistent(&tau_e, left) => Ok(*tau.clone()),
+                (Direction::Right, Type::Sum { right, .. }) if ctx.consistent(&tau_e, right) => Ok(*tau.clone()),
                 _ => Err(format!("Inject has incompatible types: inj {:?} = {:?} as {:?}", tau_e, d, tau)),
             }
         ),
@@ -134,58 +596,69 @@ fn type_check_expr(ast: &Expr, ctx: HashMap<Variable, Type>) -> Result<Type, Str
             eleft,
             xright,
             eright,
+            ..
         } => do_!(
             type_check_expr(e, ctx.clone()) => tau_e,
             match tau_e {
                 Type::Sum { left, right } => Ok((*left, *right)),
+                Type::Dyn => Ok((Type::Dyn, Type::Dyn)),
                 _ => Err(format!("Case expression should be a sum type; found {:?}", tau_e)),
             } => (tau_xleft, tau_xright),
-            {
-                let mut ctx = ctx.clone();
-                ctx.insert(xleft.clone(), tau_xleft);
-                type_check_expr(eleft, ctx)
-            } => tau_eleft,
-            {
-                let mut ctx = ctx;
-                ctx.insert(xright.clone(), tau_xright);
-                type_check_expr(eright, ctx)
-            } => tau_eright,
-            if Type::alpha_equiv(tau_eleft.clone(), tau_eright.clone()) {
-                Ok(tau_eleft)
+            ctx.branches(
+                "case",
+                || ctx.with_var(xleft, tau_xleft, false, eleft),
+                || ctx.with_var(xright, tau_xright, false, eright),
+            ) => (tau_eleft, tau_eright),
+            if ctx.consistent(&tau_eleft, &tau_eright) {
+                Ok(meet(&tau_eleft, &tau_eright))
             } else {
                 type_mismatch!(tau_eleft, tau_eright, "case")
             }
         ),
         // 6. fixpoints
-        Expr::Fix { x, tau, e } => do_!(
-            {
-                let mut ctx = ctx;
-                ctx.insert(x.clone(), *tau.clone());
-                type_check_expr(e, ctx)
-            } => tau_e,
-            if Type::alpha_equiv(*tau.clone(), tau_e.clone()) {
-                Ok(tau_e)
+        Expr::Fix { x, tau, e, .. } => do_!(
+            ctx.well_formed(tau),
+            if ctx.mode.is_some() && is_linear(tau) {
+                Err(format!("Fixpoint `{}` has linear type {:?} but may be unrolled many times", x.0, tau))
+            } else {
+                Ok(())
+            },
+            ctx.unrestricted(
+                || format!("the fixpoint `{}`", x.0),
+                || ctx.with_var(x, *tau.clone(), false, e),
+            ) => tau_e,
+            if ctx.consistent(tau, &tau_e) {
+                Ok(*tau.clone())
             } else {
                 type_mismatch!(tau, tau_e, "fixpoint")
             }
         ),
         // 7. polymorphism
-        Expr::TyLam { a, e } => do_!(
-            type_check_expr(e, ctx) => tau_e,
-            Ok(Type::Forall { a: a.clone(), tau: Box::new(tau_e) })
+        Expr::TyLam { a, e, .. } => do_!(
+            ctx.unrestricted(
+                || format!("the type abstraction `tyfun {} -> ...`", a.0),
+                || ctx.with_tyvar(a, || type_check_expr(e, ctx.clone())),
+            ) => tau_e,
+            Ok(Type::forall(a.clone(), tau_e))
         ),
-        Expr::TyApp { e, tau: tau_arg } => do_!(
-            type_check_expr(e, ctx) => tau_e,
+        Expr::TyApp {
+            e, tau: tau_arg, ..
+        } => do_!(
+            ctx.well_formed(tau_arg),
+            type_check_expr(e, ctx.clone()) => tau_e,
             match tau_e {
-                Type::Forall { a, tau: tau_body } => Ok(tau_body.substitute(a, *tau_arg.clone())),
+                Type::Forall { .. } if ctx.mode.is_some() && is_linear(tau_arg) =>
+                    Err(format!("Type variables can't be instantiated with linear type {:?}", tau_arg)),
+                Type::Forall { tau: tau_body, .. } => Ok(tau_body.open(tau_arg)),
                 _ => type_mismatch!(tau_e, tau_arg, "type application"),
             }
         ),
         // 8. recursive types
-        Expr::Fold { e, tau } => match tau.as_ref() {
-            Type::Rec { a, tau: tau_body } => do_!(
-                type_check_expr(e, ctx) => tau_e,
-                if Type::alpha_equiv(tau_e.clone(), tau_body.clone().substitute(a.clone(), *tau.clone())) {
+        Expr::Fold { e, tau, .. } => match tau.as_ref() {
+            Type::Rec { tau: tau_body, .. } => do_!(
+                ctx.well_formed(tau),
+                type_check_expr(e, ctx.clone()) => tau_e,
+                if ctx.consistent(&tau_e, &tau_body.open(tau)) {
                     Ok(*tau.clone())
                 } else {
                     type_mismatch!(tau_e, tau_body, "folding")
@@ -193,10 +666,10 @@ fn type_check_expr(ast: &Expr, ctx: HashMap<Variable, Type>) -> Result<Type, Str
             ),
             _ => Err(format!("Folding to type: {:?}", tau)),
         },
-        Expr::Unfold(e) => do_!(
+        Expr::Unfold(e, _) => do_!(
             type_check_expr(e, ctx) => tau_e,
             match tau_e.clone() {
-                Type::Rec { a, tau: tau_body } => Ok(tau_body.substitute(a.clone(), tau_e)),
+                Type::Rec { tau: tau_body, .. } => Ok(tau_body.open(&tau_e)),
                 _ => Err(format!("Unfolding from type: {:?}", tau_e)),
             }
         ),
@@ -205,10 +678,13 @@ fn type_check_expr(ast: &Expr, ctx: HashMap<Variable, Type>) -> Result<Type, Str
             e,
             tau_adt,
             tau_mod,
+            ..
         } => do_!(
-            type_check_expr(e, ctx) => tau_e,
-            if let Type::Exists { a, tau } = *tau_mod.clone() {
-                if Type::alpha_equiv(tau_e.clone(), tau.clone().substitute(a.clone(), *tau_adt.clone())) {
+            ctx.well_formed(tau_adt),
+            ctx.well_formed(tau_mod),
+            type_check_expr(e, ctx.clone()) => tau_e,
+            if let Type::Exists { tau, .. } = tau_mod.as_ref() {
+                if ctx.consistent(&tau_e, &tau.open(tau_adt)) {
                     Ok(*tau_mod.clone())
                 } else {
                     type_mismatch!(tau_e, tau, "export")
@@ -222,14 +698,30 @@ fn type_check_expr(ast: &Expr, ctx: HashMap<Variable, Type>) -> Result<Type, Str
             a: b,
             e_mod,
             e_body,
+            ..
         } => do_!(
             type_check_expr(e_mod, ctx.clone()) => tau_exist,
-            if let Type::Exists { a, tau: tau_mod } = tau_exist {
-                let mut ctx = ctx;
-                ctx.insert(x.clone(), tau_mod.substitute(a, Type::Var(b.clone())));
-                type_check_expr(e_body, ctx)
+            if let Type::Exists { tau: tau_mod, .. } = tau_exist {
+                let tau_x = tau_mod.open(&Type::Var(b.clone()));
+                ctx.with_tyvar(b, || ctx.with_var(x, tau_x, false, e_body))
             } else {
                 Err(format!("Type {:?} is not an existential type", tau_exist))
+            } => tau_body,
+            if tau_body.free_vars().contains(b) {
+                Err(format!("Abstract type `{}` escapes the body of import: {:?}", b.0, tau_body))
+            } else {
+                Ok(tau_body)
+            }
+        ),
+        // 10. gradual typing
+        Expr::Cast { e, from, to, .. } => do_!(
+            ctx.well_formed(from),
+            ctx.well_formed(to),
+            type_check_expr(e, ctx.clone()) => tau_e,
+            if ctx.consistent(&tau_e, from) && ctx.consistent(from, to) {
+                Ok(*to.clone())
+            } else {
+                type_mismatch!(from, to, "cast")
             }
         ),
     }
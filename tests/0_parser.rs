@@ -11,9 +11,11 @@ mod tests {
                 x: Variable::from("x"),
                 tau: Box::new(Type::Num),
                 e: Box::new(Expr::App {
-                    lam: Box::new(Expr::Var("x".into())),
-                    arg: Box::new(Expr::Var("y".into()))
-                })
+                    lam: Box::new(Expr::Var("x".into(), NodeId::default())),
+                    arg: Box::new(Expr::Var("y".into(), NodeId::default())),
+                    id: NodeId::default()
+                }),
+                id: NodeId::default()
             })
         );
 
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::evaluate::try_eval;
+    use interpreter::flags::{Mode, OutputMode};
+    use interpreter::gradual::{consistent, elaborate};
+    use interpreter::parser::{parse_type, parse_with_spans};
+    use interpreter::span::Span;
+    use interpreter::typecheck::type_check;
+
+    fn run(program: &str) -> Result<Expr, Blame> {
+        let (ast, spans) = parse_with_spans(program).unwrap();
+        type_check(&ast).unwrap();
+        try_eval(&elaborate(&ast, &spans), Mode::Eval, OutputMode::Full)
+    }
+
+    #[test]
+    fn eval_test() {
+        assert_eq!(
+            run("(fun (x : ?) -> x + 1) 41"),
+            Ok(Expr::Num(42, NodeId::default()))
+        );
+        assert_eq!(
+            run("let f : ? -> ? = fun (x : ?) -> x in (f 1) + (f 2)"),
+            Ok(Expr::Num(3, NodeId::default()))
+        );
+        assert_eq!(
+            run("let p : ? = (1, true) in (p.R) && ((p.L) < 2)"),
+            Ok(Expr::True(NodeId::default()))
+        );
+        assert_eq!(
+            run("let s : ? = inj 3 = L as num + bool in case s { L(n) -> n + 1 | R(b) -> 0 }"),
+            Ok(Expr::Num(4, NodeId::default()))
+        );
+        assert_eq!(
+            run("let id : ? = tyfun a -> fun (x : a) -> x in (fun (f : forall a . a -> a) -> f [num] 5) id"),
+            Ok(Expr::Num(5, NodeId::default()))
+        );
+    }
+
+    #[test]
+    fn blame() {
+        assert_eq!(
+            run("(fun (x : ?) -> if x then 1 else 2) 5"),
+            Err(Blame {
+                label: String::from("operand of `if` at 1:20"),
+                span: Some(Span { start: 19, end: 20 }),
+                polarity: Polarity::Positive,
+            })
+        );
+        assert_eq!(
+            run("let f : ? = fun (x : num) -> x * 2 in\n(fun (g : bool -> num) -> g true) f"),
+            Err(Blame {
+                label: String::from("annotation `bool → num` at 2:11"),
+                span: Some(Span { start: 48, end: 59 }),
+                polarity: Polarity::Positive,
+            })
+        );
+        assert_eq!(
+            run("let f : num -> num = fun (x : num) -> x in\n(fun (g : ? -> num) -> g true) f"),
+            Err(Blame {
+                label: String::from("annotation `? → num` at 2:11"),
+                span: Some(Span { start: 53, end: 61 }),
+                polarity: Polarity::Negative,
+            })
+        );
+    }
+
+    #[test]
+    fn type_check_test() {
+        let (ast, _) = parse_with_spans("fun (x : ?) -> if x then x else 1").unwrap();
+        assert_eq!(type_check(&ast).unwrap(), *parse_type("? -> num").unwrap());
+        let (ast, _) = parse_with_spans("(fun (x : num) -> x) true").unwrap();
+        assert!(type_check(&ast).is_err());
+        let (ast, _) =
+            parse_with_spans("(fun (x : ? -> num) -> x) (fun (y : bool) -> ())").unwrap();
+        assert!(type_check(&ast).is_err());
+    }
+
+    #[test]
+    fn static_programs_unchanged() {
+        let (ast, spans) =
+            parse_with_spans("let f : num -> num = fun (x : num) -> x in f 1").unwrap();
+        assert_eq!(elaborate(&ast, &spans), *ast);
+    }
+
+    #[test]
+    fn consistency() {
+        let ty = |s| *parse_type(s).unwrap();
+        assert!(consistent(&ty("?"), &ty("num -> bool")));
+        assert!(consistent(&ty("? -> num"), &ty("bool -> ?")));
+        assert!(consistent(
+            &ty("forall a . a -> ?"),
+            &ty("forall b . b -> b")
+        ));
+        assert!(!consistent(&ty("? -> num"), &ty("bool -> bool")));
+        assert!(!consistent(&ty("num * ?"), &ty("num + ?")));
+        assert!(!consistent(&ty("forall a . a"), &ty("forall b . num")));
+    }
+}
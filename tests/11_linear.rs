@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::evaluate::eval;
+    use interpreter::flags::{Mode, OutputMode};
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::typecheck::{type_check, type_check_substructural, Substructural};
+
+    fn linear(program: &str) -> Result<Type, String> {
+        type_check_substructural(&parse(program).unwrap(), Substructural::Linear)
+    }
+
+    fn affine(program: &str) -> Result<Type, String> {
+        type_check_substructural(&parse(program).unwrap(), Substructural::Affine)
+    }
+
+    #[test]
+    fn eval_test() {
+        let swap = parse(
+            r#"
+            let apply : (num -o num) -o num -o num = fun (f : num -o num) -o fun (x : num) -o f x in
+            apply (fun (x : num) -o x * 2) 21
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            eval(&swap, Mode::Eval, OutputMode::Full),
+            Expr::Num(42, NodeId::default())
+        );
+        assert_eq!(type_check(&swap).unwrap(), Type::Num);
+        assert_eq!(
+            type_check_substructural(&swap, Substructural::Linear).unwrap(),
+            Type::Num
+        );
+    }
+
+    #[test]
+    fn well_typed() {
+        assert_eq!(
+            linear("fun (f : num -o num) -o fun (x : num) -o f x").unwrap(),
+            *parse_type("(num -o num) -o num -o num").unwrap()
+        );
+        assert!(linear("fun (f : num -o num) -o fun (s : num + num) -o case s { L(a) -> (f a) | R(b) -> (f b) }").is_ok());
+        assert!(
+            linear("fun (f : num -o num) -o fun (b : bool) -> if b then (f 1) else (f 0)").is_err()
+        );
+        assert!(
+            linear("fun (s : (num -o num) + num) -o case s { L(f) -> (f 1) | R(n) -> n }").is_ok()
+        );
+        // unrestricted variables can still be duplicated and dropped
+        assert!(linear("fun (x : num) -o (fun (y : num) -> y + y) x").is_ok());
+    }
+
+    #[test]
+    fn duplication() {
+        assert_eq!(
+            linear("fun (f : num -o num) -o (f 1) + (f 2)"),
+            Err(String::from("Linear variable `f` is used 2 times"))
+        );
+        assert_eq!(
+            affine("fun (p : (num -o num) * num) -o (p, p)"),
+            Err(String::from("Linear variable `p` is used 2 times"))
+        );
+    }
+
+    #[test]
+    fn dynamic() {
+        // casting `f` to `?` would let it be used any number of times
+        let program = "fun (f : num -o num) -o (fun (d : ?) -> (d 1) + (d 2)) f";
+        assert!(linear(program).is_err());
+        assert!(affine(program).is_err());
+        assert!(type_check(&parse(program).unwrap()).is_ok());
+        assert!(linear("fun (d : ?) -> fun (f : num -o num) -o f (d 1)").is_ok());
+        assert!(linear("fun (d : ?) -> fun (f : num -o num) -o (d f)").is_err());
+    }
+
+    #[test]
+    fn dropping() {
+        assert_eq!(
+            linear("fun (f : num -o num) -o 3"),
+            Err(String::from("Linear variable `f` is never used"))
+        );
+        assert!(affine("fun (f : num -o num) -o 3").is_ok());
+        assert_eq!(
+            linear("fun (p : (num -o num) * num) -o p.R"),
+            Err(String::from(
+                "Projection `.R` drops a value of linear type LinFn { arg: Num, ret: Num }"
+            ))
+        );
+        assert!(affine("fun (p : (num -o num) * num) -o p.R").is_ok());
+    }
+
+    #[test]
+    fn pairs() {
+        let apply = parse("let (f, n) = (fun (x : num) -o x * 2, 21) in f n").unwrap();
+        assert_eq!(
+            eval(&apply, Mode::Eval, OutputMode::Full),
+            Expr::Num(42, NodeId::default())
+        );
+        assert_eq!(
+            linear("fun (p : (num -o num) * num) -o (let (f, n) = p in f n)").unwrap(),
+            *parse_type("(num -o num) * num -o num").unwrap()
+        );
+        assert_eq!(
+            linear("fun (p : (num -o num) * num) -o (let (f, n) = p in n)"),
+            Err(String::from("Linear variable `f` is never used"))
+        );
+        assert!(affine("fun (p : (num -o num) * num) -o (let (f, n) = p in n)").is_ok());
+        assert_eq!(
+            linear("fun (p : (num -o num) * num) -o (let (f, n) = p in (f n) + (f 1))"),
+            Err(String::from("Linear variable `f` is used 2 times"))
+        );
+        // the pair itself is used up by taking it apart
+        assert_eq!(
+            linear("fun (p : (num -o num) * num) -o (let (f, n) = p in (f n, p))"),
+            Err(String::from("Linear variable `p` is used 2 times"))
+        );
+    }
+
+    #[test]
+    fn branches() {
+        assert_eq!(
+            linear("fun (g : num -o num) -o fun (s : num + num) -o case s { L(a) -> (g a) | R(b) -> b }"),
+            Err(String::from(
+                "Linear variable `g` is used 1 time(s) in the first branch of case but 0 time(s) in the second"
+            ))
+        );
+        assert!(affine(
+            "fun (g : num -o num) -o fun (s : num + num) -o case s { L(a) -> (g a) | R(b) -> b }"
+        )
+        .is_ok());
+        assert!(affine("fun (g : num -o num) -o fun (s : num + num) -o (case s { L(a) -> (g a) | R(b) -> b }) + (g 1)").is_err());
+    }
+
+    #[test]
+    fn captures() {
+        assert_eq!(
+            linear("fun (f : num -o num) -o fun (x : num) -> f x"),
+            Err(String::from(
+                "Linear variable `f` is captured by the unrestricted function `fun (x : num) -> ...`"
+            ))
+        );
+        assert_eq!(
+            linear("fun (g : num -o num) -o fix (h : num) -> g 1"),
+            Err(String::from(
+                "Linear variable `g` is captured by the fixpoint `h`"
+            ))
+        );
+        assert!(linear("fix (f : (num -o num) -> num) -> (fun (g : num -o num) -> g 1)").is_ok());
+        assert!(linear("fix (f : num -o num) -> (fun (x : num) -o x)").is_err());
+        assert!(linear("(tyfun a -> fun (x : a) -> x) [num -o num]").is_err());
+    }
+
+    #[test]
+    fn subtraction() {
+        let num = |program: &str| match eval(&parse(program).unwrap(), Mode::Eval, OutputMode::Full)
+        {
+            Expr::Num(n, _) => n,
+            e => panic!("{e:?}"),
+        };
+        assert_eq!(num("let one : num = 1 in 5-one"), 4);
+        assert_eq!(num("let one : num = 1 in 5-one*2+1"), 4);
+        assert_eq!(num("let o : num = 2 in 5-o*3"), -1);
+        assert_eq!(num("(fun (x : num) -o x-1) 3"), 2);
+    }
+}
@@ -0,0 +1,156 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::Rng;
+    use interpreter::ast::*;
+    use interpreter::ast_util::Symbol;
+    use interpreter::parser::parse;
+    use std::collections::HashSet;
+
+    /// Names chosen so that binders, free variables and generated names collide often
+    const NAMES: [&str; 5] = ["x", "y", "z", "x1", "y1"];
+
+    impl Rng {
+        fn name(&mut self) -> Variable {
+            Variable::from(NAMES[self.below(NAMES.len() as u64) as usize])
+        }
+
+        fn expr(&mut self, size: u32) -> Expr {
+            let num = || Box::new(Type::Num);
+            match if size == 0 {
+                self.below(2)
+            } else {
+                self.below(8)
+            } {
+                0 => Expr::Var(self.name(), NodeId::default()),
+                1 => Expr::Num(self.below(10) as i32, NodeId::default()),
+                2 | 3 => Expr::Lam {
+                    x: self.name(),
+                    tau: num(),
+                    e: Box::new(self.expr(size - 1)),
+                    id: NodeId::default(),
+                },
+                4 => Expr::App {
+                    lam: Box::new(self.expr(size / 2)),
+                    arg: Box::new(self.expr(size / 2)),
+                    id: NodeId::default(),
+                },
+                5 => Expr::Pair {
+                    left: Box::new(self.expr(size / 2)),
+                    right: Box::new(self.expr(size / 2)),
+                    id: NodeId::default(),
+                },
+                6 => Expr::Fix {
+                    x: self.name(),
+                    tau: num(),
+                    e: Box::new(self.expr(size - 1)),
+                    id: NodeId::default(),
+                },
+                _ => Expr::Case {
+                    e: Box::new(self.expr(size / 3)),
+                    xleft: self.name(),
+                    eleft: Box::new(self.expr(size / 3)),
+                    xright: self.name(),
+                    eright: Box::new(self.expr(size / 3)),
+                    id: NodeId::default(),
+                },
+            }
+        }
+
+        fn ty(&mut self, size: u32) -> Type {
+            match if size == 0 {
+                self.below(2)
+            } else {
+                self.below(7)
+            } {
+                0 => Type::Var(self.name()),
+                1 => Type::Num,
+                2 => Type::forall(self.name(), self.ty(size - 1)),
+                3 => Type::exists(self.name(), self.ty(size - 1)),
+                4 => Type::rec(self.name(), self.ty(size - 1)),
+                5 => Type::Fn {
+                    arg: Box::new(self.ty(size / 2)),
+                    ret: Box::new(self.ty(size / 2)),
+                },
+                _ => Type::Product {
+                    left: Box::new(self.ty(size / 2)),
+                    right: Box::new(self.ty(size / 2)),
+                },
+            }
+        }
+    }
+
+    /// Free variables are exactly those of the term minus `x`, plus those of `v` if `x`
+    /// occurred: a captured variable of `v` would disappear from the result
+    fn check_free_vars<T: Symbol + Clone + std::fmt::Debug>(e: &T, x: &Variable, v: &T) {
+        let mut expected: HashSet<Variable> = e.free_vars();
+        if expected.remove(x) {
+            expected.extend(v.free_vars());
+        }
+        let result = e.clone().substitute(x.clone(), v.clone());
+        assert_eq!(
+            result.free_vars(),
+            expected,
+            "{e:?} [{x:?} := {v:?}] = {result:?}"
+        );
+    }
+
+    /// Renaming `x` to a variable `w` that is not free and back is the identity up to
+    /// alpha-equivalence, even when `w` is bound inside the term
+    fn check_round_trip<T: Symbol + Clone + std::fmt::Debug>(e: &T, x: &Variable, w: &Variable) {
+        if x == w || e.free_vars().contains(w) {
+            return;
+        }
+        let renamed = e.clone().substitute(x.clone(), T::var(w.clone()));
+        let back = renamed.clone().substitute(w.clone(), T::var(x.clone()));
+        assert!(
+            T::alpha_equiv(e.clone(), back.clone()),
+            "{e:?} -> {renamed:?} -> {back:?}"
+        );
+    }
+
+    #[test]
+    fn expr_capture_avoiding() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..2000 {
+            let e = rng.expr(8);
+            let v = rng.expr(3);
+            let (x, w) = (rng.name(), rng.name());
+            check_free_vars(&e, &x, &v);
+            check_round_trip(&e, &x, &w);
+        }
+    }
+
+    #[test]
+    fn type_capture_avoiding() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..2000 {
+            let tau = rng.ty(8);
+            let v = rng.ty(3);
+            let (a, w) = (rng.name(), rng.name());
+            check_free_vars(&tau, &a, &v);
+            check_round_trip(&tau, &a, &w);
+        }
+    }
+
+    #[test]
+    fn fresh_names() {
+        // binders are only renamed when they would capture
+        let expr = parse("fun (y : num) -> x + y").unwrap();
+        assert_eq!(
+            expr.clone()
+                .substitute(Variable::from("x"), Expr::Num(1, NodeId::default())),
+            *parse("fun (y : num) -> 1 + y").unwrap()
+        );
+        // a user variable named like a generated one is never clobbered
+        let expr = parse("fun (y : num) -> fun (y1 : num) -> x + y + y1").unwrap();
+        assert_eq!(
+            expr.substitute(
+                Variable::from("x"),
+                Expr::Var("y".into(), NodeId::default())
+            ),
+            *parse("fun (y1 : num) -> fun (y2 : num) -> y + y1 + y2").unwrap()
+        );
+    }
+}
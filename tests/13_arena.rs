@@ -0,0 +1,164 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::arena::Arena;
+    use interpreter::ast::*;
+    use interpreter::ast_util::Symbol;
+    use interpreter::dotgen::to_dot_shared;
+    use interpreter::evaluate::{try_step, Outcome};
+    use interpreter::gradual::elaborate;
+    use interpreter::intern::Interner;
+    use interpreter::parser::{parse, parse_with_spans};
+    use interpreter::typecheck::type_check;
+
+    const PROGRAMS: [&str; 8] = [
+        "let f : num -> num = fun (x : num) -> x * 2 in f (f 3) + 1",
+        "letrec fact : num -> num = fun (n : num) -> (if n < 1 then 1 else n * (fact (n - 1))) in fact 6",
+        "case (inj (1, true) = L as (num * bool) + unit) {L(p) -> (if p.R then p.L else 0) | R(u) -> 2}",
+        "(tyfun a -> fun (x : a) -> (x, x)) [num] 4",
+        "let y : num = 5 in (fun (x : num) -> fun (y : num) -> x + y) y",
+        "import (m, t) = export (0, fun (n : num) -> n + 1) without num as exists s . s * (s -> num) in (m.R (m.L))",
+        "(fun (x : ?) -> x + 1) 2",
+        "let (x, y) = (1, 2) in let (y, x) = (x, y) in x - y",
+    ];
+
+    /// Evaluate with the tree-based small-step rules
+    fn eval_tree(e: Expr) -> Expr {
+        let mut e = e;
+        loop {
+            match try_step(&e) {
+                Outcome::Step(next) => e = next,
+                Outcome::Value => return e,
+                Outcome::Blame(blame) => panic!("{blame}"),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for program in PROGRAMS {
+            let ast = parse(program).unwrap();
+            let mut arena = Arena::new();
+            let root = arena.from_expr(&ast);
+            assert_eq!(arena.to_expr(root), *ast);
+        }
+    }
+
+    #[test]
+    fn same_result() {
+        for program in PROGRAMS {
+            let (ast, spans) = parse_with_spans(program).unwrap();
+            type_check(&ast).unwrap();
+            let ast = elaborate(&ast, &spans);
+            let expected = eval_tree(ast.clone());
+            for mut arena in [Arena::new(), Arena::hash_consed()] {
+                let root = arena.from_expr(&ast);
+                let value = arena.eval(root).unwrap();
+                assert!(
+                    Expr::alpha_equiv(arena.to_expr(value), expected.clone()),
+                    "{program}"
+                );
+            }
+        }
+    }
+
+    /// `d{n}` calls `d{n-1}` twice, so substituting the definitions doubles the result each time
+    fn doubling(n: usize) -> String {
+        let mut program = String::from("let d0 : num -> num = fun (n : num) -> n + 1 in ");
+        for i in 1..=n {
+            program += &format!(
+                "let d{i} : num -> num = fun (n : num) -> d{0} (d{0} n) in ",
+                i - 1
+            );
+        }
+        program + &format!("d{n}")
+    }
+
+    #[test]
+    fn hash_consing() {
+        let ast = parse("(1 + 2, 1 + 2)").unwrap();
+        let mut arena = Arena::hash_consed();
+        let root = arena.from_expr(&ast);
+        assert_eq!(arena.tree_size(root), 7);
+        assert_eq!(arena.dag_size(root), 4);
+        assert_eq!(arena.len(), 4);
+
+        // the tree doubles with every definition, the DAG grows by a few nodes
+        let ast = parse(&doubling(40)).unwrap();
+        let mut arena = Arena::hash_consed();
+        let root = arena.from_expr(&ast);
+        let value = arena.eval(root).unwrap();
+        assert!(arena.tree_size(value) > 1 << 40);
+        assert!(arena.dag_size(value) < 200);
+
+        let ast = parse(&doubling(6)).unwrap();
+        let mut arena = Arena::hash_consed();
+        let root = arena.from_expr(&ast);
+        let value = arena.eval(root).unwrap();
+        assert_eq!(arena.to_expr(value), eval_tree(*ast));
+    }
+
+    #[test]
+    fn shared_graph() {
+        let ast = parse("(1 + 2, 1 + 2)").unwrap();
+        let mut arena = Arena::hash_consed();
+        let root = arena.from_expr(&ast);
+        let dot = to_dot_shared(&arena, root, None);
+        // the root point and one point per distinct node
+        assert_eq!(dot.matches("shape=point").count(), 5);
+        // but both edges into the shared sum
+        assert_eq!(dot.matches("label=\"+\"").count(), 2);
+    }
+
+    #[test]
+    fn sharing() {
+        // substitution reuses every subterm the variable doesn't occur in
+        let ast = parse("fun (y : num) -> (x, (y, 1 + 2))").unwrap();
+        let mut arena = Arena::new();
+        let root = arena.from_expr(&ast);
+        let one = arena.from_expr(&Expr::Num(1, NodeId::default()));
+        let x = arena.intern(&Variable::from("x"));
+        let before = arena.len();
+        let result = arena.substitute(root, x, one);
+        // only the outer pair and the function are new
+        assert_eq!(arena.len(), before + 2);
+        assert_eq!(
+            arena.to_expr(result),
+            *parse("fun (y : num) -> (1, (y, 1 + 2))").unwrap()
+        );
+        // nothing is allocated when the variable doesn't occur
+        let z = arena.intern(&Variable::from("z"));
+        assert_eq!(arena.substitute(root, z, one), root);
+    }
+
+    #[test]
+    fn interning() {
+        let mut names = Interner::new();
+        let x = names.intern(&Variable::from("x"));
+        assert_eq!(
+            names.intern(&Variable::from("y")),
+            names.intern(&Variable::from("y"))
+        );
+        assert_ne!(names.get(&Variable::from("y")), Some(x));
+        assert_eq!(names.name(x), "x");
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn reclaim() {
+        let program = "letrec sum : num -> num = fun (n : num) -> (if n < 1 then 0 else n + (sum (n - 1))) in sum 60";
+        let ast = parse(program).unwrap();
+        for mut arena in [Arena::new(), Arena::hash_consed()] {
+            let root = arena.from_expr(&ast);
+            let value = arena.eval(root).unwrap();
+            assert_eq!(arena.to_expr(value), Expr::Num(1830, NodeId::default()));
+            // the nodes of earlier steps were dropped
+            assert!(arena.len() < 4096, "{}", arena.len());
+        }
+        // small arenas are left alone
+        let mut arena = Arena::new();
+        let root = arena.from_expr(&ast);
+        let before = arena.len();
+        assert_eq!(arena.reclaim(root), root);
+        assert_eq!(arena.len(), before);
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::evaluate::{try_eval_with, Machine, Outcome};
+    use interpreter::flags::{Mode, OutputMode, Strategy};
+    use interpreter::parser::parse;
+    use interpreter::typecheck::type_check;
+
+    const STRATEGIES: [Strategy; 3] = [Strategy::Value, Strategy::Name, Strategy::Need];
+
+    /// Step until a value, returning it with the number of steps, or `None` after `budget` steps
+    fn run(e: &Expr, strategy: Strategy, budget: usize) -> Option<(Expr, usize)> {
+        let machine = Machine::new(strategy);
+        let mut e = e.clone();
+        for steps in 0..budget {
+            match machine.step(&e) {
+                Outcome::Step(next) => e = next,
+                Outcome::Value => return Some((machine.read_back(e), steps)),
+                Outcome::Blame(blame) => panic!("{blame}"),
+            }
+        }
+        None
+    }
+
+    fn eval(e: &Expr, strategy: Strategy) -> Expr {
+        try_eval_with(e, Mode::Eval, OutputMode::Full, strategy).unwrap()
+    }
+
+    #[test]
+    fn same_result() {
+        for (program, result) in [
+            ("let f : num -> num = fun (x : num) -> x * 2 in (f (f 3)) + 1", 13),
+            ("letrec fact : num -> num = fun (n : num) -> (if n < 1 then 1 else n * (fact (n - 1))) in fact 5", 120),
+            ("case (inj (1 + 1) = L as num + bool) {L(n) -> n * n | R(b) -> 0}", 4),
+            ("import (m, t) = export (1 + 1, fun (n : num) -> n * 3) without num as exists s . s * (s -> num) in (m.R (m.L))", 6),
+        ] {
+            let ast = parse(program).unwrap();
+            type_check(&ast).unwrap();
+            for strategy in STRATEGIES {
+                assert_eq!(eval(&ast, strategy), Expr::Num(result, NodeId::default()), "{program}");
+            }
+        }
+    }
+
+    #[test]
+    fn diverge_under_value() {
+        // the argument loops forever but is never used
+        let unused = parse(
+            "letrec loop : num -> num = fun (n : num) -> loop n in (fun (x : num) -> 1) (loop 0)",
+        )
+        .unwrap();
+        // so is the other component of a pair
+        let pair =
+            parse("letrec loop : num -> num = fun (n : num) -> loop n in (1, loop 0).L").unwrap();
+        for ast in [unused, pair] {
+            type_check(&ast).unwrap();
+            assert_eq!(run(&ast, Strategy::Value, 10000), None);
+            assert_eq!(
+                run(&ast, Strategy::Name, 10000).unwrap().0,
+                Expr::Num(1, NodeId::default())
+            );
+            assert_eq!(
+                run(&ast, Strategy::Need, 10000).unwrap().0,
+                Expr::Num(1, NodeId::default())
+            );
+        }
+    }
+
+    #[test]
+    fn need_shares_work() {
+        let ast = parse("(fun (x : num) -> x + x + x) (1 + 2 + 3 + 4)").unwrap();
+        let (by_value, value_steps) = run(&ast, Strategy::Value, 100).unwrap();
+        let (by_name, name_steps) = run(&ast, Strategy::Name, 100).unwrap();
+        let (by_need, need_steps) = run(&ast, Strategy::Need, 100).unwrap();
+        assert_eq!(
+            (by_value, by_name, by_need),
+            (
+                Expr::Num(30, NodeId::default()),
+                Expr::Num(30, NodeId::default()),
+                Expr::Num(30, NodeId::default())
+            )
+        );
+        // the argument's three additions are done once, three times, and once
+        assert_eq!(value_steps, 1 + 3 + 2);
+        assert_eq!(name_steps, 1 + 3 * 3 + 2);
+        // plus a step to read the finished thunk at each use
+        assert_eq!(need_steps, 1 + 3 + 3 + 2);
+    }
+
+    #[test]
+    fn lazy_data() {
+        let ast = parse("(fun (x : num) -> (x, 2 * 3)) (1 + 2)").unwrap();
+        assert_eq!(eval(&ast, Strategy::Value), *parse("(3, 6)").unwrap());
+        assert_eq!(
+            eval(&ast, Strategy::Name),
+            *parse("(1 + 2, 2 * 3)").unwrap()
+        );
+        // unevaluated thunks are read back as their expressions
+        assert_eq!(
+            eval(&ast, Strategy::Need),
+            *parse("(1 + 2, 2 * 3)").unwrap()
+        );
+        let machine = Machine::new(Strategy::Need);
+        assert!(matches!(machine.step(&ast), Outcome::Step(_)));
+        assert_eq!(machine.heap(), vec![*parse("1 + 2").unwrap()]);
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::ast_util::Symbol;
+    use interpreter::flags::{Mode, OutputMode};
+    use interpreter::normalize::{normalize, Error};
+    use interpreter::parser::parse;
+    use interpreter::typecheck::type_check;
+
+    fn normal_form(program: &str) -> Result<Expr, Error> {
+        let ast = parse(program).unwrap();
+        type_check(&ast).unwrap();
+        normalize(&ast, 1000, Mode::Eval, OutputMode::Full)
+    }
+
+    #[test]
+    fn church_numerals() {
+        let nat = "forall a . (a -> a) -> a -> a";
+        let program = format!(
+            "let two : {nat} = tyfun a -> fun (f : a -> a) -> fun (x : a) -> f (f x) in
+             let three : {nat} = tyfun a -> fun (f : a -> a) -> fun (x : a) -> f (f (f x)) in
+             let plus : ({nat}) -> ({nat}) -> ({nat}) =
+               fun (m : {nat}) -> fun (n : {nat}) ->
+                 (tyfun a -> fun (f : a -> a) -> fun (x : a) -> m [a] f (n [a] f x)) in
+             plus two three"
+        );
+        let five =
+            parse("tyfun b -> fun (g : b -> b) -> fun (y : b) -> g (g (g (g (g y))))").unwrap();
+        assert!(Expr::alpha_equiv(normal_form(&program).unwrap(), *five));
+    }
+
+    #[test]
+    fn under_binders() {
+        assert_eq!(
+            normal_form("fun (x : num) -> (fun (y : num) -> (y, 1 + 2)) x").unwrap(),
+            *parse("fun (x : num) -> (x, 3)").unwrap()
+        );
+        assert_eq!(
+            normal_form("inj ((fun (b : bool) -> b || false) true) = R as num + bool").unwrap(),
+            *parse("inj true = R as num + bool").unwrap()
+        );
+        // stuck on a variable, or on a division that may never happen
+        let stuck = "fun (x : bool) -> (if x then 1 else 1 / 0) + 1";
+        assert_eq!(normal_form(stuck).unwrap(), *parse(stuck).unwrap());
+    }
+
+    #[test]
+    fn normal_order() {
+        // the unused argument is never reduced
+        let program =
+            "letrec loop : num -> num = fun (n : num) -> loop n in (fun (x : num) -> 1) (loop 0)";
+        assert_eq!(
+            normal_form(program).unwrap(),
+            Expr::Num(1, NodeId::default())
+        );
+        let program = "letrec fact : num -> num = fun (n : num) -> (if n < 1 then 1 else n * (fact (n - 1))) in fact 5";
+        assert_eq!(
+            normal_form(program).unwrap(),
+            Expr::Num(120, NodeId::default())
+        );
+    }
+
+    #[test]
+    fn budget() {
+        let program = "letrec loop : num -> num = fun (n : num) -> loop n in loop 0";
+        assert!(matches!(normal_form(program), Err(Error::OutOfSteps(1000))));
+        // an unapplied `fix` is already normal
+        let program = "fix (f : num -> num) -> (fun (n : num) -> f n)";
+        assert_eq!(normal_form(program).unwrap(), *parse(program).unwrap());
+    }
+
+    #[test]
+    fn fix_operands() {
+        // operators need the value of a `fix`, as `evaluate` does
+        assert_eq!(
+            normal_form("(fix (x : num) -> 1) + 2").unwrap(),
+            Expr::Num(3, NodeId::default())
+        );
+        assert_eq!(
+            normal_form("3 * (fix (x : num) -> 2) < 7").unwrap(),
+            Expr::True(NodeId::default())
+        );
+        assert_eq!(
+            normal_form(
+                "if (fix (b : bool) -> true) && (fix (c : bool) -> false || true) then 1 else 0"
+            )
+            .unwrap(),
+            Expr::Num(1, NodeId::default())
+        );
+    }
+}
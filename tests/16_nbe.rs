@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::ast_util::Symbol;
+    use interpreter::nbe::{equiv, normal_form, Equivalence};
+    use interpreter::parser::parse;
+    use interpreter::typecheck::type_check;
+
+    fn same(left: &str, right: &str) -> bool {
+        let (left, right) = (parse(left).unwrap(), parse(right).unwrap());
+        let tau = type_check(&left).unwrap();
+        assert_eq!(type_check(&right).unwrap(), tau);
+        matches!(
+            equiv(&left, &right, &tau).unwrap(),
+            Equivalence::Equivalent(_)
+        )
+    }
+
+    #[test]
+    fn beta() {
+        assert!(same(
+            "fun (x : num) -> (fun (y : num) -> (y, 1 + 2)) x",
+            "fun (z : num) -> (z, 3)"
+        ));
+        let nat = "forall a . (a -> a) -> a -> a";
+        assert!(same(
+            &format!(
+                "let two : {nat} = tyfun a -> fun (f : a -> a) -> fun (x : a) -> f (f x) in
+                 let plus : ({nat}) -> ({nat}) -> ({nat}) =
+                   fun (m : {nat}) -> fun (n : {nat}) ->
+                     (tyfun a -> fun (f : a -> a) -> fun (x : a) -> m [a] f (n [a] f x)) in
+                 plus two two"
+            ),
+            "tyfun b -> fun (g : b -> b) -> fun (y : b) -> g (g (g (g y)))"
+        ));
+    }
+
+    #[test]
+    fn eta() {
+        assert!(same(
+            "fun (f : num -> num) -> f",
+            "fun (f : num -> num) -> fun (x : num) -> f x"
+        ));
+        assert!(same(
+            "fun (p : num * bool) -> p",
+            "fun (p : num * bool) -> (p.L, p.R)"
+        ));
+        assert!(same(
+            "fun (f : forall a . a -> a) -> f",
+            "fun (f : forall a . a -> a) -> (tyfun b -> f [b])"
+        ));
+    }
+
+    #[test]
+    fn stuck_branches() {
+        // applying a stuck `case` applies each branch
+        assert!(same(
+            "fun (s : num + num) -> fun (x : num) -> (case s {L(a) -> (fun (y : num) -> a + y) | R(b) -> (fun (y : num) -> b)}) x",
+            "fun (s : num + num) -> fun (x : num) -> case s {L(a) -> a + x | R(b) -> b}"
+        ));
+        let ast = parse("fun (b : bool) -> (if b then (1, 2) else (3, 4)).L").unwrap();
+        let tau = type_check(&ast).unwrap();
+        assert!(Expr::alpha_equiv(
+            normal_form(&ast, &tau).unwrap(),
+            *parse("fun (c : bool) -> if c then 1 else 3").unwrap()
+        ));
+    }
+
+    #[test]
+    fn different() {
+        assert!(!same("fun (x : num) -> x + 1", "fun (x : num) -> 1 + x"));
+        // there is no eta for booleans
+        assert!(!same(
+            "fun (b : bool) -> b",
+            "fun (b : bool) -> if b then true else false"
+        ));
+        let (left, right) = (parse("(1, 2)").unwrap(), parse("(1, 1 + 1)").unwrap());
+        let tau = type_check(&left).unwrap();
+        assert!(matches!(
+            equiv(&left, &right, &tau).unwrap(),
+            Equivalence::Equivalent(_)
+        ));
+        let right = parse("(2, 1)").unwrap();
+        let Equivalence::Different(n1, n2) = equiv(&left, &right, &tau).unwrap() else {
+            panic!()
+        };
+        assert_eq!((n1, n2), (*left, *right));
+    }
+
+    #[test]
+    fn recursion() {
+        let fact = "letrec fact : num -> num = fun (n : num) -> (if n < 1 then 1 else n * (fact (n - 1))) in";
+        assert!(same(&format!("{fact} fact 5"), "120"));
+        // unrolling under a binder never stops
+        let ast = parse(&format!("{fact} fact")).unwrap();
+        let tau = type_check(&ast).unwrap();
+        assert!(normal_form(&ast, &tau).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::evaluate::{Machine, Outcome, StepRecord};
+    use interpreter::flags::Strategy;
+    use interpreter::gradual::elaborate;
+    use interpreter::parser::{parse, parse_with_spans};
+
+    /// The records of every step to a value
+    fn trace(e: &Expr, strategy: Strategy) -> Vec<StepRecord> {
+        let machine = Machine::new(strategy);
+        let mut e = e.clone();
+        let mut records = Vec::new();
+        loop {
+            match machine.step_traced(&e) {
+                (Outcome::Step(next), record) => {
+                    let record = record.unwrap();
+                    if record.thunk.is_none() {
+                        // the path leads to the redex
+                        let mut redex = &e;
+                        for &i in &record.path {
+                            redex = redex.children()[i];
+                        }
+                        assert_eq!(*redex, record.redex);
+                    }
+                    records.push(record);
+                    e = next
+                }
+                (Outcome::Value, record) => {
+                    assert_eq!(record, None);
+                    return records;
+                }
+                (Outcome::Blame(blame), _) => panic!("{blame}"),
+            }
+        }
+    }
+
+    fn rules(records: &[StepRecord]) -> Vec<&'static str> {
+        records.iter().map(|record| record.rule).collect()
+    }
+
+    #[test]
+    fn rules_and_paths() {
+        let ast = parse("let f : num -> num = fun (x : num) -> x * 2 in (f (f 3)) + 1").unwrap();
+        let records = trace(&ast, Strategy::Name);
+        assert_eq!(
+            rules(&records),
+            ["E-AppLam", "E-AppLam", "E-AppLam", "E-Mul", "E-Mul", "E-Add"]
+        );
+        assert_eq!(records[2].path, [0, 0]);
+        assert_eq!(
+            records[2].redex,
+            *parse("(fun (x : num) -> x * 2) 3").unwrap()
+        );
+        assert_eq!(records[2].result, *parse("3 * 2").unwrap());
+
+        let ast = parse(
+            "letrec f : num -> num = fun (n : num) -> (if n < 1 then 0 else (f (n - 1))) in
+             case (inj (f 1) = L as num + bool) {L(n) -> (n, true).L | R(b) -> 0}",
+        )
+        .unwrap();
+        assert_eq!(
+            rules(&trace(&ast, Strategy::Name)),
+            [
+                "E-AppLam",
+                "E-CaseInl",
+                "E-ProjL",
+                "E-Fix",
+                "E-AppLam",
+                "E-Lt",
+                "E-IfFalse",
+                "E-Fix",
+                "E-AppLam",
+                "E-Sub",
+                "E-Lt",
+                "E-IfTrue",
+            ]
+        );
+    }
+
+    #[test]
+    fn thunks() {
+        let ast = parse("(fun (x : num) -> x + x) (1 + 2)").unwrap();
+        let records = trace(&ast, Strategy::Need);
+        assert_eq!(
+            rules(&records),
+            ["E-AppLam", "E-Add", "E-Lookup", "E-Lookup", "E-Add"]
+        );
+        // the first use of `x` evaluates the thunk, at its root
+        assert_eq!(
+            (records[1].thunk, records[1].path.as_slice()),
+            (Some(0), &[][..])
+        );
+        // then each use reads it
+        assert_eq!(
+            (records[2].thunk, records[2].path.as_slice()),
+            (None, &[0][..])
+        );
+        assert_eq!(records[3].path, [1]);
+    }
+
+    #[test]
+    fn casts() {
+        let (ast, spans) = parse_with_spans("(fun (x : ?) -> x + 1) 2").unwrap();
+        let ast = elaborate(&ast, &spans);
+        let rules = rules(&trace(&ast, Strategy::Name));
+        assert!(rules.contains(&"E-CastCollapse"), "{rules:?}");
+        assert_eq!(rules.last(), Some(&"E-Add"));
+    }
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::debugger::{Breakpoint, Debugger};
+    use interpreter::flags::OutputMode;
+    use interpreter::parser::parse;
+
+    const FACT: &str = "letrec fact : num -> num = fun (n : num) -> (if n < 1 then 1 else n * (fact (n - 1))) in fact 3";
+
+    fn debugger(program: &str) -> Debugger {
+        Debugger::new(*parse(program).unwrap(), OutputMode::Simplified)
+    }
+
+    #[test]
+    fn time_travel() {
+        let mut debugger = debugger(FACT);
+        debugger.goto(5);
+        let term = debugger.term().clone();
+        debugger.back(3);
+        assert_eq!(debugger.current(), 2);
+        debugger.goto(5);
+        assert_eq!(*debugger.term(), term);
+        // running off the end stops at the value
+        debugger.goto(1000);
+        assert_eq!(*debugger.term(), Expr::Num(6, NodeId::default()));
+        assert_eq!(debugger.record(), None);
+        assert!(!debugger.forward());
+        debugger.back(1000);
+        assert_eq!(debugger.current(), 0);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = debugger(FACT);
+        debugger.command("break rule E-Fix");
+        debugger.command("break var n");
+        assert_eq!(
+            debugger.breakpoints(),
+            [
+                Breakpoint::Rule(String::from("E-Fix")),
+                Breakpoint::Var(Variable::from("n"))
+            ]
+        );
+        let mut hits = Vec::new();
+        while let Some(i) = debugger.resume() {
+            hits.push(i)
+        }
+        // `fact` is unrolled and applied for 3, 2, 1 and 0
+        assert_eq!(hits, [0, 1, 0, 1, 0, 1, 0, 1]);
+        assert_eq!(*debugger.term(), Expr::Num(6, NodeId::default()));
+    }
+
+    #[test]
+    fn commands() {
+        let mut debugger = debugger("(fun (x : num) -> x * 2) (1 + 2)");
+        assert_eq!(debugger.command("ctx").unwrap(), "□");
+        assert_eq!(
+            debugger.command("s").unwrap(),
+            "step 1: E-Add at [0]\n((1 + 2) * 2)"
+        );
+        assert_eq!(debugger.command("ctx").unwrap(), "(□ * 2)");
+        assert_eq!(
+            debugger.command("redex").unwrap(),
+            "E-Add: (1 + 2)\n  ⟶ 3\n  : num"
+        );
+        assert_eq!(debugger.command("step 5").unwrap(), "step 3: value\n6");
+        assert!(debugger.command("goto x").unwrap().contains("not a number"));
+        assert!(debugger.command("quit").is_none());
+    }
+
+    #[test]
+    fn session() {
+        let mut output = Vec::new();
+        debugger("1 + 2")
+            .run("step\nquit\n".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "step 0: E-Add at []\n(1 + 2)\n(debug) step 1: value\n3\n(debug) "
+        );
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::dap::{read_message, write_message};
+    use serde_json::{json, Value};
+    use std::io::BufReader;
+    use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+    const PROGRAM: &str = "let double : num -> num = fun (x : num) ->
+  x * 2 in
+(double (double 3)) + 1";
+
+    /// A scripted DAP client talking to `interpreter dap`
+    struct Client {
+        child: Child,
+        input: ChildStdin,
+        output: BufReader<ChildStdout>,
+        seq: i64,
+        /// Events received while waiting for responses
+        events: Vec<Value>,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+                .args(["dap", "simplified"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            Client {
+                input: child.stdin.take().unwrap(),
+                output: BufReader::new(child.stdout.take().unwrap()),
+                child,
+                seq: 0,
+                events: Vec::new(),
+            }
+        }
+
+        fn request(&mut self, command: &str, arguments: Value) -> Value {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            write_message(&mut self.input, &request).unwrap();
+            loop {
+                let message = read_message(&mut self.output).unwrap().unwrap();
+                match message["type"].as_str() {
+                    Some("response") => {
+                        assert_eq!(message["request_seq"], self.seq);
+                        return message;
+                    }
+                    _ => self.events.push(message),
+                }
+            }
+        }
+
+        /// Wait for an event, skipping the ones before it
+        fn event(&mut self, event: &str) -> Value {
+            loop {
+                let message = match self.events.is_empty() {
+                    true => read_message(&mut self.output).unwrap().unwrap(),
+                    false => self.events.remove(0),
+                };
+                if message["event"] == event {
+                    return message;
+                }
+            }
+        }
+
+        /// The name and line of the only stack frame
+        fn frame(&mut self) -> (String, u64) {
+            let trace = self.request("stackTrace", json!({"threadId": 1}));
+            let frame = &trace["body"]["stackFrames"][0];
+            (
+                frame["name"].as_str().unwrap().to_string(),
+                frame["line"].as_u64().unwrap(),
+            )
+        }
+
+        fn variables(&mut self, reference: i64) -> Vec<(String, String)> {
+            let response = self.request("variables", json!({"variablesReference": reference}));
+            response["body"]["variables"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| {
+                    let name = v["name"].as_str().unwrap().to_string();
+                    (name, v["value"].as_str().unwrap().to_string())
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn session() {
+        let path = std::env::temp_dir().join(format!("dap-{}.lam", std::process::id()));
+        std::fs::write(&path, PROGRAM).unwrap();
+        let mut client = Client::new();
+
+        let response = client.request("initialize", json!({"adapterID": "lam"}));
+        assert_eq!(response["body"]["supportsStepBack"], true);
+        client.event("initialized");
+        let response = client.request("launch", json!({"program": path, "stopOnEntry": true}));
+        assert_eq!(response["success"], true);
+        let response = client.request(
+            "setBreakpoints",
+            json!({"source": {"path": path}, "breakpoints": [{"line": 2}, {"line": 7}]}),
+        );
+        assert_eq!(
+            response["body"]["breakpoints"],
+            json!([{"verified": true, "line": 2}, {"verified": false, "line": 7}])
+        );
+        client.request("configurationDone", json!({}));
+        assert_eq!(client.event("stopped")["body"]["reason"], "entry");
+        assert_eq!(client.frame(), (String::from("E-AppLam"), 1));
+
+        // the first multiplication comes from `x * 2` on line 2
+        client.request("continue", json!({"threadId": 1}));
+        assert_eq!(client.event("stopped")["body"]["reason"], "breakpoint");
+        assert_eq!(client.frame(), (String::from("E-Mul"), 2));
+        let substitutions = client.variables(1);
+        assert_eq!(substitutions.len(), 2);
+        assert_eq!(substitutions[1], (String::from("x"), String::from("3")));
+        assert!(client
+            .variables(2)
+            .contains(&(String::from("redex"), String::from("(3 * 2)"))));
+
+        client.request("stepBack", json!({"threadId": 1}));
+        client.event("stopped");
+        assert_eq!(client.frame(), (String::from("E-AppLam"), 3));
+        client.request("stepIn", json!({"threadId": 1}));
+        client.event("stopped");
+        client.request("next", json!({"threadId": 1}));
+        client.event("stopped");
+        assert_eq!(client.frame(), (String::from("E-Mul"), 2));
+
+        client.request("continue", json!({"threadId": 1}));
+        let output = client.event("output");
+        assert_eq!(output["body"]["output"], "13\n");
+        client.event("terminated");
+
+        let response = client.request("disconnect", json!({}));
+        assert_eq!(response["success"], true);
+        assert!(client.child.wait().unwrap().success());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn repeated_expressions() {
+        let path = std::env::temp_dir().join(format!("dap-repeated-{}.lam", std::process::id()));
+        std::fs::write(&path, "let x : num = 1 + 1 in\n(1 + 1) * x").unwrap();
+        let mut client = Client::new();
+        client.request("initialize", json!({}));
+        client.request("launch", json!({"program": path, "stopOnEntry": true}));
+        client.request("configurationDone", json!({}));
+        client.event("stopped");
+        assert_eq!(client.frame().1, 1);
+
+        // the two additions are the same term, but come from different lines
+        client.request("stepIn", json!({"threadId": 1}));
+        client.event("stopped");
+        assert_eq!(client.frame(), (String::from("E-Add"), 2));
+        client.request("stepIn", json!({"threadId": 1}));
+        client.event("stopped");
+        assert_eq!(client.frame(), (String::from("E-Add"), 1));
+
+        client.request("disconnect", json!({}));
+        assert!(client.child.wait().unwrap().success());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn launch_errors() {
+        let mut client = Client::new();
+        client.request("initialize", json!({}));
+        let response = client.request("launch", json!({"program": "/nonexistent.lam"}));
+        assert_eq!(response["success"], false);
+        assert!(response["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("I/O error"));
+        let response = client.request("evaluate", json!({"expression": "1"}));
+        assert_eq!(response["success"], false);
+        client.request("disconnect", json!({}));
+        assert!(client.child.wait().unwrap().success());
+    }
+}
@@ -0,0 +1,209 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::dap::{read_message, write_message};
+    use serde_json::{json, Value};
+    use std::io::BufReader;
+    use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+    const URI: &str = "file:///program.lam";
+
+    const PROGRAM: &str = "let double : num -> num = fun (x : num) ->
+  x * 2 in
+case inj (double 3) = L as num + bool { L (n) -> (double n) | R (b) -> 0 }";
+
+    /// The position of the `nth` occurrence of `needle` on line `line` of `PROGRAM`
+    fn at(line: usize, needle: &str, nth: usize) -> Value {
+        let text = PROGRAM.lines().nth(line).unwrap();
+        let (character, _) = text.match_indices(needle).nth(nth).unwrap();
+        json!({"line": line, "character": character})
+    }
+
+    fn range(line: usize, start: usize, end: usize) -> Value {
+        json!({"start": {"line": line, "character": start}, "end": {"line": line, "character": end}})
+    }
+
+    /// A scripted LSP client talking to `interpreter lsp`
+    struct Client {
+        child: Child,
+        input: ChildStdin,
+        output: BufReader<ChildStdout>,
+        id: i64,
+        /// Notifications received while waiting for responses
+        notifications: Vec<Value>,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+                .args(["lsp", "simplified"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            Client {
+                input: child.stdin.take().unwrap(),
+                output: BufReader::new(child.stdout.take().unwrap()),
+                child,
+                id: 0,
+                notifications: Vec::new(),
+            }
+        }
+
+        fn request(&mut self, method: &str, params: Value) -> Value {
+            self.id += 1;
+            let request =
+                json!({"jsonrpc": "2.0", "id": self.id, "method": method, "params": params});
+            write_message(&mut self.input, &request).unwrap();
+            loop {
+                let message = read_message(&mut self.output).unwrap().unwrap();
+                match message.get("id") {
+                    Some(id) => {
+                        assert_eq!(id, self.id);
+                        return message;
+                    }
+                    None => self.notifications.push(message),
+                }
+            }
+        }
+
+        fn notify(&mut self, method: &str, params: Value) {
+            let notification = json!({"jsonrpc": "2.0", "method": method, "params": params});
+            write_message(&mut self.input, &notification).unwrap();
+        }
+
+        /// Open or change the document and wait for its diagnostics
+        fn diagnostics(&mut self, method: &str, text: &str) -> Value {
+            let params = match method {
+                "textDocument/didOpen" => json!({"textDocument": {
+                    "uri": URI, "languageId": "lam", "version": 1, "text": text,
+                }}),
+                _ => json!({
+                    "textDocument": {"uri": URI, "version": 2},
+                    "contentChanges": [{"text": text}],
+                }),
+            };
+            self.notify(method, params);
+            let message = match self.notifications.is_empty() {
+                true => read_message(&mut self.output).unwrap().unwrap(),
+                false => self.notifications.remove(0),
+            };
+            assert_eq!(message["method"], "textDocument/publishDiagnostics");
+            assert_eq!(message["params"]["uri"], URI);
+            message["params"]["diagnostics"].clone()
+        }
+
+        fn at(&mut self, method: &str, position: Value) -> Value {
+            let params = json!({
+                "textDocument": {"uri": URI},
+                "position": position,
+                "context": {"includeDeclaration": true},
+            });
+            self.request(method, params)["result"].clone()
+        }
+
+        fn hover(&mut self, position: Value) -> Value {
+            self.at("textDocument/hover", position)["contents"]["value"].clone()
+        }
+    }
+
+    #[test]
+    fn navigation() {
+        let mut client = Client::new();
+        let response = client.request("initialize", json!({"capabilities": {}}));
+        assert_eq!(response["result"]["capabilities"]["hoverProvider"], true);
+        client.notify("initialized", json!({}));
+        assert_eq!(
+            client.diagnostics("textDocument/didOpen", PROGRAM),
+            json!([])
+        );
+
+        assert_eq!(client.hover(at(1, "x", 0)), "x : num");
+        assert_eq!(client.hover(at(2, "double", 1)), "double : num → num");
+        assert_eq!(client.hover(at(2, "n)", 0)), "n : num");
+        assert_eq!(client.hover(at(1, "*", 0)), "num");
+        assert_eq!(client.hover(at(2, "inj", 0)), "num + bool");
+
+        let definition = client.at("textDocument/definition", at(2, "n)", 1));
+        assert_eq!(definition, json!({"uri": URI, "range": range(2, 43, 44)}));
+        let references = client.at("textDocument/references", at(0, "double", 0));
+        let ranges: Vec<Value> = references
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|location| location["range"].clone())
+            .collect();
+        assert_eq!(
+            ranges,
+            [range(0, 4, 10), range(2, 10, 16), range(2, 50, 56)]
+        );
+
+        let symbols = client.request(
+            "textDocument/documentSymbol",
+            json!({"textDocument": {"uri": URI}}),
+        );
+        assert_eq!(
+            symbols["result"],
+            json!([{
+                "name": "double",
+                "detail": "num → num",
+                "kind": 13,
+                "range": {"start": {"line": 0, "character": 0}, "end": {"line": 1, "character": 7}},
+                "selectionRange": range(0, 4, 10),
+            }])
+        );
+
+        client.request("shutdown", Value::Null);
+        client.notify("exit", Value::Null);
+        assert!(client.child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn binder_after_comment() {
+        let mut client = Client::new();
+        client.request("initialize", json!({"capabilities": {}}));
+        client.diagnostics(
+            "textDocument/didOpen",
+            "let # x is one\nx : num = 1 in\nx + 1",
+        );
+        let position = json!({"line": 2, "character": 0});
+        let definition = client.at("textDocument/definition", position);
+        assert_eq!(definition, json!({"uri": URI, "range": range(1, 0, 1)}));
+        client.request("shutdown", Value::Null);
+        client.notify("exit", Value::Null);
+        assert!(client.child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut client = Client::new();
+        client.request("initialize", json!({"capabilities": {}}));
+        let diagnostics =
+            client.diagnostics("textDocument/didOpen", "let y : num = 1 in\ny + true");
+        assert_eq!(diagnostics[0]["range"], range(1, 0, 8));
+        assert!(diagnostics[0]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Type error: Type mismatch in +"));
+
+        let diagnostics = client.diagnostics("textDocument/didChange", "1 +");
+        assert_eq!(diagnostics[0]["range"], range(0, 3, 3));
+        assert!(diagnostics[0]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Parse error"));
+
+        let diagnostics = client.diagnostics("textDocument/didChange", "z");
+        assert_eq!(diagnostics[0]["range"], range(0, 0, 1));
+        assert_eq!(diagnostics[0]["message"], "Type error: Free variable: z");
+        assert_eq!(
+            client.diagnostics("textDocument/didChange", "1 + 2"),
+            json!([])
+        );
+
+        let response = client.request("workspace/symbol", json!({"query": ""}));
+        assert_eq!(response["error"]["code"], -32601);
+        client.request("shutdown", Value::Null);
+        client.notify("exit", Value::Null);
+        assert!(client.child.wait().unwrap().success());
+    }
+}
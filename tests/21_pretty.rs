@@ -0,0 +1,325 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::Rng;
+    use interpreter::ast::*;
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::pretty::{pretty, pretty_type, pretty_width};
+
+    impl Rng {
+        fn var(&mut self) -> Variable {
+            Variable::from(["x", "y", "f", "acc", "x1"][self.below(5) as usize])
+        }
+
+        fn direction(&mut self) -> Direction {
+            match self.below(2) {
+                0 => Direction::Left,
+                _ => Direction::Right,
+            }
+        }
+
+        /// A type whose variables are bound by `scope` or free type variables
+        fn ty(&mut self, depth: u32, scope: &mut Vec<Variable>) -> Box<Type> {
+            let leaves = 4 + scope.len() as u64;
+            let choice = match depth {
+                0 => self.below(leaves),
+                _ => self.below(leaves + 7),
+            };
+            let mut binary = |rng: &mut Self| (rng.ty(depth - 1, scope), rng.ty(depth - 1, scope));
+            Box::new(match choice {
+                0 => Type::Num,
+                1 => Type::Bool,
+                2 => Type::Unit,
+                3 => Type::Var(Variable::from("b")),
+                n if n < leaves => Type::Var(scope[(n - 4) as usize].clone()),
+                n => match n - leaves {
+                    0 => {
+                        let (arg, ret) = binary(self);
+                        Type::Fn { arg, ret }
+                    }
+                    1 => {
+                        let (arg, ret) = binary(self);
+                        Type::LinFn { arg, ret }
+                    }
+                    2 => {
+                        let (left, right) = binary(self);
+                        Type::Product { left, right }
+                    }
+                    3 => {
+                        let (left, right) = binary(self);
+                        Type::Sum { left, right }
+                    }
+                    4 => Type::Dyn,
+                    q => {
+                        let a = Variable::from(["a", "t"][self.below(2) as usize]);
+                        scope.push(a.clone());
+                        let tau = *self.ty(depth - 1, scope);
+                        scope.pop();
+                        match q {
+                            5 => Type::rec(a, tau),
+                            6 => Type::forall(a, tau),
+                            _ => Type::exists(a, tau),
+                        }
+                    }
+                },
+            })
+        }
+
+        /// A term without casts, which have no concrete syntax
+        fn expr(&mut self, depth: u32) -> Box<Expr> {
+            let e = match depth {
+                0 => match self.below(5) {
+                    0 => Expr::Num(self.below(100) as i32, NodeId::default()),
+                    1 => Expr::True(NodeId::default()),
+                    2 => Expr::False(NodeId::default()),
+                    3 => Expr::Unit(NodeId::default()),
+                    _ => Expr::Var(self.var(), NodeId::default()),
+                },
+                _ => {
+                    let d = depth - 1;
+                    match self.below(24) {
+                        0 => Expr::Addop {
+                            binop: [AddOp::Add, AddOp::Sub][self.below(2) as usize].clone(),
+                            left: self.expr(d),
+                            right: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        1 => Expr::Mulop {
+                            binop: [MulOp::Mul, MulOp::Div][self.below(2) as usize].clone(),
+                            left: self.expr(d),
+                            right: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        2 => Expr::Relop {
+                            relop: [RelOp::Lt, RelOp::Gt, RelOp::Eq][self.below(3) as usize]
+                                .clone(),
+                            left: self.expr(d),
+                            right: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        3 => Expr::And {
+                            left: self.expr(d),
+                            right: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        4 => Expr::Or {
+                            left: self.expr(d),
+                            right: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        5 => Expr::If {
+                            cond: self.expr(d),
+                            then_: self.expr(d),
+                            else_: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        6 => Expr::Lam {
+                            x: self.var(),
+                            tau: self.ty(2, &mut Vec::new()),
+                            e: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        7 => Expr::LinLam {
+                            x: self.var(),
+                            tau: self.ty(2, &mut Vec::new()),
+                            e: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        8 | 9 => Expr::App {
+                            lam: self.expr(d),
+                            arg: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        10 => Expr::Pair {
+                            left: self.expr(d),
+                            right: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        11 => Expr::Project {
+                            e: self.expr(d),
+                            d: self.direction(),
+                            id: NodeId::default(),
+                        },
+                        12 => Expr::Inject {
+                            e: self.expr(d),
+                            d: self.direction(),
+                            tau: self.ty(2, &mut Vec::new()),
+                            id: NodeId::default(),
+                        },
+                        13 => Expr::Case {
+                            e: self.expr(d),
+                            xleft: self.var(),
+                            eleft: self.expr(d),
+                            xright: self.var(),
+                            eright: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        14 => Expr::Fix {
+                            x: self.var(),
+                            tau: self.ty(2, &mut Vec::new()),
+                            e: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        15 => Expr::TyLam {
+                            a: Variable::from("b"),
+                            e: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        16 => Expr::TyApp {
+                            e: self.expr(d),
+                            tau: self.ty(2, &mut Vec::new()),
+                            id: NodeId::default(),
+                        },
+                        17 => Expr::Fold {
+                            e: self.expr(d),
+                            tau: self.ty(2, &mut Vec::new()),
+                            id: NodeId::default(),
+                        },
+                        18 => Expr::Unfold(self.expr(d), NodeId::default()),
+                        19 => Expr::Export {
+                            e: self.expr(d),
+                            tau_adt: self.ty(2, &mut Vec::new()),
+                            tau_mod: self.ty(2, &mut Vec::new()),
+                            id: NodeId::default(),
+                        },
+                        20 => Expr::Import {
+                            x: self.var(),
+                            a: Variable::from("b"),
+                            e_mod: self.expr(d),
+                            e_body: self.expr(d),
+                            id: NodeId::default(),
+                        },
+                        // `let` and `letrec`
+                        21 | 22 => {
+                            let x = self.var();
+                            let tau = self.ty(2, &mut Vec::new());
+                            let arg = match self.below(2) {
+                                0 => self.expr(d),
+                                _ => Box::new(Expr::Fix {
+                                    x: x.clone(),
+                                    tau: tau.clone(),
+                                    e: self.expr(d),
+                                    id: NodeId::default(),
+                                }),
+                            };
+                            Expr::App {
+                                lam: Box::new(Expr::Lam {
+                                    x,
+                                    tau,
+                                    e: self.expr(d),
+                                    id: NodeId::default(),
+                                }),
+                                arg,
+                                id: NodeId::default(),
+                            }
+                        }
+                        _ => Expr::Var(self.var(), NodeId::default()),
+                    }
+                }
+            };
+            Box::new(e)
+        }
+    }
+
+    fn canonical(program: &str) -> String {
+        pretty(&parse(program).unwrap())
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        for program in [
+            "1 + 2 * 3",
+            "(1 + 2) * 3",
+            "1 - (2 - 3)",
+            "f x y",
+            "f (g x)",
+            "((x, y).L).R",
+            "inj (f 1) = L as num + bool",
+            "if x < 1 then (f x) else (fun (y : num) -> y)",
+            "case x { L (y) -> y | R (y) -> case y { L (z) -> z | R (z) -> z } }",
+            "let x : num = 1 in let y : num = x in y",
+            "let x : num = (letrec f : num -> num = fun (y : num) -> f y in f) in x",
+            "tyfun b -> fun (f : (num -> b) -> num * b + bool) -> f",
+            "fun (x : forall a . a -> (exists t . t)) -> x",
+        ] {
+            assert_eq!(canonical(program), program);
+        }
+        assert_eq!(canonical("(fun (x : num) -> x) 1"), "let x : num = 1 in x");
+        assert_eq!(canonical("((((1))))"), "1");
+        assert_eq!(canonical("x == 1"), "x == 1");
+    }
+
+    #[test]
+    fn types() {
+        for tau in [
+            "(num -> num) -> num",
+            "num -o num -o num",
+            "num * num * num",
+            "(num * num) * num",
+            "(num + num) + num",
+            "(num * num) + num",
+            "rec a . unit + a * a",
+            "forall a . forall a1 . a -> a1",
+            "num -> (forall a . a)",
+        ] {
+            assert_eq!(pretty_type(&parse_type(tau).unwrap()), tau);
+        }
+        assert_eq!(pretty_type(&Type::Unit), "unit");
+        // binders that would capture a free variable are renamed
+        let tau = Type::forall(
+            Variable::from("a"),
+            Type::Fn {
+                arg: Box::new(Type::Var(Variable::from("a"))),
+                ret: Box::new(Type::Var(Variable::from("b"))),
+            },
+        );
+        let tau = Type::forall(Variable::from("b"), tau);
+        assert_eq!(pretty_type(&tau), "forall b . forall a . a -> b");
+        let shadowed = Type::forall(
+            Variable::from("a"),
+            Type::forall(Variable::from("a"), Type::Var(Variable::from("a"))),
+        );
+        assert_eq!(pretty_type(&shadowed), "forall a . forall a1 . a1");
+    }
+
+    #[test]
+    fn width() {
+        let e = parse("let x : num = 1 + 2 in if x < 3 then x * x else (f x)").unwrap();
+        assert_eq!(
+            pretty_width(&e, 30),
+            "let x : num = 1 + 2 in\nif x < 3 then x * x else (f x)"
+        );
+        assert_eq!(
+            pretty_width(&e, 24),
+            "let x : num = 1 + 2 in\nif x < 3\nthen x * x\nelse (f x)"
+        );
+        assert_eq!(
+            pretty_width(&e, 20),
+            "let x : num =\n  1 + 2 in\nif x < 3\nthen x * x\nelse (f x)"
+        );
+        let e = parse("case (f x) { L (y) -> (g y y y) | R (z) -> z }").unwrap();
+        assert_eq!(
+            pretty_width(&e, 20),
+            "case (f x) {\n  L (y) -> (g y y y)\n| R (z) -> z\n}"
+        );
+        assert_eq!(
+            pretty_width(&e, 10),
+            "case (f x) {\n  L (y) -> (g\n              y\n              y\n              y)\n| R (z) -> z\n}"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..500 {
+            let e = rng.expr(4);
+            for width in [80, 30, 0] {
+                let printed = pretty_width(&e, width);
+                let parsed =
+                    parse(&printed).unwrap_or_else(|err| panic!("{printed}\ndoesn't parse: {err}"));
+                assert_eq!(parsed, e, "{printed}");
+            }
+        }
+    }
+}
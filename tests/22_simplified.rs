@@ -0,0 +1,76 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::list;
+    use interpreter::doc::{align, group, line, nest, text};
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::pretty::{simplified, simplified_type};
+
+    #[test]
+    fn documents() {
+        let doc = group(text("f") + nest(2, line() + "x" + line() + "y"));
+        assert_eq!(doc.flat(), "f x y");
+        assert_eq!(doc.render(5), "f x y");
+        assert_eq!(doc.render(4), "f\n  x\n  y");
+        // a group fits when the text up to the next break after it does
+        let doc = group(text("ab") + line() + "c") + "de";
+        assert_eq!(doc.render(6), "ab cde");
+        assert_eq!(doc.render(5), "ab\ncde");
+        let doc = text("let ") + align(group(text("x") + line() + "y"));
+        assert_eq!(doc.render(3), "let x\n    y");
+    }
+
+    #[test]
+    fn lists() {
+        let e = parse(&list(&[1, 2, 3])).unwrap();
+        assert_eq!(
+            e.to_string(),
+            "fold (1 , fold (2 , fold (3 , fold () as ...) as ...) as ...) as ..."
+        );
+        assert_eq!(
+            simplified(&e, 50),
+            "fold (1 ,\nfold (2 ,\nfold (3 , fold () as ...) as ...) as ...) as ..."
+        );
+        // elements line up with the first one, however deep the list is
+        let e = parse(&format!("fun (x : num) -> {}", list(&[1, 2]))).unwrap();
+        assert_eq!(
+            simplified(&e, 20),
+            "λ (x : num) ->\n  fold (1 ,\n  fold (2 ,\n  fold () as ...) as ...) as ..."
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        let e = parse("(1, (true, ((), (2, false))))").unwrap();
+        assert_eq!(e.to_string(), "(1 , (true , (() , (2 , false))))");
+        assert_eq!(simplified(&e, 16), "(1 ,\n(true ,\n(() ,\n(2 , false))))");
+        let e = parse("((1 + 2, 3), 4)").unwrap();
+        assert_eq!(simplified(&e, 12), "(((1 + 2) ,\n 3) ,\n4)");
+    }
+
+    #[test]
+    fn types() {
+        for (tau, printed) in [
+            ("(num -> num) -> num", "(num → num) → num"),
+            ("num -> num -> num", "num → num → num"),
+            ("num * (bool + unit)", "num * bool + ()"),
+            ("(num * bool) + unit", "(num * bool) + ()"),
+            ("(num + bool) + unit", "(num + bool) + ()"),
+            ("num -o (forall a . a)", "num ⊸ (∀ a . a)"),
+            ("rec l . unit + (num * l)", "μ l . () + (num * l)"),
+            ("rec l . unit + num * l", "μ l . () + num * l"),
+        ] {
+            assert_eq!(parse_type(tau).unwrap().to_string(), printed);
+        }
+        let tau = parse_type("(num -> num) -> (bool -> bool) -> unit").unwrap();
+        assert_eq!(
+            simplified_type(&tau, 20),
+            "(num → num) →\n(bool → bool) → ()"
+        );
+        assert_eq!(
+            simplified_type(&tau, 10),
+            "(num →\n num) →\n(bool →\n bool) →\n()"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{list, LIST};
+    use interpreter::evaluate::{eval, force};
+    use interpreter::flags::{Mode, OutputMode};
+    use interpreter::parser::parse;
+    use interpreter::pretty::{value, WIDTH};
+    use interpreter::typecheck::type_check;
+
+    fn run(program: &str, width: usize) -> String {
+        let e = parse(program).unwrap();
+        let tau = type_check(&e).unwrap();
+        let result = force(eval(&e, Mode::Eval, OutputMode::Simplified)).unwrap();
+        value(&result, &tau, width)
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(run(&list(&[1, 2, 3]), WIDTH), "[1, 2, 3]");
+        assert_eq!(run(&list(&[]), WIDTH), "[]");
+        assert_eq!(run(&list(&[10, 20, 30]), 8), "[10,\n 20,\n 30]");
+        let pairs = format!("({}, {})", list(&[1]), list(&[2, 3]));
+        assert_eq!(run(&pairs, WIDTH), "([1], [2, 3])");
+        // a type with the shape of a list whose elements mention the list type isn't one
+        let tree = "rec t . unit + (t * t)";
+        let leaf = format!("fold inj () = L as unit + (({tree}) * ({tree})) as {tree}");
+        let node =
+            format!("fold inj ({leaf}, {leaf}) = R as unit + (({tree}) * ({tree})) as {tree}");
+        assert!(run(&node, WIDTH).starts_with("fold"));
+    }
+
+    #[test]
+    fn recursive_list() {
+        let cell = format!("unit + (num * ({LIST}))");
+        let range = format!(
+            "letrec range : num -> ({LIST}) = fun (n : num) -> \
+               if n < 1 then (fold inj () = L as {cell} as {LIST}) \
+               else (fold inj (n, range (n - 1)) = R as {cell} as {LIST}) \
+             in range 3"
+        );
+        assert_eq!(run(&range, WIDTH), "[3, 2, 1]");
+        // the elements are evaluated too
+        let doubled = format!(
+            "letrec map : ({LIST}) -> ({LIST}) = fun (l : {LIST}) -> \
+               case (unfold l) {{ \
+                 L (u) -> (fold inj () = L as {cell} as {LIST}) \
+               | R (c) -> (fold inj ((c.L) * 2, map (c.R)) = R as {cell} as {LIST}) \
+               }} \
+             in map ({})",
+            list(&[1, 2, 3])
+        );
+        assert_eq!(run(&doubled, WIDTH), "[2, 4, 6]");
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(run("inj () = L as unit + num", WIDTH), "None");
+        assert_eq!(run("inj 4 = R as unit + num", WIDTH), "Some 4");
+        let nested = "inj (inj 4 = R as unit + num) = R as unit + (unit + num)";
+        assert_eq!(run(nested, WIDTH), "Some (Some 4)");
+        assert_eq!(
+            run("inj (1, true) = R as unit + (num * bool)", WIDTH),
+            "Some (1, true)"
+        );
+        // other sums print their contents
+        assert_eq!(run("inj 1 = R as bool + num", WIDTH), "1");
+    }
+
+    #[test]
+    fn tuples() {
+        assert_eq!(run("(1, (true, ()))", WIDTH), "(1, true, ())");
+        assert_eq!(run("((1, 2), 3)", WIDTH), "((1, 2), 3)");
+        assert_eq!(run("(100, (200, 300))", 10), "(100,\n 200,\n 300)");
+        // unevaluated components print as they are
+        let lazy = "(1, (fun (x : num) -> x) 2)";
+        let e = parse(lazy).unwrap();
+        let tau = type_check(&e).unwrap();
+        assert_eq!(value(&e, &tau, WIDTH), "(1, (λ (x : num) -> x 2))");
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::serialize::*;
+    use interpreter::span::Span;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    const PROGRAMS: [&str; 9] = [
+        "let x : num = 1 + 2 * 3 in if x < 7 || x == 7 && true then x / 2 - 1 else 0",
+        "fun (x : unit) -o (x, false)",
+        "((1, 2).L, inj () = R as num + unit)",
+        "case inj 1 = L as num + bool { L (n) -> n > 0 | R (b) -> b }",
+        "letrec f : num -> num = fun (n : num) -> if n == 0 then 1 else n * (f (n - 1)) in f 5",
+        "tyfun a -> fun (x : forall b . a -> b * (exists c . c)) -> x",
+        "unfold fold inj () = L as unit + (num * (rec l . unit + (num * l))) as rec l . unit + (num * l)",
+        "import (m, t) = export (1, fun (x : num) -> x) without num as t * (t -> num) in m.R m.L",
+        "let (n, b) = (1, true) in if b then n else 0",
+    ];
+
+    #[test]
+    fn schema() {
+        let e = parse("(fun (x : num) -> x) 1").unwrap();
+        assert_eq!(
+            expr_to_json(&e).to_string(),
+            r#"{"arg":{"kind":"num","value":1},"kind":"app","lam":{"e":{"kind":"var","name":"x"},"kind":"lam","tau":{"kind":"num"},"x":"x"}}"#
+        );
+        assert_eq!(expr_to_sexp(&e), "(app (lam x num (var x)) (num 1))");
+        let tau = parse_type("forall a . a -> b").unwrap();
+        assert_eq!(type_to_sexp(&tau), "(forall a (fn (bound 0) (var b)))");
+        assert_eq!(
+            type_to_json(&tau).to_string(),
+            r#"{"a":"a","kind":"forall","tau":{"arg":{"index":0,"kind":"bound"},"kind":"fn","ret":{"kind":"var","name":"b"}}}"#
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for program in PROGRAMS {
+            let e = parse(program).unwrap();
+            assert_eq!(expr_from_json(&expr_to_json(&e).to_string()).unwrap(), e);
+            assert_eq!(expr_from_sexp(&expr_to_sexp(&e)).unwrap(), e);
+        }
+        // casts have no concrete syntax, and blame labels need quotes
+        let cast = Expr::Cast {
+            e: Box::new(Expr::Num(-3, NodeId::default())),
+            from: Box::new(Type::Num),
+            to: Box::new(Type::Dyn),
+            blame: Blame {
+                label: String::from("cast at 1:2 \"x\""),
+                span: Some(Span { start: 1, end: 2 }),
+                polarity: Polarity::Negative,
+            },
+            id: NodeId::default(),
+        };
+        let sexp = expr_to_sexp(&cast);
+        assert_eq!(
+            sexp,
+            r#"(cast (num -3) num dyn (blame "cast at 1:2 \"x\"" negative 1 2))"#
+        );
+        assert_eq!(*expr_from_sexp(&sexp).unwrap(), cast);
+        assert_eq!(
+            *expr_from_json(&expr_to_json(&cast).to_string()).unwrap(),
+            cast
+        );
+        for tau in ["rec l . unit + (num * l)", "num -o (exists a . a) + bool"] {
+            let tau = parse_type(tau).unwrap();
+            assert_eq!(
+                type_from_json(&type_to_json(&tau).to_string()).unwrap(),
+                tau
+            );
+            assert_eq!(type_from_sexp(&type_to_sexp(&tau)).unwrap(), tau);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            expr_from_sexp("(num 1) 2").unwrap_err(),
+            "Unexpected '2' after the S-expression"
+        );
+        assert_eq!(
+            expr_from_sexp("(pair (num 1))").unwrap_err(),
+            "Missing field right in (pair (num 1))"
+        );
+        assert_eq!(
+            expr_from_json(r#"{"kind": "quote"}"#).unwrap_err(),
+            "Unknown expression kind: quote"
+        );
+        assert_eq!(
+            expr_from_json(r#"{"kind": "num", "value": 1e100}"#).unwrap_err(),
+            r#"Expected a number in range, found 1e+100"#
+        );
+        assert!(expr_from_json("{").unwrap_err().starts_with("Invalid JSON"));
+    }
+
+    #[test]
+    fn input_format() {
+        let e = parse(PROGRAMS[4]).unwrap();
+        for (format, input) in [
+            ("json", expr_to_json(&e).to_string()),
+            ("sexp", expr_to_sexp(&e)),
+        ] {
+            let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+                .args(["verbose", format, "--input-format", format])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(input.as_bytes())
+                .unwrap();
+            let output = child.wait_with_output().unwrap();
+            let output = String::from_utf8(output.stdout).unwrap();
+            let lines: Vec<&str> = output.lines().collect();
+            let (ty, result) = match format {
+                "json" => (
+                    type_to_json(&Type::Num).to_string(),
+                    expr_to_json(&Expr::Num(120, NodeId::default())).to_string(),
+                ),
+                _ => (
+                    type_to_sexp(&Type::Num),
+                    expr_to_sexp(&Expr::Num(120, NodeId::default())),
+                ),
+            };
+            assert_eq!(lines, [ty, result]);
+        }
+    }
+}
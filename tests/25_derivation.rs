@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::ast::*;
+    use interpreter::derivation::{to_latex, to_text, LatexStyle};
+    use interpreter::flags::OutputMode;
+    use interpreter::parser::parse;
+    use interpreter::typecheck::{type_check, type_check_derivation, Assumption, Derivation};
+
+    fn derive(program: &str) -> Derivation {
+        type_check_derivation(&parse(program).unwrap(), None).unwrap()
+    }
+
+    fn rules(d: &Derivation) -> Vec<&'static str> {
+        let mut names = vec![d.rule];
+        names.extend(d.premises.iter().flat_map(rules));
+        names
+    }
+
+    #[test]
+    fn derivations() {
+        let program = "tyfun a -> fun (x : a) -> (x, if true then 1 else 2)";
+        let d = derive(program);
+        assert_eq!(d.tau, type_check(&parse(program).unwrap()).unwrap());
+        assert_eq!(
+            rules(&d),
+            ["T-TyLam", "T-Lam", "T-Pair", "T-Var", "T-If", "T-True", "T-Num", "T-Num"]
+        );
+        let var = &d.premises[0].premises[0].premises[0];
+        assert_eq!(
+            var.context,
+            [
+                Assumption::TyVar(Variable::from("a")),
+                Assumption::Var(Variable::from("x"), Type::Var(Variable::from("a")))
+            ]
+        );
+        assert_eq!(var.e, Expr::Var(Variable::from("x"), NodeId::default()));
+        // the branches of a case see their own variables
+        let d = derive("case inj 1 = L as num + bool { L (n) -> n | R (b) -> 0 }");
+        assert_eq!(rules(&d), ["T-Case", "T-InjL", "T-Num", "T-Var", "T-Num"]);
+        assert_eq!(
+            d.premises[2].context,
+            [Assumption::Var(Variable::from("b"), Type::Bool)]
+        );
+        assert!(type_check_derivation(&parse("1 + true").unwrap(), None).is_err());
+    }
+
+    #[test]
+    fn text() {
+        let d = derive("let f : num -> num = fun (x : num) -> x + 1 in f 2");
+        assert_eq!(
+            to_text(&d, OutputMode::Simplified),
+            "\
+T-App ⊢ let f : num -> num = fun (x : num) -> x + 1 in f 2 : num
+├── T-Lam ⊢ fun (f : num -> num) -> f 2 : (num → num) → num
+│   └── T-App f : num → num ⊢ f 2 : num
+│       ├── T-Var f : num → num ⊢ f : num → num
+│       └── T-Num f : num → num ⊢ 2 : num
+└── T-Lam ⊢ fun (x : num) -> x + 1 : num → num
+    └── T-Add x : num ⊢ x + 1 : num
+        ├── T-Var x : num ⊢ x : num
+        └── T-Num x : num ⊢ 1 : num"
+        );
+    }
+
+    #[test]
+    fn latex() {
+        let d = derive("fun (x_1 : num) -> x_1");
+        assert_eq!(
+            to_latex(&d, LatexStyle::Bussproofs),
+            r"\begin{prooftree}
+\AxiomC{}
+\RightLabel{\scriptsize T-Var}
+\UnaryInfC{$\texttt{x\_1} : \mathsf{num} \vdash \texttt{x\_1} : \mathsf{num}$}
+\RightLabel{\scriptsize T-Lam}
+\UnaryInfC{$\vdash \texttt{fun (x\_1 : num) -> x\_1} : \mathsf{num} \to \mathsf{num}$}
+\end{prooftree}"
+        );
+        let d = derive("(1, true)");
+        assert_eq!(
+            to_latex(&d, LatexStyle::Mathpartir),
+            r"\begin{mathpar}
+  \inferrule*[right=T-Pair]
+    {
+      \inferrule*[right=T-Num]
+        { }
+        {\vdash \texttt{1} : \mathsf{num}} \\
+      \inferrule*[right=T-True]
+        { }
+        {\vdash \texttt{true} : \mathsf{bool}}
+    }
+    {\vdash \texttt{(1, true)} : \mathsf{num} \times \mathsf{bool}}
+\end{mathpar}"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::derivation::to_dot;
+    use interpreter::dotgen::type_to_dot;
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::typecheck::type_check_derivation;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// The `(from, to)` nodes of the edge with `label`
+    fn edge(graph: &str, label: &str) -> (String, String) {
+        let line = graph
+            .lines()
+            .find(|line| line.contains(&format!("[label=\"{label}\"")))
+            .unwrap();
+        let (from, rest) = line.trim().split_once(" -> ").unwrap();
+        let (to, _) = rest.split_once(' ').unwrap();
+        (from.to_string(), to.to_string())
+    }
+
+    fn run(args: &[&str], program: &str) -> String {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(program.as_bytes()).unwrap();
+        drop(stdin);
+        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    }
+
+    #[test]
+    fn types() {
+        let graph = type_to_dot(&parse_type("rec l . unit + (num * l)").unwrap(), None);
+        assert!(graph.starts_with("digraph {"));
+        // the bound variable is an edge from the product back to the binder
+        let back = graph.lines().filter(|line| line.contains("dashed"));
+        assert_eq!(back.count(), 1);
+        let (_, rec) = edge(&graph, "μ");
+        let (_, product) = edge(&graph, "*");
+        assert_eq!(edge(&graph, "l").0, rec);
+        let back = graph.lines().find(|line| line.contains("dashed")).unwrap();
+        assert!(back.contains(&format!("{product} -> {rec} [label=\"l\", style=dashed")));
+        // free variables are leaves
+        let graph = type_to_dot(
+            &parse_type("forall a . a -> b").unwrap(),
+            Some(String::from("t")),
+        );
+        assert!(graph.starts_with("subgraph t {"));
+        assert!(graph.contains("[label=\"b\", arrowhead=none"));
+        assert!(graph.contains("[label=\"a\", style=dashed"));
+    }
+
+    #[test]
+    fn derivations() {
+        let d = type_check_derivation(&parse("(1, true)").unwrap(), None).unwrap();
+        assert_eq!(
+            to_dot(&d),
+            "\
+digraph Derivation {
+\tnode [shape=box];
+\trankdir=BT;
+\t0 [label=\"T-Pair\\n⊢ (1, true) : num * bool\"];
+\t1 [label=\"T-Num\\n⊢ 1 : num\"];
+\t1 -> 0;
+\t2 [label=\"T-True\\n⊢ true : bool\"];
+\t2 -> 0;
+}"
+        );
+    }
+
+    #[test]
+    fn verbose() {
+        let output = run(&["verbose", "graphviz"], "fun (x : num) -> x");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[..2], ["digraph Program {", "subgraph type {"]);
+        assert!(output.contains("subgraph last {"));
+        assert_eq!(lines.last(), Some(&"}"));
+        let output = run(&["derivation", "graphviz"], "1");
+        assert!(output.starts_with("digraph Derivation {"));
+    }
+}
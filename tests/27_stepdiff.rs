@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::evaluate::try_eval_frames;
+    use interpreter::flags::Strategy;
+    use interpreter::parser::parse;
+    use interpreter::stepdiff::{html, write_frames, Frame, Mark, Tree};
+    use std::fs;
+
+    fn frames(program: &str, strategy: Strategy) -> Vec<Frame> {
+        let (frames, result) = try_eval_frames(&parse(program).unwrap(), strategy);
+        assert!(result.is_ok());
+        frames
+    }
+
+    /// The labels of the nodes marked `mark`, in preorder
+    fn marked(tree: &Tree, mark: Mark) -> Vec<String> {
+        let mut labels = match tree.mark == mark {
+            true => vec![tree.label.clone()],
+            false => vec![],
+        };
+        for child in &tree.children {
+            labels.extend(marked(child, mark));
+        }
+        labels
+    }
+
+    #[test]
+    fn marks() {
+        let steps = frames("(fun (x : num) -> x + 1) 2", Strategy::Name);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].caption, "step 1: E-AppLam at []");
+        assert_eq!(
+            marked(&steps[0].before, Mark::Redex),
+            ["app", "λ x", "+", "x", "1", "2"]
+        );
+        // the argument and the constant are copies, the sum is new
+        assert_eq!(marked(&steps[0].after, Mark::Created), ["+"]);
+        assert_eq!(marked(&steps[1].after, Mark::Created), ["3"]);
+        // a result equal to an operand is still new
+        let steps = frames("1 * ((fun (y : num) -> y) 1)", Strategy::Name);
+        assert_eq!(marked(&steps[0].after, Mark::Created), [] as [&str; 0]);
+        assert_eq!(marked(&steps[1].after, Mark::Created), ["1"]);
+        // a step inside the term leaves the context plain
+        let steps = frames("(1 + 2, 3).L", Strategy::Value);
+        assert_eq!(steps[0].caption, "step 1: E-Add at [0, 0]");
+        assert_eq!(marked(&steps[0].before, Mark::Redex), ["+", "1", "2"]);
+        assert_eq!(marked(&steps[0].before, Mark::Plain), [".L", "pair", "3"]);
+        assert_eq!(
+            marked(&steps[1].before, Mark::Redex),
+            [".L", "pair", "3", "3"]
+        );
+        // under call-by-need the thunk is drawn
+        let steps = frames("(fun (x : num) -> x * x) (1 + 2)", Strategy::Need);
+        assert!(steps.iter().any(|frame| frame.caption.contains(" in ℓ0 ")));
+    }
+
+    #[test]
+    fn outputs() {
+        let steps = frames("if 1 < 2 then 3 else 4", Strategy::Name);
+        let page = html(&steps);
+        assert_eq!(page.matches("<div class=\"frame").count(), steps.len());
+        assert_eq!(page.matches("<svg").count(), steps.len());
+        assert!(page.contains("step 2: E-IfTrue at []"));
+        assert!(html(&[]).contains("no steps"));
+        let dir = std::env::temp_dir().join(format!("stepdiff-{}", std::process::id()));
+        write_frames(&steps, &dir).unwrap();
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["step1.svg", "step2.svg"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("step1.svg")).unwrap(),
+            steps[0].svg()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::svggen::{expr_tree, to_html, to_svg, type_tree, Mark, Tree};
+
+    /// The `(x, y, width)` of each node, in preorder
+    fn boxes(svg: &str) -> Vec<(f64, f64, f64)> {
+        let attribute = |line: &str, name: &str| -> f64 {
+            let start = line.find(&format!(" {name}=\"")).unwrap() + name.len() + 3;
+            let end = start + line[start..].find('"').unwrap();
+            line[start..end].parse().unwrap()
+        };
+        svg.lines()
+            .filter(|line| line.starts_with("<rect"))
+            .map(|line| {
+                (
+                    attribute(line, "x"),
+                    attribute(line, "y"),
+                    attribute(line, "width"),
+                )
+            })
+            .collect()
+    }
+
+    fn labels(tree: &Tree) -> Vec<String> {
+        let mut all = vec![tree.label.clone()];
+        all.extend(tree.children.iter().flat_map(labels));
+        all
+    }
+
+    #[test]
+    fn trees() {
+        let tree = expr_tree(&parse("fun (x : num) -> (x, inj x = L as num + bool)").unwrap());
+        assert_eq!(
+            labels(&tree),
+            ["λ x", "num", "pair", "x", "inj L", "x", "+", "num", "bool"]
+        );
+        assert_eq!(tree.children[0].mark, Mark::Type);
+        assert_eq!(tree.children[1].mark, Mark::Plain);
+        let tau = type_tree(&parse_type("forall a . rec l . unit + (a * l)").unwrap());
+        assert_eq!(labels(&tau), ["∀ a", "μ l", "+", "()", "*", "a", "l"]);
+    }
+
+    #[test]
+    fn layout() {
+        let program =
+            "if (1 + 2) * 3 < 4 then ((fun (f : num -> num) -> f 1) (fun (y : num) -> y)) else 5";
+        let svg = to_svg(&expr_tree(&parse(program).unwrap()));
+        let boxes = boxes(&svg);
+        assert_eq!(boxes.len(), 20);
+        // nodes on a level don't overlap
+        let mut levels: Vec<Vec<(f64, f64, f64)>> = Vec::new();
+        for &(x, y, width) in &boxes {
+            match levels.iter_mut().find(|level| level[0].1 == y) {
+                Some(level) => level.push((x, y, width)),
+                None => levels.push(vec![(x, y, width)]),
+            }
+        }
+        for level in &levels {
+            for pair in level.windows(2) {
+                assert!(pair[0].0 + pair[0].2 < pair[1].0, "{pair:?} overlap");
+            }
+        }
+        // parents are centered over their first and last child
+        let center = |(x, _, width): (f64, f64, f64)| x + width / 2.0;
+        let (root, cond, then_, else_) = (boxes[0], boxes[1], boxes[8], boxes[19]);
+        // up to the rounding of coordinates to one decimal
+        assert!((center(root) - (center(cond) + center(else_)) / 2.0).abs() < 0.1);
+        assert!(center(cond) < center(then_) && center(then_) < center(else_));
+        // the picture starts at the margin
+        let left = boxes.iter().map(|b| b.0).fold(f64::INFINITY, f64::min);
+        assert_eq!(left, 8.0);
+    }
+
+    #[test]
+    fn deep() {
+        let mut program = String::from("()");
+        for _ in 0..500 {
+            program = format!("(1, {program})");
+        }
+        let svg = to_svg(&expr_tree(&parse(&program).unwrap()));
+        assert_eq!(boxes(&svg).len(), 1001);
+        let html = to_html(&type_tree(&parse_type("num -> bool").unwrap()));
+        assert!(html.contains("classList.toggle('collapsed')"));
+        assert_eq!(html.matches("<g class=\"children\">").count(), 1);
+    }
+}
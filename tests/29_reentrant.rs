@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::dotgen::{to_dot, type_to_dot};
+    use interpreter::evaluate::eval;
+    use interpreter::flags::{Mode, OutputMode};
+    use interpreter::parser::{parse, parse_type};
+    use interpreter::svggen::{expr_tree, to_svg};
+    use std::collections::HashSet;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::thread;
+
+    const PROGRAM: &str = "(fun (x : num) -> (x + 1, inj x = L as num + bool)) 2";
+
+    fn render() -> Vec<String> {
+        let e = parse(PROGRAM).unwrap();
+        let tau = parse_type("rec l . unit + (num * l)").unwrap();
+        vec![
+            to_dot(&e, None),
+            to_dot(&e, Some(String::from("first"))),
+            type_to_dot(&tau, None),
+            to_svg(&expr_tree(&e)),
+            format!("{:?}", eval(&e, Mode::Eval, OutputMode::Full)),
+        ]
+    }
+
+    #[test]
+    fn deterministic() {
+        let first = render();
+        assert_eq!(render(), first);
+        // each graph numbers its nodes from its root
+        assert!(first[0].starts_with("digraph {\n\t0 [shape=point"));
+        assert!(first[1].starts_with("subgraph first {\n\tfirst_0 [shape=point"));
+        assert!(first[1].contains("first_0 -> first_1 [label=\"app\""));
+    }
+
+    #[test]
+    fn concurrent() {
+        let expected = render();
+        thread::scope(|scope| {
+            let threads: Vec<_> = (0..8).map(|_| scope.spawn(render)).collect();
+            for thread in threads {
+                assert_eq!(thread.join().unwrap(), expected);
+            }
+        });
+    }
+
+    #[test]
+    fn subgraphs() {
+        // the type, the steps and the result are drawn into one graph with distinct nodes
+        let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+            .args(["very-verbose", "graphviz"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(PROGRAM.as_bytes()).unwrap();
+        drop(stdin);
+        let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+        let subgraphs: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("subgraph "))
+            .collect();
+        assert_eq!(subgraphs, ["type {", "step1 {", "last {"]);
+        let nodes: Vec<&str> = output
+            .lines()
+            .filter(|line| line.contains("[shape=point"))
+            .map(|line| line.trim().split(' ').next().unwrap())
+            .collect();
+        assert_eq!(nodes.iter().collect::<HashSet<_>>().len(), nodes.len());
+    }
+}
@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use interpreter::golden::{run, test, Summary};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn programs() {
+        let mut out = Vec::new();
+        let summary = test(Path::new("tests/programs"), false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "7 passed, 0 failed\n");
+        assert_eq!(
+            summary,
+            Summary {
+                passed: 7,
+                failed: 0,
+                blessed: 0
+            }
+        );
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(run("(1, true)"), ["type: num * bool", "value: (1, true)"]);
+        assert_eq!(run("# one\n1 # and no more"), ["type: num", "value: 1"]);
+        assert_eq!(run("fun (x : num) -> "), ["error: parse"]);
+        assert_eq!(run("1 + true"), ["error: type"]);
+        assert_eq!(
+            run("(fun (x : ?) -> x + 1) true"),
+            ["type: num", "error: blame"]
+        );
+    }
+
+    #[test]
+    fn bless() {
+        let dir = std::env::temp_dir().join(format!("golden-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.lam"), "# add\n# value: 4\n2 + 3\n").unwrap();
+        fs::write(dir.join("b.lam"), "true\n").unwrap();
+        // only the expected keys are compared
+        fs::write(dir.join("c.lam"), "# type: num\n2 * 3\n").unwrap();
+        let mut out = Vec::new();
+        let summary = test(&dir, false, &mut out).unwrap();
+        assert_eq!((summary.passed, summary.failed), (1, 2));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "FAIL {0}/a.lam\n  - value: 4\n  + value: 5\nFAIL {0}/b.lam\n  no expectations\n  + type: bool\n  + value: true\n1 passed, 2 failed\n",
+                dir.display()
+            )
+        );
+        let summary = test(&dir, true, &mut Vec::new()).unwrap();
+        assert_eq!((summary.passed, summary.blessed), (1, 2));
+        assert_eq!(
+            fs::read_to_string(dir.join("a.lam")).unwrap(),
+            "# add\n# type: num\n# value: 5\n2 + 3\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("b.expected")).unwrap(),
+            "type: bool\nvalue: true\n"
+        );
+        let summary = test(&dir, false, &mut Vec::new()).unwrap();
+        assert_eq!((summary.passed, summary.failed), (3, 0));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
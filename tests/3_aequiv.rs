@@ -9,20 +9,28 @@ mod tests {
         let expr1 = parse("(fun (x : num) -> x) y").unwrap();
         assert!(Expr::alpha_equiv(
             *expr1.clone(),
-            expr1.clone().substitute(Variable::from("x"), Expr::Num(0))
+            expr1
+                .clone()
+                .substitute(Variable::from("x"), Expr::Num(0, NodeId::default()))
         ));
         assert!(Expr::alpha_equiv(
             *parse("(fun (x : num) -> x) 0").unwrap(),
-            expr1.clone().substitute(Variable::from("y"), Expr::Num(0))
+            expr1
+                .clone()
+                .substitute(Variable::from("y"), Expr::Num(0, NodeId::default()))
         ));
 
         let expr2 = parse("x + (fun (x : num) -> y)").unwrap();
         assert!(Expr::alpha_equiv(
-            expr2.clone().substitute(Variable::from("x"), Expr::Num(0)),
+            expr2
+                .clone()
+                .substitute(Variable::from("x"), Expr::Num(0, NodeId::default())),
             *parse("0 + (fun (x : num) -> y)").unwrap()
         ));
         assert!(Expr::alpha_equiv(
-            expr2.clone().substitute(Variable::from("y"), Expr::Num(0)),
+            expr2
+                .clone()
+                .substitute(Variable::from("y"), Expr::Num(0, NodeId::default())),
             *parse("x + (fun (x : num) -> 0)").unwrap()
         ));
 
@@ -41,23 +49,34 @@ mod tests {
     fn lec_2_example() {
         let expr1 = parse("(fun (z : num) -> x)").unwrap();
         assert!(Expr::alpha_equiv(
-            expr1.substitute(Variable::from("x"), Expr::Var("y".into())),
+            expr1.substitute(
+                Variable::from("x"),
+                Expr::Var("y".into(), NodeId::default())
+            ),
             *parse("(fun (z : num) -> y)").unwrap()
         ));
 
         let expr2 = parse("fun (y : num) -> (x y)").unwrap();
         assert!(Expr::alpha_equiv(
-            expr2.clone().substitute(Variable::from("x"), Expr::Var("y".into())),
+            expr2.clone().substitute(
+                Variable::from("x"),
+                Expr::Var("y".into(), NodeId::default())
+            ),
             *parse("fun (y_ : num) -> (y y_)").unwrap()
         ));
         assert!(Expr::alpha_equiv(
-            expr2.clone().substitute(Variable::from("x"), Expr::Num(0)),
+            expr2
+                .clone()
+                .substitute(Variable::from("x"), Expr::Num(0, NodeId::default())),
             *parse("fun (y_ : num) -> (0 y_)").unwrap()
         ));
 
         let expr3 = parse("x (fun (x : num) -> (x x))").unwrap();
         assert!(Expr::alpha_equiv(
-            expr3.substitute(Variable::from("x"), Expr::Var("y".into())),
+            expr3.substitute(
+                Variable::from("x"),
+                Expr::Var("y".into(), NodeId::default())
+            ),
             *parse("y (fun (x : num) -> (x x))").unwrap()
         ));
     }
@@ -76,7 +95,10 @@ mod tests {
             *parse("fun (x : num) -> 3 + x - 2 * x / z").unwrap(),
             parse("fun (x : num) -> 3 + y - 2 * x / z")
                 .unwrap()
-                .substitute(Variable::from("y"), Expr::Var("x".into())),
+                .substitute(
+                    Variable::from("y"),
+                    Expr::Var("x".into(), NodeId::default())
+                ),
         ));
     }
 
@@ -90,7 +112,10 @@ mod tests {
         assert!(Expr::alpha_equiv(
             parse("fun (x : num) -> if 1 && x then z < 0 || y < z else x == y")
                 .unwrap()
-                .substitute(Variable::from("y"), Expr::Var("x".into())),
+                .substitute(
+                    Variable::from("y"),
+                    Expr::Var("x".into(), NodeId::default())
+                ),
             *parse("fun (x_ : num) -> if 1 && x_ then z < 0 || x < z else x_ == x").unwrap()
         ));
 
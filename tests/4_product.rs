@@ -10,26 +10,38 @@ mod tests {
     #[test]
     fn eval_test() {
         let expr1 = parse("(1+2,3-4).L").unwrap();
-        assert_eq!(eval(&expr1, Mode::Eval, OutputMode::Full), Expr::Num(3));
+        assert_eq!(
+            eval(&expr1, Mode::Eval, OutputMode::Full),
+            Expr::Num(3, NodeId::default())
+        );
         assert_eq!(type_check(&expr1).unwrap(), Type::Num);
         let expr2 = parse("(1*2,3/4).R").unwrap();
-        assert_eq!(eval(&expr2, Mode::Eval, OutputMode::Full), Expr::Num(0));
+        assert_eq!(
+            eval(&expr2, Mode::Eval, OutputMode::Full),
+            Expr::Num(0, NodeId::default())
+        );
         assert_eq!(type_check(&expr2).unwrap(), Type::Num);
         let expr3 = parse("((1+2,3-4).L,(1*2,3/4).R).L").unwrap();
-        assert_eq!(eval(&expr3, Mode::Eval, OutputMode::Full), Expr::Num(3));
+        assert_eq!(
+            eval(&expr3, Mode::Eval, OutputMode::Full),
+            Expr::Num(3, NodeId::default())
+        );
         assert_eq!(type_check(&expr3).unwrap(), Type::Num);
         let expr4 = parse("(((),(1,2)),())").unwrap();
         assert_eq!(
             eval(&expr4, Mode::Eval, OutputMode::Full),
             Expr::Pair {
                 left: Box::new(Expr::Pair {
-                    left: Box::new(Expr::Unit),
+                    left: Box::new(Expr::Unit(NodeId::default())),
                     right: Box::new(Expr::Pair {
-                        left: Box::new(Expr::Num(1)),
-                        right: Box::new(Expr::Num(2))
-                    })
+                        left: Box::new(Expr::Num(1, NodeId::default())),
+                        right: Box::new(Expr::Num(2, NodeId::default())),
+                        id: NodeId::default()
+                    }),
+                    id: NodeId::default()
                 }),
-                right: Box::new(Expr::Unit)
+                right: Box::new(Expr::Unit(NodeId::default())),
+                id: NodeId::default()
             }
         );
         assert_eq!(
@@ -51,11 +63,16 @@ mod tests {
     fn aequiv() {
         let expr1 = parse("(((),(x,2)),(y,x)).L").unwrap();
         assert!(Expr::alpha_equiv(
-            expr1.clone().substitute(Variable::from("x"), Expr::Num(0)),
+            expr1
+                .clone()
+                .substitute(Variable::from("x"), Expr::Num(0, NodeId::default())),
             *parse("(((),(0,2)),(y,0)).L").unwrap()
         ));
         assert!(Expr::alpha_equiv(
-            expr1.clone().substitute(Variable::from("x"), Expr::Var("y".into())),
+            expr1.clone().substitute(
+                Variable::from("x"),
+                Expr::Var("y".into(), NodeId::default())
+            ),
             *parse("(((),(y,2)),(y,y)).L").unwrap()
         ));
     }
@@ -18,7 +18,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&fact, Mode::Eval, OutputMode::Full), Expr::Num(120));
+        assert_eq!(
+            eval(&fact, Mode::Eval, OutputMode::Full),
+            Expr::Num(120, NodeId::default())
+        );
         assert_eq!(type_check(&fact).unwrap(), Type::Num);
     }
 
@@ -34,7 +37,10 @@ mod tests {
         )
         .unwrap();
         assert!(Expr::alpha_equiv(
-            fact.clone().substitute(Variable::from("n"), Expr::Var("t".into())),
+            fact.clone().substitute(
+                Variable::from("n"),
+                Expr::Var("t".into(), NodeId::default())
+            ),
             *fact
         ));
     }
@@ -16,7 +16,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&poly, Mode::Eval, OutputMode::Full), Expr::Num(100));
+        assert_eq!(
+            eval(&poly, Mode::Eval, OutputMode::Full),
+            Expr::Num(100, NodeId::default())
+        );
         assert_eq!(type_check(&poly).unwrap(), Type::Num);
         let poly = parse(
             r#"
@@ -25,7 +28,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&poly, Mode::Eval, OutputMode::Full), Expr::Num(100));
+        assert_eq!(
+            eval(&poly, Mode::Eval, OutputMode::Full),
+            Expr::Num(100, NodeId::default())
+        );
         assert_eq!(type_check(&poly).unwrap(), Type::Num);
         let opt = parse(
             r#"
@@ -40,7 +46,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&opt, Mode::Eval, OutputMode::Full), Expr::Num(2));
+        assert_eq!(
+            eval(&opt, Mode::Eval, OutputMode::Full),
+            Expr::Num(2, NodeId::default())
+        );
         assert_eq!(type_check(&opt).unwrap(), Type::Num);
     }
 
@@ -125,4 +134,29 @@ mod tests {
             *parse_type("forall x . forall y . x -> y").unwrap()
         ));
     }
+
+    #[test]
+    fn locally_nameless() {
+        // binder names are only hints: equality is alpha-equivalence
+        assert_eq!(
+            *parse_type("forall a . exists b . rec c . a * b * c").unwrap(),
+            *parse_type("forall x . exists y . rec z . x * y * z").unwrap()
+        );
+        assert_ne!(
+            *parse_type("forall a . forall b . a").unwrap(),
+            *parse_type("forall a . forall b . b").unwrap()
+        );
+        // instantiation needs no renaming, and printing picks unambiguous names
+        let Type::Forall { tau, .. } = *parse_type("forall a . forall b . a -> b").unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(
+            tau.open(&parse_type("b").unwrap()).to_string(),
+            "∀ b' . b → b'"
+        );
+        assert_eq!(
+            parse_type("forall a . forall a . a").unwrap().to_string(),
+            "∀ a . ∀ a' . a'"
+        );
+    }
 }
@@ -19,7 +19,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&objrec, Mode::Eval, OutputMode::Full), Expr::Num(0));
+        assert_eq!(
+            eval(&objrec, Mode::Eval, OutputMode::Full),
+            Expr::Num(0, NodeId::default())
+        );
         assert_eq!(type_check(&objrec).unwrap(), Type::Num);
         let counter = parse(
             r#"
@@ -34,7 +37,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&counter, Mode::Eval, OutputMode::Full), Expr::Num(2));
+        assert_eq!(
+            eval(&counter, Mode::Eval, OutputMode::Full),
+            Expr::Num(2, NodeId::default())
+        );
         assert_eq!(type_check(&counter).unwrap(), Type::Num);
     }
 
@@ -24,7 +24,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&objmod, Mode::Eval, OutputMode::Full), Expr::Num(0));
+        assert_eq!(
+            eval(&objmod, Mode::Eval, OutputMode::Full),
+            Expr::Num(0, NodeId::default())
+        );
         assert_eq!(type_check(&objmod).unwrap(), Type::Num);
         let objmod = parse(
             r#"
@@ -42,7 +45,10 @@ mod tests {
             "#,
         )
         .unwrap();
-        assert_eq!(eval(&objmod, Mode::Eval, OutputMode::Full), Expr::Num(0));
+        assert_eq!(
+            eval(&objmod, Mode::Eval, OutputMode::Full),
+            Expr::Num(0, NodeId::default())
+        );
         assert_eq!(type_check(&objmod).unwrap(), Type::Num);
     }
 
@@ -0,0 +1,27 @@
+//! Helpers shared by the test files that include this module, each of which uses only some
+
+#![allow(dead_code)]
+
+/// xorshift64: deterministic so failures are reproducible. Test files add their own
+/// generators of terms and types.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+/// The type of lists of numbers
+pub const LIST: &str = "rec l . unit + (num * l)";
+
+/// A list of numbers in source notation
+pub fn list(elements: &[i32]) -> String {
+    let tail = format!("fold inj () = L as unit + (num * ({LIST})) as {LIST}");
+    elements.iter().rev().fold(tail, |tail, n| {
+        format!("fold inj ({n}, {tail}) = R as unit + (num * ({LIST})) as {LIST}")
+    })
+}
@@ -85,5 +85,24 @@ mod tests {
         assert!(type_check(&existential).is_err());
         let import = parse("import (x, a) = 2 in a").unwrap();
         assert!(type_check(&import).is_err());
+        let escape = parse("import (x, b) = (export 1 without num as exists a . a) in x").unwrap();
+        let err = type_check(&escape).unwrap_err();
+        assert!(err.contains("Abstract type `b` escapes"), "{err}");
+    }
+
+    #[test]
+    fn well_formedness() {
+        let unbound = parse("fun (x : zzz) -> x").unwrap();
+        let err = type_check(&unbound).unwrap_err();
+        assert!(err.contains("Unbound type variable `zzz`"), "{err}");
+        let inj = parse("inj 1=L as num+a").unwrap();
+        assert!(type_check(&inj).is_err());
+        let tyapp = parse("(tyfun a -> fun (x : a) -> x) [b]").unwrap();
+        assert!(type_check(&tyapp).is_err());
+        let bound =
+            parse("tyfun a -> fun (x : a) -> fun (f : forall b . b -> a) -> f [num] 1").unwrap();
+        assert!(type_check(&bound).is_ok());
+        let rec = parse("fun (l : rec t . unit + num * t) -> l").unwrap();
+        assert!(type_check(&rec).is_ok());
     }
 }